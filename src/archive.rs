@@ -0,0 +1,233 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use flate2::{write::GzEncoder, Compression};
+use tokio::sync::mpsc;
+
+use crate::{
+    config::Layout, error::APIError, scanner, state::AppState, versions::existing_version_directory,
+};
+
+// the whole version as `{version}/...` in a gzipped tarball, compressed while
+// it streams so nothing is buffered on disk or in memory
+pub async fn get_version_archive(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<Response, APIError> {
+    let version_directory = existing_version_directory(&state, &version).await?;
+
+    // platform folders of the flat layout live outside the version folder
+    let mut roots = vec![(version_directory, version.clone())];
+    if state.config.layout == Layout::Flat {
+        for platform in &state.config.platforms {
+            if let Some(directory) =
+                scanner::locate_platform_directory(&state.config, &version, platform).await?
+            {
+                roots.push((directory, format!("{}/{}", version, platform)));
+            }
+        }
+    }
+
+    let (sender, receiver) = mpsc::channel::<io::Result<Bytes>>(8);
+    let in_flight = state.start_download();
+    let archived = version.clone();
+    let level = Compression::new(state.config.archive_compression_level);
+    tokio::task::spawn_blocking(move || {
+        let _in_flight = in_flight;
+        if let Err(e) = write_archive(ChannelWriter(sender.clone()), &roots, level) {
+            // a closed channel means the client went away
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                tracing::error!("failed to archive version {}: {}", archived, e);
+                let _ = sender.blocking_send(Err(e));
+            }
+        }
+    });
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|chunk| (chunk, receiver))
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.tar.gz\"", version),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
+fn write_archive(
+    writer: ChannelWriter,
+    roots: &[(PathBuf, String)],
+    level: Compression,
+) -> io::Result<()> {
+    let encoder = GzEncoder::new(BufWriter::with_capacity(64 * 1024, writer), level);
+    let mut builder = tar::Builder::new(encoder);
+    for (directory, name) in roots {
+        append_directory(&mut builder, directory, name)?;
+    }
+
+    builder.into_inner()?.finish()?.flush()
+}
+
+// markers and other dotfiles as well as unfinished uploads are left out
+fn append_directory<W: Write>(
+    builder: &mut tar::Builder<W>,
+    directory: &FsPath,
+    name: &str,
+) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(directory)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let Some(file_name) = entry.file_name().to_str().map(String::from) else {
+            continue;
+        };
+        let is_part = FsPath::new(&file_name)
+            .extension()
+            .is_some_and(|extension| extension == scanner::PART_EXTENSION);
+        if file_name.starts_with('.') || is_part {
+            continue;
+        }
+
+        let member = format!("{}/{}", name, file_name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            append_directory(builder, &entry.path(), &member)?;
+        } else if file_type.is_file() {
+            builder.append_path_with_name(entry.path(), &member)?;
+        }
+    }
+
+    Ok(())
+}
+
+// hands the compressed bytes to the response body, the bounded channel keeps
+// the blocking writer from running ahead of a slow client
+struct ChannelWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    #[tokio::test]
+    async fn streams_the_version_as_a_gzipped_tarball() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.write("1.0.0/.yanked", "");
+        kiosk.write("1.0.0/linux_x86_64/next.tar.gz.part", "half");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let response = get_version_archive(State(state), Path("1.0.0".into()))
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/gzip");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        let mut archive = tar::Archive::new(GzDecoder::new(&body[..]));
+        let mut members = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            members.push((entry.path().unwrap().display().to_string(), content));
+        }
+
+        let names: Vec<&str> = members.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "1.0.0/darwin_aarch64/app-darwin_aarch64.tar.gz",
+                "1.0.0/darwin_aarch64/app-darwin_aarch64.tar.gz.sig",
+                "1.0.0/darwin_x86_64/app-darwin_x86_64.tar.gz",
+                "1.0.0/darwin_x86_64/app-darwin_x86_64.tar.gz.sig",
+                "1.0.0/linux_x86_64/app-linux_x86_64.tar.gz",
+                "1.0.0/linux_x86_64/app-linux_x86_64.tar.gz.sig",
+                "1.0.0/notes.txt",
+                "1.0.0/windows_x86_64/app-windows_x86_64.tar.gz",
+                "1.0.0/windows_x86_64/app-windows_x86_64.tar.gz.sig",
+            ]
+        );
+        assert!(members.contains(&(
+            "1.0.0/linux_x86_64/app-linux_x86_64.tar.gz".to_string(),
+            "bin 1.0.0 linux_x86_64".to_string()
+        )));
+        assert!(members.contains(&(
+            "1.0.0/linux_x86_64/app-linux_x86_64.tar.gz.sig".to_string(),
+            test_support::SIGNATURE.to_string()
+        )));
+    }
+
+    #[tokio::test]
+    async fn decodes_at_every_compression_extreme() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.write(
+            "1.0.0/linux_x86_64/app-linux_x86_64.tar.gz",
+            &"bin ".repeat(4096),
+        );
+
+        let mut sizes = Vec::new();
+        for level in ["0", "9"] {
+            let state = AppState::build(kiosk.config(&[("ARCHIVE_COMPRESSION_LEVEL", level)]))
+                .await
+                .unwrap();
+            let response = get_version_archive(State(state), Path("1.0.0".into()))
+                .await
+                .unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            sizes.push(body.len());
+
+            let mut archive = tar::Archive::new(GzDecoder::new(&body[..]));
+            let mut entry = archive
+                .entries()
+                .unwrap()
+                .map(Result::unwrap)
+                .find(|entry| {
+                    entry.path().unwrap().to_str()
+                        == Some("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz")
+                })
+                .unwrap();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            assert_eq!(content, "bin ".repeat(4096), "level {}", level);
+        }
+
+        // level 0 stores the tarball as is
+        assert!(sizes[0] > sizes[1], "{:?}", sizes);
+    }
+}
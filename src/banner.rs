@@ -0,0 +1,103 @@
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::config::{Config, MessageSeverity};
+
+// optional file in KIOSK_DIRECTORY, `{"message": "...", "severity": "warning"}`,
+// read on every request so a banner can be changed without a restart
+pub const MESSAGE_FILE: &str = "message.json";
+
+#[derive(Deserialize)]
+struct MessageFile {
+    message: String,
+    #[serde(default)]
+    severity: Option<MessageSeverity>,
+}
+
+// the banner shown by clients, MESSAGE wins over the message file
+pub async fn current_message(config: &Config) -> Option<(String, MessageSeverity)> {
+    if let Some(message) = &config.message {
+        return Some((message.clone(), config.message_severity));
+    }
+
+    let path = std::path::Path::new(&config.kiosk_directory).join(MESSAGE_FILE);
+    let content = match fs::read_to_string(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            tracing::warn!("failed to read {}: {}", path.display(), e);
+            return None;
+        }
+    };
+    match serde_json::from_str::<MessageFile>(&content) {
+        Ok(file) if !file.message.trim().is_empty() => Some((
+            file.message,
+            file.severity.unwrap_or(config.message_severity),
+        )),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("ignoring invalid {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    #[tokio::test]
+    async fn the_environment_beats_the_file() {
+        let kiosk = Kiosk::new();
+        kiosk.write(
+            MESSAGE_FILE,
+            r#"{"message": "from the file", "severity": "critical"}"#,
+        );
+
+        let config = kiosk.config(&[("MESSAGE", "from the env"), ("MESSAGE_SEVERITY", "warning")]);
+        assert_eq!(
+            current_message(&config).await,
+            Some(("from the env".to_string(), MessageSeverity::Warning))
+        );
+        assert_eq!(
+            current_message(&kiosk.config(&[])).await,
+            Some(("from the file".to_string(), MessageSeverity::Critical))
+        );
+    }
+
+    #[tokio::test]
+    async fn a_blank_or_invalid_file_shows_no_banner() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[]);
+        assert_eq!(current_message(&config).await, None);
+
+        for content in [
+            r#"{"message": "  "}"#,
+            "",
+            "not json",
+            r#"{"severity": "info"}"#,
+            r#"{"message": "hi", "severity": "loud"}"#,
+        ] {
+            kiosk.write(MESSAGE_FILE, content);
+            assert_eq!(current_message(&config).await, None, "{}", content);
+        }
+    }
+
+    #[tokio::test]
+    async fn the_severity_defaults_to_message_severity() {
+        let kiosk = Kiosk::new();
+        kiosk.write(MESSAGE_FILE, r#"{"message": "maintenance tonight"}"#);
+
+        assert_eq!(
+            current_message(&kiosk.config(&[("MESSAGE_SEVERITY", "critical")])).await,
+            Some(("maintenance tonight".to_string(), MessageSeverity::Critical))
+        );
+        assert_eq!(
+            current_message(&kiosk.config(&[]))
+                .await
+                .map(|(_, severity)| severity),
+            Some(MessageSeverity::Info)
+        );
+    }
+}
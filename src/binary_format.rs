@@ -0,0 +1,191 @@
+use std::path::Path;
+
+use tokio::io::AsyncReadExt;
+
+use crate::error::{APIError, BinaryFormatMismatch};
+
+// what the first bytes of an artifact say it is, archives like the .tar.gz
+// update bundles don't tell which os they are for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryFormat {
+    Pe,
+    Msi,
+    Elf,
+    AppImage,
+    MachO,
+}
+
+impl BinaryFormat {
+    fn detect(header: &[u8]) -> Option<Self> {
+        match header {
+            [0x7f, b'E', b'L', b'F', _, _, _, _, b'A', b'I', 0x02, ..] => Some(Self::AppImage),
+            [0x7f, b'E', b'L', b'F', ..] => Some(Self::Elf),
+            [b'M', b'Z', ..] => Some(Self::Pe),
+            [0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1, ..] => Some(Self::Msi),
+            [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..]
+            | [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..]
+            | [0xca, 0xfe, 0xba, 0xbe, ..] => Some(Self::MachO),
+            _ => None,
+        }
+    }
+
+    // the format a file name promises, None for archives and disk images
+    fn for_extension(filename: &str) -> Option<Self> {
+        let extension = Path::new(filename).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "exe" => Some(Self::Pe),
+            "msi" => Some(Self::Msi),
+            "appimage" => Some(Self::AppImage),
+            _ => None,
+        }
+    }
+
+    fn os(self) -> &'static str {
+        match self {
+            Self::Pe | Self::Msi => "windows",
+            Self::Elf | Self::AppImage => "linux",
+            Self::MachO => "darwin",
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Pe => "windows executable (MZ)",
+            Self::Msi => "windows installer (msi)",
+            Self::Elf => "linux executable (ELF)",
+            Self::AppImage => "linux AppImage",
+            Self::MachO => "macos executable (Mach-O)",
+        }
+    }
+}
+
+// the os part of a platform name, `windows` for windows_x86_64
+fn platform_os(platform: &str) -> &str {
+    match platform.split('_').next().unwrap_or(platform) {
+        "macos" => "darwin",
+        os => os,
+    }
+}
+
+// rejects a binary that can't run on the platform it was uploaded for, e.g.
+// the linux build in the windows folder. a file whose header doesn't match
+// what its extension promises is rejected too
+pub async fn check(artifact: &Path, filename: &str, platform: &str) -> Result<(), APIError> {
+    let mut header = Vec::with_capacity(16);
+    tokio::fs::File::open(artifact)
+        .await?
+        .take(16)
+        .read_to_end(&mut header)
+        .await?;
+    let detected = BinaryFormat::detect(&header);
+
+    let mismatch = |expected: String| {
+        tracing::warn!(
+            "refused {} for {}, expected {} but found {}",
+            filename,
+            platform,
+            expected,
+            detected.map_or("an unknown format", BinaryFormat::describe)
+        );
+        Err(APIError::BinaryFormatMismatch(BinaryFormatMismatch {
+            filename: filename.to_string(),
+            platform: platform.to_string(),
+            expected,
+            found: detected.map(|format| format.describe().to_string()),
+        }))
+    };
+
+    if let Some(expected) = BinaryFormat::for_extension(filename) {
+        if detected != Some(expected) {
+            return mismatch(expected.describe().to_string());
+        }
+    }
+    if let Some(detected) = detected {
+        let os = platform_os(platform);
+        if detected.os() != os {
+            return mismatch(format!("a {} binary", os));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PE: &[u8] = b"MZ\x90\x00\x03\x00\x00\x00\x04\x00\x00\x00";
+    const MSI: &[u8] = &[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1, 0, 0, 0, 0];
+    const ELF: &[u8] = b"\x7fELF\x02\x01\x01\x00\x00\x00\x00\x00";
+    const APPIMAGE: &[u8] = b"\x7fELF\x02\x01\x01\x00AI\x02\x00";
+    const MACHO: &[u8] = &[0xcf, 0xfa, 0xed, 0xfe, 0x07, 0, 0, 0x01, 0, 0, 0, 0];
+
+    async fn check_bytes(header: &[u8], filename: &str, platform: &str) -> Result<(), APIError> {
+        let dir = tempfile::tempdir().unwrap();
+        let artifact = dir.path().join(filename);
+        std::fs::write(&artifact, header).unwrap();
+        check(&artifact, filename, platform).await
+    }
+
+    async fn mismatch(header: &[u8], filename: &str, platform: &str) -> BinaryFormatMismatch {
+        match check_bytes(header, filename, platform).await {
+            Err(APIError::BinaryFormatMismatch(mismatch)) => mismatch,
+            other => panic!("{} for {} wasn't refused: {:?}", filename, platform, other),
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_binaries_for_their_platform() {
+        for (header, filename, platform) in [
+            (PE, "app.exe", "windows_x86_64"),
+            (MSI, "app.msi", "windows_x86_64"),
+            (APPIMAGE, "app.AppImage", "linux_x86_64"),
+            (ELF, "app.tar.gz", "linux_x86_64"),
+            (MACHO, "app.tar.gz", "darwin_aarch64"),
+            (MACHO, "app.tar.gz", "macos_x86_64"),
+            // gzip says nothing about the os
+            (b"\x1f\x8b\x08\x00", "app.tar.gz", "windows_x86_64"),
+        ] {
+            assert!(
+                check_bytes(header, filename, platform).await.is_ok(),
+                "{} for {}",
+                filename,
+                platform
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_a_binary_for_another_platform() {
+        let refused = mismatch(ELF, "app.tar.gz", "windows_x86_64").await;
+        assert_eq!(refused.expected, "a windows binary");
+        assert_eq!(refused.found.as_deref(), Some("linux executable (ELF)"));
+
+        let refused = mismatch(PE, "app.tar.gz", "linux_x86_64").await;
+        assert_eq!(refused.expected, "a linux binary");
+        assert_eq!(refused.found.as_deref(), Some("windows executable (MZ)"));
+
+        for platform in ["darwin_x86_64", "darwin_aarch64"] {
+            let refused = mismatch(APPIMAGE, "app.tar.gz", platform).await;
+            assert_eq!(refused.platform, platform);
+            assert_eq!(refused.expected, "a darwin binary");
+            assert_eq!(refused.found.as_deref(), Some("linux AppImage"));
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_a_header_the_extension_doesnt_promise() {
+        let refused = mismatch(ELF, "app.exe", "windows_x86_64").await;
+        assert_eq!(refused.expected, "windows executable (MZ)");
+
+        let refused = mismatch(PE, "app.msi", "windows_x86_64").await;
+        assert_eq!(refused.expected, "windows installer (msi)");
+
+        // a plain ELF lacks the AppImage marker
+        let refused = mismatch(ELF, "app.AppImage", "linux_x86_64").await;
+        assert_eq!(refused.expected, "linux AppImage");
+
+        let refused = mismatch(b"not a binary", "app.exe", "windows_x86_64").await;
+        assert_eq!(refused.found, None);
+    }
+}
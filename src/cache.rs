@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+use sea_orm::sqlx::types::chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+
+use crate::{stats::StatsResponse, KioskVersionResponse};
+
+// the computed latest version manifest per query profile, entries expire
+// after `ttl` so artifacts copied into KIOSK_DIRECTORY out of band still show up
+pub type LatestVersionCache = KeyedTtlCache<ManifestKey, KioskVersionResponse>;
+
+// the query params that change what /latest-version resolves, normalized so
+// equivalent requests share an entry. the default key is a plain poll
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ManifestKey {
+    pub as_of: Option<DateTime<Utc>>,
+    pub languages: Vec<String>,
+}
+
+pub type StatsCache = TtlCache<StatsResponse>;
+
+pub struct TtlCache<T> {
+    ttl: Duration,
+    entry: RwLock<Option<CachedEntry<T>>>,
+}
+
+struct CachedEntry<T> {
+    value: T,
+    cached_at: Instant,
+}
+
+impl<T: Clone> TtlCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: RwLock::new(None),
+        }
+    }
+
+    pub async fn get(&self) -> Option<T> {
+        let entry = self.entry.read().await;
+        entry
+            .as_ref()
+            .filter(|cached| cached.cached_at.elapsed() < self.ttl)
+            .map(|cached| cached.value.clone())
+    }
+
+    pub async fn set(&self, value: T) {
+        *self.entry.write().await = Some(CachedEntry {
+            value,
+            cached_at: Instant::now(),
+        });
+    }
+
+    pub async fn invalidate(&self) {
+        *self.entry.write().await = None;
+    }
+
+    pub async fn is_populated(&self) -> bool {
+        self.get().await.is_some()
+    }
+}
+
+// a TtlCache per key, holding at most `capacity` entries. a full map drops its
+// expired entries first and the oldest one if that wasn't enough
+pub struct KeyedTtlCache<K, T> {
+    ttl: Duration,
+    capacity: usize,
+    entries: RwLock<HashMap<K, CachedEntry<T>>>,
+}
+
+impl<K: Eq + Hash + Clone, T: Clone> KeyedTtlCache<K, T> {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get(&self, key: &K) -> Option<T> {
+        let entries = self.entries.read().await;
+        entries
+            .get(key)
+            .filter(|cached| cached.cached_at.elapsed() < self.ttl)
+            .map(|cached| cached.value.clone())
+    }
+
+    pub async fn set(&self, key: K, value: T) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.write().await;
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, cached| cached.cached_at.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.cached_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            CachedEntry {
+                value,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    pub async fn invalidate(&self) {
+        self.entries.write().await.clear();
+    }
+
+    // drops the entries whose value matches, the rest stay cached
+    pub async fn evict(&self, matches: impl Fn(&T) -> bool) -> usize {
+        let mut entries = self.entries.write().await;
+        let before = entries.len();
+        entries.retain(|_, cached| !matches(&cached.value));
+        before - entries.len()
+    }
+
+    pub async fn is_populated(&self, key: &K) -> bool {
+        self.get(key).await.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_an_entry_per_key() {
+        let cache = KeyedTtlCache::new(Duration::from_secs(60), 8);
+        cache.set("a", 1).await;
+        cache.set("b", 2).await;
+
+        assert_eq!(cache.get(&"a").await, Some(1));
+        assert_eq!(cache.get(&"b").await, Some(2));
+
+        cache.invalidate().await;
+        assert_eq!(cache.get(&"a").await, None);
+        assert_eq!(cache.get(&"b").await, None);
+    }
+
+    #[tokio::test]
+    async fn a_full_cache_drops_the_oldest_entry() {
+        let cache = KeyedTtlCache::new(Duration::from_secs(60), 2);
+        cache.set("a", 1).await;
+        cache.set("b", 2).await;
+        cache.set("c", 3).await;
+
+        assert_eq!(cache.get(&"a").await, None);
+        assert_eq!(cache.get(&"b").await, Some(2));
+        assert_eq!(cache.get(&"c").await, Some(3));
+    }
+
+    #[tokio::test]
+    async fn entries_expire_after_the_ttl() {
+        let cache = KeyedTtlCache::new(Duration::ZERO, 2);
+        cache.set("a", 1).await;
+
+        assert!(!cache.is_populated(&"a").await);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use base64::Engine;
+use lru::LruCache;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{self, File},
+    io::AsyncReadExt,
+};
+
+// extension of the sidecar caching an artifact's Content-MD5
+pub const MD5_EXTENSION: &str = "md5";
+
+// extension of the sidecar holding the sha256 the integrity check compares
+// against
+pub const SHA256_EXTENSION: &str = "sha256";
+
+// hex encoded sha256 of a file, read in chunks so large installers aren't
+// loaded into memory
+pub async fn sha256_file(path: &Path) -> std::io::Result<String> {
+    Ok(hex::encode(digest_file::<Sha256>(path).await?))
+}
+
+async fn digest_file<D: Digest>(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path).await?;
+    let mut hasher = D::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().to_vec())
+}
+
+pub fn sidecar(path: &Path, extension: &str) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(format!(".{}", extension));
+    PathBuf::from(sidecar)
+}
+
+// base64 md5 for the Content-MD5 header, cached next to the file and
+// recomputed when the file is newer than its sidecar (e.g. after an overwrite)
+pub async fn content_md5(path: &Path) -> std::io::Result<String> {
+    let sidecar = sidecar(path, MD5_EXTENSION);
+    let modified = fs::metadata(path).await?.modified()?;
+    if let Ok(metadata) = fs::metadata(&sidecar).await {
+        if metadata.modified()? >= modified {
+            return Ok(fs::read_to_string(&sidecar).await?.trim().to_string());
+        }
+    }
+
+    let md5 = base64::engine::general_purpose::STANDARD.encode(digest_file::<Md5>(path).await?);
+    // a failed write only costs a recomputation on the next download
+    if let Err(e) = fs::write(&sidecar, &md5).await {
+        tracing::warn!("failed to cache md5 in {}: {}", sidecar.display(), e);
+    }
+
+    Ok(md5)
+}
+
+struct CachedHash {
+    modified: SystemTime,
+    size: u64,
+    hash: String,
+}
+
+// hashes already computed by this process so repeated downloads and diffs
+// don't even read the sidecar. an entry only counts while the file keeps the
+// modification time and size it was hashed at, HASH_CACHE_CAPACITY of 0
+// turns it off
+pub struct HashCache {
+    entries: Option<Mutex<LruCache<(PathBuf, &'static str), CachedHash>>>,
+}
+
+impl HashCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: NonZeroUsize::new(capacity)
+                .map(|capacity| Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    pub async fn content_md5(&self, path: &Path) -> std::io::Result<String> {
+        self.get_or_compute(path, MD5_EXTENSION, content_md5(path))
+            .await
+    }
+
+    pub async fn sha256(&self, path: &Path) -> std::io::Result<String> {
+        self.get_or_compute(path, SHA256_EXTENSION, sha256_file(path))
+            .await
+    }
+
+    // the md5 computed earlier if the file is unchanged since, never reads the
+    // file itself so a HEAD request stays cheap
+    pub async fn peek_content_md5(&self, path: &Path) -> std::io::Result<Option<String>> {
+        let Some(entries) = &self.entries else {
+            return Ok(None);
+        };
+        let metadata = fs::metadata(path).await?;
+        let (modified, size) = (metadata.modified()?, metadata.len());
+
+        Ok(entries
+            .lock()
+            .unwrap()
+            .get(&(path.to_path_buf(), MD5_EXTENSION))
+            .filter(|cached| cached.modified == modified && cached.size == size)
+            .map(|cached| cached.hash.clone()))
+    }
+
+    async fn get_or_compute(
+        &self,
+        path: &Path,
+        algorithm: &'static str,
+        compute: impl std::future::Future<Output = std::io::Result<String>>,
+    ) -> std::io::Result<String> {
+        let Some(entries) = &self.entries else {
+            return compute.await;
+        };
+        let metadata = fs::metadata(path).await?;
+        let (modified, size) = (metadata.modified()?, metadata.len());
+        let key = (path.to_path_buf(), algorithm);
+
+        {
+            let mut entries = entries.lock().unwrap();
+            match entries.get(&key) {
+                Some(cached) if cached.modified == modified && cached.size == size => {
+                    return Ok(cached.hash.clone());
+                }
+                // the file was replaced since it was hashed
+                Some(_) => {
+                    entries.pop(&key);
+                }
+                None => {}
+            }
+        }
+
+        let hash = compute.await?;
+        entries.lock().unwrap().put(
+            key,
+            CachedHash {
+                modified,
+                size,
+                hash: hash.clone(),
+            },
+        );
+
+        Ok(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    #[tokio::test]
+    async fn peeking_never_computes_a_hash() {
+        let kiosk = Kiosk::new();
+        kiosk.write("app.exe", "bin");
+        let path = kiosk.path().join("app.exe");
+        let cache = HashCache::new(8);
+
+        assert_eq!(cache.peek_content_md5(&path).await.unwrap(), None);
+        assert!(!sidecar(&path, MD5_EXTENSION).exists());
+
+        let md5 = cache.content_md5(&path).await.unwrap();
+        assert_eq!(md5, "wREb1RKynoIbEguGRGAmuA==");
+        assert_eq!(cache.peek_content_md5(&path).await.unwrap(), Some(md5));
+
+        // a replaced file no longer matches what was hashed
+        kiosk.write("app.exe", "new bin");
+        assert_eq!(cache.peek_content_md5(&path).await.unwrap(), None);
+    }
+}
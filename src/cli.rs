@@ -0,0 +1,163 @@
+use clap::{Parser, Subcommand};
+
+use crate::{config::Config, error::APIError, publish, scanner};
+
+#[derive(Parser)]
+#[command(version, about = "Kiosk update server")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (default)
+    Serve,
+    /// Create a version folder with its notes
+    Publish {
+        #[arg(long)]
+        version: String,
+        #[arg(long, default_value = "")]
+        notes: String,
+    },
+    /// List version folders, latest first
+    List,
+    /// Delete a version folder and its artifacts
+    Delete {
+        #[arg(long)]
+        version: String,
+    },
+}
+
+// runs everything except `serve` directly against KIOSK_DIRECTORY, returning
+// the process exit code
+pub async fn run(command: Command, config: &Config) -> i32 {
+    let result = match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Publish { version, notes } => publish::create_version(config, &version, &notes)
+            .await
+            .map(|_| println!("created version {}", version)),
+        Command::List => list(config).await,
+        Command::Delete { version } => publish::delete_version(config, &version)
+            .await
+            .map(|_| println!("deleted version {}", version)),
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            let code: &'static str = (&e).into();
+            eprintln!("command failed: {}", code);
+            1
+        }
+    }
+}
+
+async fn list(config: &Config) -> Result<(), APIError> {
+    let folders = scanner::list_versions(config).await?;
+    for (_, name) in folders.versions {
+        let version_directory = std::path::Path::new(&config.kiosk_directory).join(&name);
+        if scanner::is_yanked(&version_directory).await {
+            println!("{} (yanked)", name);
+        } else {
+            println!("{}", name);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    #[tokio::test]
+    async fn publish_creates_the_version_folder() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[]);
+
+        let code = run(
+            Command::Publish {
+                version: "1.0.0".into(),
+                notes: "first".into(),
+            },
+            &config,
+        )
+        .await;
+
+        assert_eq!(code, 0);
+        let notes = std::fs::read_to_string(kiosk.path().join("1.0.0").join(scanner::NOTES_FILE));
+        assert_eq!(notes.unwrap(), "first");
+        for platform in &config.platforms {
+            assert!(kiosk.path().join("1.0.0").join(platform).is_dir());
+        }
+    }
+
+    #[tokio::test]
+    async fn publish_fails_for_an_existing_or_invalid_version() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let config = kiosk.config(&[]);
+
+        let existing = Command::Publish {
+            version: "1.0.0".into(),
+            notes: String::new(),
+        };
+        let invalid = Command::Publish {
+            version: "latest".into(),
+            notes: String::new(),
+        };
+
+        assert_eq!(run(existing, &config).await, 1);
+        assert_eq!(run(invalid, &config).await, 1);
+        assert!(!kiosk.path().join("latest").exists());
+    }
+
+    #[tokio::test]
+    async fn list_succeeds_with_and_without_versions() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[]);
+        assert_eq!(run(Command::List, &config).await, 0);
+
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        assert_eq!(run(Command::List, &config).await, 0);
+    }
+
+    #[tokio::test]
+    async fn delete_removes_the_version_folder() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        let config = kiosk.config(&[]);
+
+        let code = run(
+            Command::Delete {
+                version: "1.0.0".into(),
+            },
+            &config,
+        )
+        .await;
+
+        assert_eq!(code, 0);
+        assert!(!kiosk.path().join("1.0.0").exists());
+        assert!(kiosk.path().join("1.1.0").exists());
+    }
+
+    #[tokio::test]
+    async fn delete_fails_for_a_missing_version() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let config = kiosk.config(&[]);
+
+        let missing = Command::Delete {
+            version: "2.0.0".into(),
+        };
+        let outside = Command::Delete {
+            version: "../1.0.0".into(),
+        };
+
+        assert_eq!(run(missing, &config).await, 1);
+        assert_eq!(run(outside, &config).await, 1);
+        assert!(kiosk.path().join("1.0.0").exists());
+    }
+}
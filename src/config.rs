@@ -0,0 +1,878 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use axum::http::{HeaderName, HeaderValue};
+use sea_orm::sqlx::types::chrono::{DateTime, FixedOffset, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::Level;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub app_url: String,
+    pub max_log_level: Level,
+    pub database_url: Option<String>,
+    pub kiosk_directory: String,
+    pub kiosk_downloadable_url: String,
+    pub cache_ttl: Duration,
+    // distinct /latest-version query profiles kept in the cache
+    pub cache_capacity: usize,
+    pub warm_cache_on_start: bool,
+    pub max_scan_versions: Option<usize>,
+    pub publish_delay: Duration,
+    pub stats_cache_ttl: Duration,
+    pub signature_min_bytes: usize,
+    pub signature_max_bytes: usize,
+    pub relative_urls: bool,
+    // path every route is mounted under, `/kiosk-updater` or empty. generated
+    // urls get it appended after KIOSK_DOWNLOADABLE_URL
+    pub route_prefix: String,
+    pub fs_op_timeout: Duration,
+    pub shutdown_timeout: Duration,
+    pub download_mode: DownloadMode,
+    pub cdn_base_url: Option<String>,
+    pub signed_urls: bool,
+    pub url_signing_secret: Option<String>,
+    pub signed_url_ttl: Duration,
+    pub publish_queue_capacity: Option<usize>,
+    pub systemd_notify: bool,
+    // false keeps /health answering the plain `OK` older monitors match on
+    pub health_json: bool,
+    pub artifact_extensions: Vec<String>,
+    pub publish_webhook_url: Option<String>,
+    pub publish_webhook_timeout: Duration,
+    // attempts after the first one, each waiting twice as long as the last
+    pub publish_webhook_retries: u32,
+    pub publish_webhook_backoff: Duration,
+    pub force_octet_stream: bool,
+    pub fallback_version: Option<String>,
+    pub security_headers: Vec<(HeaderName, HeaderValue)>,
+    pub slow_request: Option<Duration>,
+    // smallest response body worth gzipping
+    pub compress_min_bytes: u16,
+    // platform folders every version is expected to contain, in manifest order
+    pub platforms: Vec<String>,
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    pub layout: Layout,
+    pub prerelease_order: PrereleaseOrder,
+    pub emit_content_md5: bool,
+    // artifact hashes kept in memory, see checksum::HashCache
+    pub hash_cache_capacity: usize,
+    pub max_disposition_filename_length: usize,
+    // incoming header reused as the request id, echoed back on the response
+    pub request_id_header: HeaderName,
+    // minisign public key the tauri updater verifies artifacts with
+    pub tauri_public_key: Option<String>,
+    pub fsync_uploads: bool,
+    // completed uploads must be a binary for their platform, see binary_format
+    pub validate_binary_format: bool,
+    // banner clients display next to the update check
+    pub message: Option<String>,
+    pub message_severity: MessageSeverity,
+    // backup hosts with the same `version/platform/file` tree as CDN_BASE_URL
+    pub mirror_base_urls: Vec<String>,
+    pub signature_mode: SignatureMode,
+    pub include_notes_in_manifest: bool,
+    // re-hash every artifact this often to detect bit-rot, off when unset
+    pub integrity_check_interval: Option<Duration>,
+    // free space KIOSK_DIRECTORY has to keep after a publish or upload
+    pub min_free_space_bytes: u64,
+    // /latest-version only offers updates inside this window, always when unset
+    pub rollout_window: Option<RolloutWindow>,
+    // iana zone the dashboard shows pub dates in next to utc
+    pub pub_date_tz: Option<chrono_tz::Tz>,
+    // /download answers 404 for versions /latest-version wouldn't serve
+    pub block_incomplete_downloads: bool,
+    // written instead of empty notes, `{version}` is replaced by the version
+    pub default_notes: Option<String>,
+    pub version_scheme: VersionScheme,
+    pub symlinked_versions: SymlinkedVersions,
+    // gzip level of the archive endpoints, 0 (store) to 9 (smallest)
+    pub archive_compression_level: u32,
+    // new versions wait for POST /versions/{version}/approve before they are
+    // served
+    pub require_approval: bool,
+    // bearer token of /versions/{version}/approve, distinct from anything CI holds
+    pub approver_token: Option<String>,
+}
+
+// how /download hands out artifacts, streamed by this server or redirected to
+// the same path below CDN_BASE_URL
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownloadMode {
+    Proxy,
+    Redirect,
+}
+
+impl FromStr for DownloadMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "proxy" => Ok(DownloadMode::Proxy),
+            "redirect" => Ok(DownloadMode::Redirect),
+            _ => Err(()),
+        }
+    }
+}
+
+// where platform folders live below KIOSK_DIRECTORY, `version/platform/file`
+// (nested) or `platform/version/file` (flat), notes and markers always stay in
+// the version folder
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Nested,
+    Flat,
+}
+
+impl FromStr for Layout {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "nested" => Ok(Layout::Nested),
+            "flat" => Ok(Layout::Flat),
+            _ => Err(()),
+        }
+    }
+}
+
+// how versions are ranked when picking the latest one. `semver` is the
+// standard precedence (`1.2.0-beta.1 < 1.2.0`), `prerelease_first` keeps that
+// across different major.minor.patch bases but ranks the prereleases of a base
+// above its release, so a beta deployment keeps serving the newest beta of
+// 1.2.0 even once 1.2.0 itself is out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrereleaseOrder {
+    Semver,
+    PrereleaseFirst,
+}
+
+impl FromStr for PrereleaseOrder {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "semver" => Ok(PrereleaseOrder::Semver),
+            "prerelease_first" => Ok(PrereleaseOrder::PrereleaseFirst),
+            _ => Err(()),
+        }
+    }
+}
+
+// which folder names are versions and how they rank. `calver` is
+// `YYYY.MM.DD` with an optional `.N` build of the day, `lexical` takes any
+// folder and ranks by name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VersionScheme {
+    Semver,
+    Calver,
+    Lexical,
+}
+
+impl FromStr for VersionScheme {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "semver" => Ok(VersionScheme::Semver),
+            "calver" => Ok(VersionScheme::Calver),
+            "lexical" => Ok(VersionScheme::Lexical),
+            _ => Err(()),
+        }
+    }
+}
+
+// symlinked folders in KIOSK_DIRECTORY. with `alias` one pointing at a version
+// folder (`stable -> 1.2.0`) isn't scanned as a version of its own but
+// /versions/{alias} answers for its target, `ignore` skips symlinks entirely
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymlinkedVersions {
+    Alias,
+    Ignore,
+}
+
+impl FromStr for SymlinkedVersions {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "alias" => Ok(SymlinkedVersions::Alias),
+            "ignore" => Ok(SymlinkedVersions::Ignore),
+            _ => Err(()),
+        }
+    }
+}
+
+// what the manifest's `signature` holds, the `.sig` content (what the tauri
+// updater expects) or the url of /versions/{version}/{platform}/signature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureMode {
+    Inline,
+    Url,
+}
+
+impl FromStr for SignatureMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "inline" => Ok(SignatureMode::Inline),
+            "url" => Ok(SignatureMode::Url),
+            _ => Err(()),
+        }
+    }
+}
+
+// `22:00-06:00` in utc or `22:00-06:00 +07:00` in a fixed offset, a start
+// after the end wraps past midnight
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RolloutWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub offset: FixedOffset,
+}
+
+impl RolloutWindow {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let time = at.with_timezone(&self.offset).time();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for RolloutWindow {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (hours, offset) = match value.trim().split_once(' ') {
+            Some((hours, offset)) => (hours, offset.trim().parse().map_err(|_| ())?),
+            None => (value.trim(), FixedOffset::east_opt(0).ok_or(())?),
+        };
+        let (start, end) = hours.split_once('-').ok_or(())?;
+        let time = |value: &str| NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| ());
+
+        Ok(RolloutWindow {
+            start: time(start)?,
+            end: time(end)?,
+            offset,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl FromStr for MessageSeverity {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "info" => Ok(MessageSeverity::Info),
+            "warning" => Ok(MessageSeverity::Warning),
+            "critical" => Ok(MessageSeverity::Critical),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ReadFile(String, std::io::Error),
+    ParseFile(String, toml::de::Error),
+    InvalidValue(&'static str, String),
+    // a setting another setting depends on is not set
+    MissingValue(&'static str, &'static str),
+    // not an absolute http(s) url with a host
+    InvalidUrl(&'static str, String),
+    // a list setting that needs at least one entry is set but empty
+    EmptyList(&'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ReadFile(path, e) => {
+                write!(f, "failed to read config file {}: {}", path, e)
+            }
+            ConfigError::ParseFile(path, e) => {
+                write!(f, "failed to parse config file {}: {}", path, e)
+            }
+            ConfigError::InvalidValue(key, value) => {
+                write!(f, "invalid value {:?} for {}", value, key)
+            }
+            ConfigError::MissingValue(key, reason) => {
+                write!(f, "{} is required {}", key, reason)
+            }
+            ConfigError::InvalidUrl(key, value) => write!(
+                f,
+                "{} must be an http or https url with a host like http://example.com, got {:?}",
+                key, value
+            ),
+            ConfigError::EmptyList(key) => write!(f, "{} needs at least one entry", key),
+        }
+    }
+}
+
+impl Config {
+    // loads the optional CONFIG_FILE (toml) and lets environment variables
+    // override any value found in it
+    pub fn load() -> Result<Self, ConfigError> {
+        let file = match dotenv::var("CONFIG_FILE") {
+            Ok(path) => {
+                let content = std::fs::read_to_string(&path)
+                    .map_err(|e| ConfigError::ReadFile(path.clone(), e))?;
+                content
+                    .parse::<toml::Table>()
+                    .map_err(|e| ConfigError::ParseFile(path, e))?
+            }
+            Err(_) => toml::Table::new(),
+        };
+
+        Self::from_sources(&ConfigSources {
+            file,
+            env: &|key| dotenv::var(key).ok(),
+        })
+    }
+
+    pub fn from_sources(sources: &ConfigSources) -> Result<Self, ConfigError> {
+        let download_mode = sources.get("download_mode")?.unwrap_or(DownloadMode::Proxy);
+        let cdn_base_url: Option<String> = sources.get("cdn_base_url")?;
+        if download_mode == DownloadMode::Redirect && cdn_base_url.is_none() {
+            return Err(ConfigError::MissingValue(
+                "cdn_base_url",
+                "when download_mode is redirect",
+            ));
+        }
+
+        let signed_urls = sources.get("signed_urls")?.unwrap_or(false);
+        let url_signing_secret: Option<String> = sources.get("url_signing_secret")?;
+        if signed_urls && url_signing_secret.is_none() {
+            return Err(ConfigError::MissingValue(
+                "url_signing_secret",
+                "when signed_urls is enabled",
+            ));
+        }
+
+        // with no platforms every version would be servable without artifacts
+        let platforms = unique_platforms(
+            sources
+                .get_list("platforms")?
+                .unwrap_or_else(|| DEFAULT_PLATFORMS.map(String::from).to_vec()),
+        );
+        if platforms.is_empty() {
+            return Err(ConfigError::EmptyList("platforms"));
+        }
+
+        // a queue without room for a single job could never run one
+        let publish_queue_capacity: Option<usize> = sources.get("publish_queue_capacity")?;
+        if publish_queue_capacity == Some(0) {
+            return Err(ConfigError::InvalidValue(
+                "publish_queue_capacity",
+                "0".to_string(),
+            ));
+        }
+
+        let require_approval = sources.get("require_approval")?.unwrap_or(false);
+        let approver_token: Option<String> = sources.get("approver_token")?;
+        if require_approval && approver_token.is_none() {
+            return Err(ConfigError::MissingValue(
+                "approver_token",
+                "when require_approval is enabled",
+            ));
+        }
+
+        let tls_cert: Option<String> = sources.get("tls_cert")?;
+        let tls_key: Option<String> = sources.get("tls_key")?;
+        match (&tls_cert, &tls_key) {
+            (Some(_), None) => {
+                return Err(ConfigError::MissingValue("tls_key", "when tls_cert is set"))
+            }
+            (None, Some(_)) => {
+                return Err(ConfigError::MissingValue("tls_cert", "when tls_key is set"))
+            }
+            _ => {}
+        }
+
+        let tauri_public_key: Option<String> = sources.get("tauri_public_key")?;
+        if let Some(key) = &tauri_public_key {
+            crate::validate::decode_public_key(key)
+                .map_err(|_| ConfigError::InvalidValue("tauri_public_key", key.clone()))?;
+        }
+
+        Ok(Self {
+            app_url: sources
+                .get("app_url")?
+                .unwrap_or_else(|| "0.0.0.0:3000".to_string()),
+            max_log_level: sources.get("max_log_level")?.unwrap_or(Level::WARN),
+            database_url: sources.get("database_url")?,
+            kiosk_directory: sources
+                .get("kiosk_directory")?
+                .unwrap_or_else(|| "kiosk".to_string()),
+            kiosk_downloadable_url: http_url(
+                "kiosk_downloadable_url",
+                sources
+                    .get("kiosk_downloadable_url")?
+                    .unwrap_or_else(|| "http://localhost:3000".to_string()),
+            )?,
+            cache_ttl: Duration::from_secs(sources.get("cache_ttl_secs")?.unwrap_or(30)),
+            cache_capacity: sources.get("cache_capacity")?.unwrap_or(128),
+            warm_cache_on_start: sources.get("warm_cache_on_start")?.unwrap_or(false),
+            max_scan_versions: sources.get("max_scan_versions")?,
+            publish_delay: Duration::from_secs(sources.get("publish_delay_secs")?.unwrap_or(0)),
+            stats_cache_ttl: Duration::from_secs(
+                sources.get("stats_cache_ttl_secs")?.unwrap_or(10),
+            ),
+            // tauri signatures are a few hundred bytes, anything far outside
+            // that range is a truncated or wrong file
+            signature_min_bytes: sources.get("signature_min_bytes")?.unwrap_or(100),
+            signature_max_bytes: sources.get("signature_max_bytes")?.unwrap_or(4096),
+            relative_urls: sources.get("relative_urls")?.unwrap_or(false),
+            route_prefix: route_prefix(sources.get("route_prefix")?.unwrap_or_default()),
+            fs_op_timeout: Duration::from_millis(sources.get("fs_op_timeout_ms")?.unwrap_or(5000)),
+            shutdown_timeout: Duration::from_secs(
+                sources.get("shutdown_timeout_secs")?.unwrap_or(30),
+            ),
+            download_mode,
+            cdn_base_url,
+            signed_urls,
+            url_signing_secret,
+            signed_url_ttl: signed_url_ttl(sources.get("signed_url_ttl_secs")?.unwrap_or(3600))?,
+            publish_queue_capacity,
+            systemd_notify: sources.get("systemd_notify")?.unwrap_or(false),
+            health_json: sources.get("health_json")?.unwrap_or(true),
+            artifact_extensions: sources.get_list("artifact_extensions")?.unwrap_or_else(|| {
+                [
+                    ".tar.gz",
+                    ".zip",
+                    ".msi",
+                    ".exe",
+                    ".dmg",
+                    ".AppImage",
+                    ".deb",
+                    ".rpm",
+                ]
+                .map(String::from)
+                .to_vec()
+            }),
+            publish_webhook_url: sources.get("publish_webhook_url")?,
+            // the seconds key predates the millisecond one
+            publish_webhook_timeout: match sources.get("publish_webhook_timeout_ms")? {
+                Some(timeout) => Duration::from_millis(timeout),
+                None => {
+                    Duration::from_secs(sources.get("publish_webhook_timeout_secs")?.unwrap_or(10))
+                }
+            },
+            publish_webhook_retries: sources.get("publish_webhook_retries")?.unwrap_or(2),
+            publish_webhook_backoff: Duration::from_millis(
+                sources.get("publish_webhook_backoff_ms")?.unwrap_or(2000),
+            ),
+            force_octet_stream: sources.get("force_octet_stream")?.unwrap_or(false),
+            fallback_version: sources.get("fallback_version")?,
+            security_headers: security_headers(sources.get_list("security_headers")?)?,
+            slow_request: sources.get("slow_request_ms")?.map(Duration::from_millis),
+            compress_min_bytes: sources.get("compress_min_bytes")?.unwrap_or(1024),
+            platforms,
+            tls_cert,
+            tls_key,
+            layout: sources.get("layout")?.unwrap_or(Layout::Nested),
+            prerelease_order: sources
+                .get("prerelease_order")?
+                .unwrap_or(PrereleaseOrder::Semver),
+            emit_content_md5: sources.get("emit_content_md5")?.unwrap_or(false),
+            hash_cache_capacity: sources.get("hash_cache_capacity")?.unwrap_or(1024),
+            max_disposition_filename_length: sources
+                .get("max_disposition_filename_length")?
+                .unwrap_or(128),
+            request_id_header: sources
+                .get("request_id_header")?
+                .unwrap_or(HeaderName::from_static("x-request-id")),
+            tauri_public_key,
+            fsync_uploads: sources.get("fsync_uploads")?.unwrap_or(true),
+            validate_binary_format: sources.get("validate_binary_format")?.unwrap_or(false),
+            message: sources.get("message")?,
+            message_severity: sources
+                .get("message_severity")?
+                .unwrap_or(MessageSeverity::Info),
+            min_free_space_bytes: sources
+                .get("min_free_space_bytes")?
+                .unwrap_or(100 * 1024 * 1024),
+            integrity_check_interval: sources
+                .get("integrity_check_interval_secs")?
+                .map(Duration::from_secs),
+            include_notes_in_manifest: sources.get("include_notes_in_manifest")?.unwrap_or(true),
+            rollout_window: sources.get("rollout_window")?,
+            pub_date_tz: sources.get("pub_date_tz")?,
+            default_notes: sources.get("default_notes")?,
+            version_scheme: sources
+                .get("version_scheme")?
+                .unwrap_or(VersionScheme::Semver),
+            symlinked_versions: sources
+                .get("symlinked_versions")?
+                .unwrap_or(SymlinkedVersions::Alias),
+            archive_compression_level: compression_level(
+                sources.get("archive_compression_level")?.unwrap_or(6),
+            )?,
+            require_approval,
+            approver_token,
+            block_incomplete_downloads: sources.get("block_incomplete_downloads")?.unwrap_or(false),
+            signature_mode: sources
+                .get("signature_mode")?
+                .unwrap_or(SignatureMode::Inline),
+            mirror_base_urls: sources
+                .get_list("mirror_base_urls")?
+                .unwrap_or_default()
+                .into_iter()
+                .map(|url| http_url("mirror_base_urls", url))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+// download urls are built by appending to this, a bare `localhost:3000` would
+// give clients urls they can't resolve
+fn http_url(key: &'static str, value: String) -> Result<String, ConfigError> {
+    match reqwest::Url::parse(&value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => {
+            Ok(value)
+        }
+        _ => Err(ConfigError::InvalidUrl(key, value)),
+    }
+}
+
+fn compression_level(level: u32) -> Result<u32, ConfigError> {
+    match level {
+        0..=9 => Ok(level),
+        _ => Err(ConfigError::InvalidValue(
+            "archive_compression_level",
+            level.to_string(),
+        )),
+    }
+}
+
+// a signed url is meant to be short lived, a year already defeats the point
+const MAX_SIGNED_URL_TTL_SECS: u64 = 365 * 24 * 60 * 60;
+
+fn signed_url_ttl(secs: u64) -> Result<Duration, ConfigError> {
+    match secs {
+        0..=MAX_SIGNED_URL_TTL_SECS => Ok(Duration::from_secs(secs)),
+        _ => Err(ConfigError::InvalidValue(
+            "signed_url_ttl_secs",
+            secs.to_string(),
+        )),
+    }
+}
+
+// `kiosk-updater/`, `/kiosk-updater` and `/kiosk-updater/` all mount at
+// `/kiosk-updater`, `/` is the same as no prefix
+fn route_prefix(value: String) -> String {
+    match value.trim().trim_matches('/') {
+        "" => String::new(),
+        prefix => format!("/{}", prefix),
+    }
+}
+
+const DEFAULT_PLATFORMS: [&str; 4] = [
+    "linux_x86_64",
+    "windows_x86_64",
+    "darwin_x86_64",
+    "darwin_aarch64",
+];
+
+// platforms are matched case-insensitively, so a repeat in any spelling would
+// be published and inspected twice. the first spelling is kept, in order
+fn unique_platforms(platforms: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::with_capacity(platforms.len());
+    for platform in platforms {
+        if !unique
+            .iter()
+            .any(|existing| existing.eq_ignore_ascii_case(&platform))
+        {
+            unique.push(platform);
+        }
+    }
+
+    unique
+}
+
+// header added to every response unless SECURITY_HEADERS overrides it
+const DEFAULT_SECURITY_HEADERS: [(&str, &str); 4] = [
+    ("x-content-type-options", "nosniff"),
+    ("x-frame-options", "DENY"),
+    ("referrer-policy", "no-referrer"),
+    ("strict-transport-security", "max-age=31536000"),
+];
+
+// `Name=value` entries override the default with the same name, `Name=` drops it
+fn security_headers(
+    entries: Option<Vec<String>>,
+) -> Result<Vec<(HeaderName, HeaderValue)>, ConfigError> {
+    let mut headers: Vec<(String, String)> = DEFAULT_SECURITY_HEADERS
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+    for entry in entries.unwrap_or_default() {
+        let (name, value) = entry
+            .split_once('=')
+            .ok_or_else(|| ConfigError::InvalidValue("security_headers", entry.clone()))?;
+        let name = name.trim().to_ascii_lowercase();
+        headers.retain(|(existing, _)| *existing != name);
+        if !value.trim().is_empty() {
+            headers.push((name, value.trim().to_string()));
+        }
+    }
+
+    headers
+        .into_iter()
+        .map(
+            |(name, value)| match (HeaderName::try_from(&name), HeaderValue::try_from(&value)) {
+                (Ok(name), Ok(value)) => Ok((name, value)),
+                _ => Err(ConfigError::InvalidValue(
+                    "security_headers",
+                    format!("{}={}", name, value),
+                )),
+            },
+        )
+        .collect()
+}
+
+// every setting has a snake_case key in the config file and the same key in
+// upper case as an environment variable, the environment always wins
+pub struct ConfigSources<'a> {
+    pub file: toml::Table,
+    pub env: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl ConfigSources<'_> {
+    fn raw(&self, key: &str) -> Option<String> {
+        if let Some(value) = (self.env)(&key.to_uppercase()) {
+            return Some(value);
+        }
+
+        match self.file.get(key)? {
+            toml::Value::String(value) => Some(value.clone()),
+            toml::Value::Array(values) => Some(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        toml::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            value => Some(value.to_string()),
+        }
+    }
+
+    // comma separated in the environment, an array in the config file
+    fn get_list(&self, key: &'static str) -> Result<Option<Vec<String>>, ConfigError> {
+        Ok(self.get::<String>(key)?.map(|value| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        }))
+    }
+
+    fn get<T: FromStr>(&self, key: &'static str) -> Result<Option<T>, ConfigError> {
+        match self.raw(key) {
+            Some(value) => value
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|_| ConfigError::InvalidValue(key, value)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: &str, env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let lookup = |key: &str| {
+            env.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        };
+        Config::from_sources(&ConfigSources {
+            file: file.parse().unwrap(),
+            env: &lookup,
+        })
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let config = load(
+            "kiosk_directory = \"/srv/file\"\ncache_ttl_secs = 60",
+            &[("KIOSK_DIRECTORY", "/srv/env")],
+        )
+        .unwrap();
+
+        assert_eq!(config.kiosk_directory, "/srv/env");
+        assert_eq!(config.cache_ttl, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn defaults_apply_when_neither_source_sets_a_value() {
+        let config = load("", &[]).unwrap();
+
+        assert_eq!(config.app_url, "0.0.0.0:3000");
+        assert_eq!(config.kiosk_directory, "kiosk");
+        assert_eq!(config.cache_ttl, Duration::from_secs(30));
+        assert_eq!(config.download_mode, DownloadMode::Proxy);
+        assert_eq!(config.platforms, DEFAULT_PLATFORMS.map(String::from));
+    }
+
+    #[test]
+    fn reads_file_arrays_as_lists() {
+        let config = load("platforms = [\"linux_x86_64\", \"windows_x86_64\"]", &[]).unwrap();
+
+        assert_eq!(config.platforms, ["linux_x86_64", "windows_x86_64"]);
+    }
+
+    #[test]
+    fn collapses_repeated_platforms_in_order() {
+        let config = load(
+            "",
+            &[(
+                "PLATFORMS",
+                "windows_x86_64, linux_x86_64, Windows_X86_64, darwin_aarch64, linux_x86_64",
+            )],
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.platforms,
+            ["windows_x86_64", "linux_x86_64", "darwin_aarch64"]
+        );
+    }
+
+    #[test]
+    fn rejects_unparsable_values() {
+        let error = load("", &[("CACHE_TTL_SECS", "soon")]).unwrap_err();
+
+        assert!(matches!(
+            error,
+            ConfigError::InvalidValue("cache_ttl_secs", _)
+        ));
+    }
+
+    #[test]
+    fn requires_an_http_downloadable_url() {
+        for url in ["http://localhost:3000", "https://updates.example.com/kiosk"] {
+            let config = load("", &[("KIOSK_DOWNLOADABLE_URL", url)]).unwrap();
+            assert_eq!(config.kiosk_downloadable_url, url);
+        }
+
+        for url in [
+            "localhost:3000",
+            "updates.example.com",
+            "ftp://updates.example.com",
+            "file:///srv/kiosk",
+            "https://",
+        ] {
+            let error = load("", &[("KIOSK_DOWNLOADABLE_URL", url)]).unwrap_err();
+            assert!(
+                matches!(error, ConfigError::InvalidUrl("kiosk_downloadable_url", ref value) if value == url),
+                "{}: {:?}",
+                url,
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn reads_pub_date_tz_as_an_iana_zone() {
+        let config = load("", &[("PUB_DATE_TZ", "Asia/Jakarta")]).unwrap();
+        assert_eq!(config.pub_date_tz, Some(chrono_tz::Asia::Jakarta));
+
+        let error = load("", &[("PUB_DATE_TZ", "+07:00")]).unwrap_err();
+        assert!(matches!(error, ConfigError::InvalidValue("pub_date_tz", _)));
+    }
+
+    #[test]
+    fn rejects_a_signed_url_ttl_over_a_year() {
+        let config = load("", &[("SIGNED_URL_TTL_SECS", "31536000")]).unwrap();
+        assert_eq!(config.signed_url_ttl, Duration::from_secs(31_536_000));
+
+        let error = load("", &[("SIGNED_URL_TTL_SECS", "18446744073709551615")]).unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::InvalidValue("signed_url_ttl_secs", _)
+        ));
+    }
+
+    #[test]
+    fn rejects_an_empty_publish_queue() {
+        let config = load("", &[("PUBLISH_QUEUE_CAPACITY", "1")]).unwrap();
+        assert_eq!(config.publish_queue_capacity, Some(1));
+
+        let error = load("", &[("PUBLISH_QUEUE_CAPACITY", "0")]).unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::InvalidValue("publish_queue_capacity", _)
+        ));
+    }
+
+    #[test]
+    fn rejects_a_compression_level_over_nine() {
+        let config = load("", &[("ARCHIVE_COMPRESSION_LEVEL", "9")]).unwrap();
+        assert_eq!(config.archive_compression_level, 9);
+
+        let error = load("", &[("ARCHIVE_COMPRESSION_LEVEL", "10")]).unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::InvalidValue("archive_compression_level", _)
+        ));
+    }
+
+    #[test]
+    fn rollout_windows_wrap_past_midnight() {
+        let at = |time: &str| {
+            DateTime::parse_from_rfc3339(&format!("2026-01-01T{}:00Z", time))
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        let night: RolloutWindow = "22:00-06:00".parse().unwrap();
+        assert!(night.contains(at("23:30")));
+        assert!(night.contains(at("05:59")));
+        assert!(!night.contains(at("06:00")));
+        assert!(!night.contains(at("12:00")));
+
+        // 22:00-06:00 in +07:00 is 15:00-23:00 utc
+        let jakarta: RolloutWindow = "22:00-06:00 +07:00".parse().unwrap();
+        assert!(jakarta.contains(at("15:00")));
+        assert!(!jakarta.contains(at("23:30")));
+
+        assert!("22:00".parse::<RolloutWindow>().is_err());
+    }
+
+    #[test]
+    fn refuses_an_empty_platform_set() {
+        for (file, env) in [
+            ("platforms = []", &[][..]),
+            ("", &[("PLATFORMS", "")][..]),
+            ("", &[("PLATFORMS", " , ")][..]),
+        ] {
+            assert!(
+                matches!(load(file, env), Err(ConfigError::EmptyList("platforms"))),
+                "{:?} {:?}",
+                file,
+                env
+            );
+        }
+    }
+}
@@ -7,16 +7,46 @@ use sea_orm::DbErr;
 use serde::Serialize;
 use strum::IntoStaticStr;
 
-#[derive(IntoStaticStr)]
+#[derive(Debug, IntoStaticStr)]
 pub enum APIError {
     Internal,
     NotFound,
-    FolderExist,
+    Forbidden,
+    // nothing to offer right now, the tauri updater reads 204 as up to date
+    NoUpdate,
+    StorageUnavailable,
+    InsufficientStorage,
+    QueueFull,
+    // the version folder is already there, carries what CI compares against
+    FolderExist(ExistingVersion),
     FileOrPathNotExist,
+    InvalidContentRange,
+    // a chunk upload without Content-Length, the free space check needs it
+    LengthRequired,
+    InvalidVersion,
+    InvalidTimestamp,
+    InvalidMultipart,
+    // an if-changed publish without a single checksum to compare
+    MissingChecksums,
+    // carries the offset the upload has to resume from
+    UploadOffsetMismatch(u64),
+    // an upload would replace this file without `?overwrite=true`
+    ArtifactExists(String),
+    // the route exists but not for this method
+    MethodNotAllowed,
+    // the platform isn't one of PLATFORMS, carries the requested name
+    PlatformNotFound(String, Vec<String>),
+    // VALIDATE_BINARY_FORMAT found a binary for another platform
+    BinaryFormatMismatch(BinaryFormatMismatch),
+    // a batch asked for more versions than allowed, carries the limit
+    TooManyVersions(usize),
+    // the notes.txt of the version is a directory or another non-file,
+    // carries the version
+    NotesNotAFile(String),
 }
 
 impl APIError {
-    fn into_kiosk_version_error<T: Serialize>(
+    fn to_kiosk_version_error<T: Serialize>(
         &self,
         status_code: StatusCode,
         data: Option<T>,
@@ -26,7 +56,7 @@ impl APIError {
             Json(ReturnedResponse {
                 kiosk_version_error: ReturnedKioskVersionError {
                     code: self.into(),
-                    data: data,
+                    data,
                 },
             }),
         )
@@ -39,12 +69,73 @@ impl IntoResponse for APIError {
         match self {
             APIError::Internal => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
             APIError::NotFound => StatusCode::NOT_FOUND.into_response(),
-            APIError::FolderExist => {
-                self.into_kiosk_version_error::<()>(StatusCode::UNPROCESSABLE_ENTITY, None)
+            APIError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+            APIError::NoUpdate => StatusCode::NO_CONTENT.into_response(),
+            APIError::FolderExist(ref existing) => {
+                self.to_kiosk_version_error(StatusCode::UNPROCESSABLE_ENTITY, Some(existing))
             }
             APIError::FileOrPathNotExist => {
-                self.into_kiosk_version_error::<()>(StatusCode::UNPROCESSABLE_ENTITY, None)
+                self.to_kiosk_version_error::<()>(StatusCode::UNPROCESSABLE_ENTITY, None)
             }
+            APIError::StorageUnavailable => {
+                self.to_kiosk_version_error::<()>(StatusCode::SERVICE_UNAVAILABLE, None)
+            }
+            APIError::InsufficientStorage => {
+                self.to_kiosk_version_error::<()>(StatusCode::INSUFFICIENT_STORAGE, None)
+            }
+            APIError::QueueFull => {
+                self.to_kiosk_version_error::<()>(StatusCode::SERVICE_UNAVAILABLE, None)
+            }
+            APIError::InvalidTimestamp => {
+                self.to_kiosk_version_error::<()>(StatusCode::BAD_REQUEST, None)
+            }
+            APIError::InvalidVersion => {
+                self.to_kiosk_version_error::<()>(StatusCode::BAD_REQUEST, None)
+            }
+            APIError::InvalidMultipart => {
+                self.to_kiosk_version_error::<()>(StatusCode::BAD_REQUEST, None)
+            }
+            APIError::MissingChecksums => {
+                self.to_kiosk_version_error::<()>(StatusCode::BAD_REQUEST, None)
+            }
+            APIError::LengthRequired => {
+                self.to_kiosk_version_error::<()>(StatusCode::LENGTH_REQUIRED, None)
+            }
+            APIError::InvalidContentRange => {
+                self.to_kiosk_version_error::<()>(StatusCode::BAD_REQUEST, None)
+            }
+            APIError::UploadOffsetMismatch(offset) => {
+                self.to_kiosk_version_error(StatusCode::CONFLICT, Some(UploadOffset { offset }))
+            }
+            APIError::PlatformNotFound(ref platform, ref valid_platforms) => self
+                .to_kiosk_version_error(
+                    StatusCode::NOT_FOUND,
+                    Some(UnknownPlatform {
+                        platform: platform.clone(),
+                        valid_platforms: valid_platforms.clone(),
+                    }),
+                ),
+            APIError::MethodNotAllowed => {
+                self.to_kiosk_version_error::<()>(StatusCode::METHOD_NOT_ALLOWED, None)
+            }
+            APIError::BinaryFormatMismatch(ref mismatch) => {
+                self.to_kiosk_version_error(StatusCode::UNPROCESSABLE_ENTITY, Some(mismatch))
+            }
+            APIError::TooManyVersions(max) => self
+                .to_kiosk_version_error(StatusCode::UNPROCESSABLE_ENTITY, Some(BatchLimit { max })),
+            APIError::NotesNotAFile(ref version) => self.to_kiosk_version_error(
+                StatusCode::CONFLICT,
+                Some(InvalidNotes {
+                    version: version.clone(),
+                    path: format!("{}/{}", version, crate::scanner::NOTES_FILE),
+                }),
+            ),
+            APIError::ArtifactExists(ref filename) => self.to_kiosk_version_error(
+                StatusCode::CONFLICT,
+                Some(ExistingArtifact {
+                    filename: filename.clone(),
+                }),
+            ),
         }
     }
 }
@@ -80,6 +171,50 @@ impl From<header::ToStrError> for APIError {
     }
 }
 
+#[derive(Serialize)]
+pub struct UploadOffset {
+    pub offset: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BinaryFormatMismatch {
+    pub filename: String,
+    pub platform: String,
+    pub expected: String,
+    // None when the header matched no known binary format
+    pub found: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct InvalidNotes {
+    pub version: String,
+    // relative to KIOSK_DIRECTORY
+    pub path: String,
+}
+
+#[derive(Serialize)]
+pub struct BatchLimit {
+    pub max: usize,
+}
+
+#[derive(Serialize)]
+pub struct UnknownPlatform {
+    pub platform: String,
+    pub valid_platforms: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExistingVersion {
+    // None until the version is servable
+    pub pub_date: Option<String>,
+    pub notes_sha256: String,
+}
+
+#[derive(Serialize)]
+pub struct ExistingArtifact {
+    pub filename: String,
+}
+
 #[derive(Serialize)]
 struct ReturnedResponse<T: Serialize> {
     kiosk_version_error: ReturnedKioskVersionError<T>,
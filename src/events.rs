@@ -0,0 +1,178 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{Stream, StreamExt};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::state::AppState;
+
+// events a slow dashboard may fall behind by before it starts missing some
+pub const CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadEventKind {
+    Started,
+    Finished,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DownloadEvent {
+    pub kind: DownloadEventKind,
+    pub version: String,
+    pub platform: String,
+    pub filename: String,
+    // the file size when started, what was streamed when finished
+    pub bytes: u64,
+    // the peer address, i.e. the proxy when running behind one
+    pub client_ip: String,
+}
+
+// sent when a download starts, dropping it sends the finished event with the
+// bytes streamed, which is short of the size when the client went away
+pub struct DownloadProgress {
+    sender: broadcast::Sender<DownloadEvent>,
+    event: DownloadEvent,
+    streamed: u64,
+}
+
+impl DownloadProgress {
+    pub fn start(
+        sender: &broadcast::Sender<DownloadEvent>,
+        version: &str,
+        platform: &str,
+        filename: &str,
+        size: u64,
+        client: SocketAddr,
+    ) -> Self {
+        let event = DownloadEvent {
+            kind: DownloadEventKind::Started,
+            version: version.to_string(),
+            platform: platform.to_string(),
+            filename: filename.to_string(),
+            bytes: size,
+            client_ip: client.ip().to_string(),
+        };
+        // no subscribers is the common case, not an error
+        let _ = sender.send(event.clone());
+
+        Self {
+            sender: sender.clone(),
+            event,
+            streamed: 0,
+        }
+    }
+
+    pub fn add(&mut self, bytes: usize) {
+        self.streamed += bytes as u64;
+    }
+}
+
+impl Drop for DownloadProgress {
+    fn drop(&mut self) {
+        let _ = self.sender.send(DownloadEvent {
+            kind: DownloadEventKind::Finished,
+            bytes: self.streamed,
+            ..self.event.clone()
+        });
+    }
+}
+
+// live download starts and finishes as server-sent events, `event` is the
+// kind and `data` the json of the DownloadEvent
+pub async fn stream_downloads(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = state.download_events.subscribe();
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let kind = match event.kind {
+                        DownloadEventKind::Started => "started",
+                        DownloadEventKind::Finished => "finished",
+                    };
+                    let Ok(sse) = Event::default().event(kind).json_data(&event) else {
+                        continue;
+                    };
+                    return Some((Ok(sse), receiver));
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!("download event subscriber missed {} events", missed);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Sse::new(stream.take_until(state.shutdown.clone().cancelled_owned()))
+        .keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    #[tokio::test]
+    async fn reports_the_start_and_end_of_a_download() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let mut events = state.download_events.subscribe();
+        let size = "bin 1.0.0 darwin_aarch64".len() as u64;
+
+        let response = test_support::download(
+            &state,
+            Method::GET,
+            "darwin_aarch64",
+            "1.0.0",
+            "app-darwin_aarch64.tar.gz",
+        )
+        .await
+        .unwrap();
+
+        let started = events.recv().await.unwrap();
+        assert!(matches!(started.kind, DownloadEventKind::Started));
+        assert_eq!(started.version, "1.0.0");
+        assert_eq!(started.platform, "darwin_aarch64");
+        assert_eq!(started.filename, "app-darwin_aarch64.tar.gz");
+        assert_eq!(started.bytes, size);
+        assert_eq!(started.client_ip, "127.0.0.1");
+
+        // finished once the body has been streamed out
+        assert_eq!(test_support::body(response).await.len() as u64, size);
+        let finished = events.recv().await.unwrap();
+        assert!(matches!(finished.kind, DownloadEventKind::Finished));
+        assert_eq!(finished.bytes, size);
+    }
+
+    #[test]
+    fn a_dropped_download_reports_what_was_streamed() {
+        let (sender, mut events) = broadcast::channel(CAPACITY);
+
+        let mut progress = DownloadProgress::start(
+            &sender,
+            "1.0.0",
+            "linux_x86_64",
+            "app.tar.gz",
+            1000,
+            SocketAddr::from(([10, 0, 0, 1], 4000)),
+        );
+        progress.add(300);
+        progress.add(100);
+        drop(progress);
+
+        assert_eq!(events.try_recv().unwrap().bytes, 1000);
+        let finished = events.try_recv().unwrap();
+        assert!(matches!(finished.kind, DownloadEventKind::Finished));
+        assert_eq!(finished.bytes, 400);
+        assert_eq!(finished.client_ip, "10.0.0.1");
+    }
+}
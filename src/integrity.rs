@@ -0,0 +1,176 @@
+use std::{
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+};
+
+use tokio::fs;
+
+use crate::{
+    checksum::{self, SHA256_EXTENSION},
+    config::Config,
+    error::APIError,
+    scanner,
+    state::AppState,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum IntegrityCheck {
+    // first check (or the artifact was replaced), the hash is stored now
+    Recorded,
+    Intact,
+    Corrupted { expected: String, actual: String },
+}
+
+// compares an artifact with its `.sha256` sidecar. bit-rot leaves the
+// modification time alone, so an artifact newer than its sidecar was replaced
+// on purpose and its hash is stored again
+pub async fn check_artifact(path: &Path) -> std::io::Result<IntegrityCheck> {
+    let sidecar = checksum::sidecar(path, SHA256_EXTENSION);
+    let actual = checksum::sha256_file(path).await?;
+
+    let modified = fs::metadata(path).await?.modified()?;
+    let expected = match fs::metadata(&sidecar).await {
+        Ok(metadata) if metadata.modified()? >= modified => {
+            Some(fs::read_to_string(&sidecar).await?.trim().to_string())
+        }
+        Ok(_) => None,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    match expected {
+        Some(expected) if expected == actual => Ok(IntegrityCheck::Intact),
+        Some(expected) => Ok(IntegrityCheck::Corrupted { expected, actual }),
+        None => {
+            fs::write(&sidecar, &actual).await?;
+            Ok(IntegrityCheck::Recorded)
+        }
+    }
+}
+
+// re-hashes the artifact of every platform of every version, returns how many
+// were corrupted
+pub async fn check_all(config: &Config) -> Result<usize, APIError> {
+    let mut corrupted = 0;
+    for (_, version) in scanner::list_versions(config).await?.versions {
+        for platform in &config.platforms {
+            let Some(files) = scanner::read_platform(config, &version, platform).await? else {
+                continue;
+            };
+            let Some(artifact) = files.artifact else {
+                continue;
+            };
+
+            let path = files.folder.join(&artifact);
+            match check_artifact(&path).await {
+                Ok(IntegrityCheck::Corrupted { expected, actual }) => {
+                    corrupted += 1;
+                    tracing::error!(
+                        "artifact {} is corrupted, expected sha256 {} but got {}",
+                        path.display(),
+                        expected,
+                        actual
+                    );
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("failed to check {}: {}", path.display(), e),
+            }
+        }
+    }
+
+    Ok(corrupted)
+}
+
+// runs check_all every INTEGRITY_CHECK_INTERVAL_SECS on its own task so
+// serving never waits on it
+pub fn start(state: Arc<AppState>) {
+    let Some(interval) = state.config.integrity_check_interval else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match check_all(&state.config).await {
+                Ok(0) => tracing::debug!("integrity check found no corrupted artifacts"),
+                Ok(corrupted) => {
+                    state
+                        .corruption_detected
+                        .fetch_add(corrupted as u64, Ordering::Relaxed);
+                }
+                Err(_) => tracing::warn!("integrity check couldn't list versions"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    // bit-rot: other bytes, same size and modification time
+    fn rot(path: &Path) {
+        let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes[0] ^= 0xff;
+        std::fs::write(path, bytes).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn records_then_confirms_then_spots_corruption() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let artifact = kiosk
+            .path()
+            .join("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz");
+
+        assert_eq!(
+            check_artifact(&artifact).await.unwrap(),
+            IntegrityCheck::Recorded
+        );
+        let recorded = checksum::sha256_file(&artifact).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(checksum::sidecar(&artifact, SHA256_EXTENSION)).unwrap(),
+            recorded
+        );
+        assert_eq!(
+            check_artifact(&artifact).await.unwrap(),
+            IntegrityCheck::Intact
+        );
+
+        rot(&artifact);
+        assert_eq!(
+            check_artifact(&artifact).await.unwrap(),
+            IntegrityCheck::Corrupted {
+                expected: recorded,
+                actual: checksum::sha256_file(&artifact).await.unwrap(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_the_corrupted_artifacts_of_every_version() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        let config = kiosk.config(&[]);
+
+        assert_eq!(check_all(&config).await.unwrap(), 0);
+        assert_eq!(check_all(&config).await.unwrap(), 0);
+
+        rot(&kiosk
+            .path()
+            .join("1.0.0/windows_x86_64/app-windows_x86_64.tar.gz"));
+        rot(&kiosk
+            .path()
+            .join("1.1.0/darwin_x86_64/app-darwin_x86_64.tar.gz"));
+        assert_eq!(check_all(&config).await.unwrap(), 2);
+    }
+}
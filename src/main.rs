@@ -1,66 +1,460 @@
-use crate::error::APIError;
+use crate::{
+    cache::ManifestKey,
+    cli::{Cli, Command},
+    config::{Config, DownloadMode, MessageSeverity, SignatureMode},
+    error::APIError,
+    events::DownloadProgress,
+    platform::Platform,
+    rollout::RolloutDecision,
+    scanner::{PlatformInspection, VersionInspection},
+    state::AppState,
+};
 use axum::{
     body::Body,
-    extract::{Path, State},
-    http::{header, HeaderMap, Response, StatusCode},
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, Request, State},
+    http::{header, HeaderMap, Method, Response, StatusCode},
+    middleware::{self, Next},
     response::IntoResponse,
     routing::{get, post},
     serve, Json, Router,
 };
-use dotenv::dotenv;
-use sea_orm::{
-    sqlx::types::chrono::{self, Utc},
-    ActiveModelTrait, Database, DatabaseConnection, EntityTrait,
-};
-use semver::Version;
+use clap::Parser;
+use futures_util::StreamExt;
+use indexmap::IndexMap;
+use sea_orm::sqlx::types::chrono::{self, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::Permissions, io, os::unix::fs::PermissionsExt, path::PathBuf, sync::Arc, time::SystemTime,
+    collections::HashMap,
+    future::{Future, IntoFuture},
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
-use tokio::{
-    fs::{self},
-    net::TcpListener,
-    signal,
+use tokio::{net::TcpListener, signal, sync::Notify};
+use tower_http::compression::{
+    predicate::{Predicate, SizeAbove},
+    CompressionLayer,
 };
-use tracing::Level;
+use tracing::Instrument;
 use tracing_subscriber::fmt::Subscriber;
 
+mod archive;
+mod banner;
+mod binary_format;
+mod cache;
+mod checksum;
+mod cli;
+mod config;
 mod error;
+mod events;
+mod integrity;
+mod metrics;
+mod negotiate;
+mod notes;
+mod platform;
+mod publish;
+mod publish_queue;
+mod readiness;
+mod rollback;
+mod rollout;
+mod scanner;
+mod signing;
+mod state;
+mod stats;
+mod tauri_v1;
+#[cfg(test)]
+mod test_support;
+mod tls;
+mod upload;
+mod validate;
+mod versions;
+mod webhook;
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
     dotenv::dotenv().ok();
-    let app_url = dotenv::var("APP_URL").unwrap();
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if let Err(e) = check_kiosk_directory(&config.kiosk_directory) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
     let subscriber = Subscriber::builder()
         .with_writer(io::stderr)
-        .with_max_level(
-            dotenv::var("MAX_LOG_LEVEL")
-                .map(|level| level.parse().unwrap())
-                .unwrap_or(Level::WARN),
-        )
+        .with_max_level(config.max_log_level)
         .with_file(true)
         .pretty()
         .finish();
     tracing::subscriber::set_global_default(subscriber).unwrap();
 
-    let app = Router::new()
+    match cli.command {
+        None | Some(Command::Serve) => {}
+        Some(command) => std::process::exit(cli::run(command, &config).await),
+    }
+
+    let state = AppState::build(config).await.unwrap_or_else(|e| {
+        tracing::error!("failed to connect to database: {}", e);
+        std::process::exit(1);
+    });
+    integrity::start(state.clone());
+
+    let app = app(&state);
+    let tls = tls::load(&state.config).await.unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    });
+    let listener = listen(&state.config).await.unwrap();
+
+    let shutdown_started = Arc::new(Notify::new());
+    let server: Pin<Box<dyn Future<Output = io::Result<()>> + Send>> = match tls {
+        None => Box::pin(
+            serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown({
+                let shutdown_started = shutdown_started.clone();
+                let state = state.clone();
+                async move {
+                    shutdown_signal().await;
+                    state.shutdown.cancel();
+                    shutdown_started.notify_one();
+                }
+            })
+            .into_future(),
+        ),
+        Some(tls) => {
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                let shutdown_started = shutdown_started.clone();
+                let state = state.clone();
+                async move {
+                    shutdown_signal().await;
+                    state.shutdown.cancel();
+                    handle.graceful_shutdown(None);
+                    shutdown_started.notify_one();
+                }
+            });
+            tracing::info!("serving https on {}", state.config.app_url);
+            Box::pin(
+                axum_server::from_tcp_rustls(listener.into_std().unwrap(), tls)
+                    .handle(handle)
+                    .serve(app.into_make_service_with_connect_info::<SocketAddr>()),
+            )
+        }
+    };
+
+    // the server stops on its own once every connection is closed, this only
+    // cuts it short when downloads are still streaming after the timeout
+    let drain_downloads = async {
+        shutdown_started.notified().await;
+        notify_systemd(&state.config, sd_notify::NotifyState::Stopping);
+        tracing::info!(
+            "shutting down, waiting for {} downloads in flight",
+            state.downloads_in_flight()
+        );
+        if state
+            .wait_for_downloads(state.config.shutdown_timeout)
+            .await
+        {
+            std::future::pending::<()>().await;
+        }
+        tracing::warn!(
+            "shutdown timeout reached with {} downloads still in flight",
+            state.downloads_in_flight()
+        );
+    };
+
+    tokio::select! {
+        result = server => result.unwrap(),
+        _ = drain_downloads => {}
+    }
+}
+
+// every route with its middleware, shared by main and the router tests
+fn app(state: &Arc<AppState>) -> Router {
+    let routes = Router::new()
         .route("/health", get(health_check_handler))
+        .route("/ready", get(readiness::readiness_handler))
+        .route("/metrics", get(metrics::metrics_handler))
         .route("/kiosk-version", post(create_kiosk_version))
+        .route(
+            "/kiosk-version/{version}/if-changed",
+            post(create_kiosk_version_if_changed),
+        )
+        .route("/kiosk-version/{version}/manifest", post(register_manifest))
         .route("/latest-version", get(get_latest_version))
+        .route(
+            "/latest-version/channels",
+            get(versions::get_latest_channels),
+        )
+        .route("/v1/latest-version", get(tauri_v1::get_latest_version))
+        .route("/admin/refresh/{version}", post(versions::refresh_version))
+        .route("/admin/replay-webhooks", post(webhook::replay_webhooks))
+        .route("/admin/downloads/stream", get(events::stream_downloads))
+        .route("/admin/rollout-events", get(rollout::get_rollout_events))
+        .route("/rollback", post(rollback::rollback))
+        .route("/rollforward", post(rollback::rollforward))
+        .route("/stats", get(stats::get_stats))
+        .route("/changelog", get(versions::get_changelog))
+        .route("/diff", get(versions::get_version_diff))
+        // the binary is streamed through the verifier, never buffered
+        .route(
+            "/validate-artifact",
+            post(validate::validate_artifact).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/versions", get(versions::get_versions))
+        .route(
+            "/versions/status",
+            get(versions::get_versions_status).post(versions::get_batch_status),
+        )
+        .route(
+            "/versions/latest/status",
+            get(versions::get_latest_version_status),
+        )
+        .route("/versions/{version}", get(versions::get_version_details))
+        .route("/versions/{version}/notes", get(notes::get_version_notes))
+        .route("/versions/{version}/urls", get(versions::get_version_urls))
+        .route(
+            "/versions/{version}/archive.tar.gz",
+            get(archive::get_version_archive),
+        )
+        .route(
+            "/versions/{version}/{platform}/signature",
+            get(versions::get_platform_signature),
+        )
+        .route(
+            "/versions/{version}/status",
+            get(versions::get_version_status),
+        )
+        .route(
+            "/versions/{version}/yank",
+            post(versions::yank_version).delete(versions::unyank_version),
+        )
+        .route(
+            "/versions/{version}/approve",
+            post(versions::approve_version),
+        )
+        .route(
+            "/versions/{version}/required",
+            post(versions::mark_required).delete(versions::unmark_required),
+        )
         .route(
             "/latest-version/{platform}",
             get(get_latest_version_by_platform),
         )
+        .route(
+            "/latest-version/{target}/{arch}",
+            get(get_latest_version_by_target),
+        )
         .route(
             "/download/{version}/{platform}/{filename}",
             get(download_file),
+        )
+        .route(
+            "/upload/{version}/{platform}/{filename}",
+            get(upload::get_upload_offset).patch(upload::upload_chunk),
+        )
+        .route(
+            "/upload/{version}/{platform}/{filename}/complete",
+            post(upload::complete_upload),
+        )
+        .method_not_allowed_fallback(method_not_allowed);
+    // the middleware wraps the outer router so requests outside the prefix
+    // still get request ids and security headers
+    let app = match state.config.route_prefix.as_str() {
+        "" => routes,
+        prefix => Router::new().nest(prefix, routes),
+    };
+    app.layer(compression_layer(&state.config))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            add_security_headers,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            log_slow_requests,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            propagate_request_id,
+        ))
+        .with_state(state.clone())
+}
+
+// binds APP_URL and only then tells systemd the server is ready, the cache is
+// already warmed by now when WARM_CACHE_ON_START is set
+async fn listen(config: &Config) -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(&config.app_url).await?;
+    notify_systemd(config, sd_notify::NotifyState::Ready);
+
+    Ok(listener)
+}
+
+// tells systemd about `Type=notify` state changes, a no-op when the service
+// isn't started by systemd (there's no NOTIFY_SOCKET)
+fn notify_systemd(config: &Config, state: sd_notify::NotifyState) {
+    if !config.systemd_notify {
+        return;
+    }
+
+    if let Err(e) = sd_notify::notify(&[state]) {
+        tracing::warn!("failed to notify systemd: {}", e);
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.unwrap();
+    };
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .unwrap()
+            .recv()
+            .await;
+    };
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub uptime_secs: u64,
+    pub version: &'static str,
+}
+
+// reuses the id a gateway put in REQUEST_ID_HEADER so our logs line up with
+// its logs, otherwise generates one, either way every log line of the request
+// carries it and the response echoes it
+async fn propagate_request_id(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let header_name = &state.config.request_id_header;
+    let request_id = request
+        .headers()
+        .get(header_name)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(String::from)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!("request", request_id = %request_id);
+    let mut response = next.run(request).instrument(span).await;
+    if let Ok(value) = request_id.parse() {
+        response.headers_mut().insert(header_name, value);
+    }
+
+    response
+}
+
+// anything else is replaced by a generated id so a client can't inject
+// arbitrary text into the logs
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 128
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+// the router answers a wrong method with a bare 405, this gives it the same
+// error envelope as every other failure, axum still adds the Allow header
+async fn method_not_allowed() -> APIError {
+    APIError::MethodNotAllowed
+}
+
+// gzip for json and text when the client accepts it and the body is at least
+// COMPRESS_MIN_BYTES, smaller ones cost more cpu than they save. artifacts
+// are already compressed and the event stream has no length to go by
+fn compression_layer(config: &Config) -> CompressionLayer<impl Predicate> {
+    let compressible = |_: StatusCode, _: axum::http::Version, headers: &HeaderMap, _: &_| {
+        headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| {
+                content_type.starts_with("application/json")
+                    || content_type.starts_with("application/yaml")
+                    || (content_type.starts_with("text/")
+                        && !content_type.starts_with("text/event-stream"))
+            })
+    };
+
+    CompressionLayer::new()
+        .compress_when(SizeAbove::new(config.compress_min_bytes).and(compressible))
+}
+
+// SECURITY_HEADERS (or the defaults) on every response
+async fn add_security_headers(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    let mut response = next.run(request).await;
+    for (name, value) in &state.config.security_headers {
+        response.headers_mut().insert(name, value.clone());
+    }
+
+    response
+}
+
+// a warning for every request slower than SLOW_REQUEST_MS, only the time
+// until the response head, streamed bodies aren't counted
+async fn log_slow_requests(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> axum::response::Response {
+    if state.config.slow_request.is_none() {
+        return next.run(request).await;
+    }
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let started = std::time::Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+    if is_slow_request(&state.config, elapsed) {
+        tracing::warn!(
+            "slow request {} {} took {}ms",
+            method,
+            path,
+            elapsed.as_millis()
         );
-    let listener = TcpListener::bind(app_url).await.unwrap();
-    serve(listener, app).await.unwrap();
+    }
+
+    response
+}
+
+// strictly longer than SLOW_REQUEST_MS, never without it
+fn is_slow_request(config: &Config, elapsed: Duration) -> bool {
+    config
+        .slow_request
+        .is_some_and(|threshold| elapsed > threshold)
 }
 
-pub async fn health_check_handler() -> impl IntoResponse {
-    "OK"
+pub async fn health_check_handler(State(state): State<Arc<AppState>>) -> Response<Body> {
+    if !state.config.health_json {
+        return "OK".into_response();
+    }
+
+    Json(HealthResponse {
+        status: "ok",
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        version: env!("CARGO_PKG_VERSION"),
+    })
+    .into_response()
 }
 
 #[derive(Serialize, Deserialize)]
@@ -78,125 +472,290 @@ pub struct CreateKioskVersionRequest {
 // - [x] notes input ke txt
 
 pub async fn create_kiosk_version(
+    State(state): State<Arc<AppState>>,
     request: Json<CreateKioskVersionRequest>,
 ) -> Result<StatusCode, APIError> {
-    let kiosk_directory = dotenv::var("KIOSK_DIRECTORY").unwrap();
-    let folder_version_name = request.version.clone();
-    let kiosk_version_directory =
-        kiosk_directory.clone() + &String::from("/") + &folder_version_name;
-
-    let platforms: Vec<String> = vec![
-        "windows_x86_64".to_string(),
-        "linux_x86_64".to_string(),
-        "darwin_x86_64".to_string(),
-        "darwin_aarch64".to_string(),
-    ];
-
-    // find folder if exist
-    match fs::try_exists(kiosk_version_directory.clone()).await {
-        Ok(exists) => {
-            if exists {
-                tracing::error!(
-                    "failed to create folder {} because folder already exists",
-                    folder_version_name
-                );
-                return Err(APIError::FolderExist);
-            } else {
-                fs::create_dir(kiosk_version_directory.clone())
-                    .await
-                    .inspect_err(|e| {
-                        tracing::error!("failed to create kiosk directory: {:?}", e)
-                    })?;
+    let config = state.config.clone();
+    let request = request.0;
+    let (version, notes) = (request.version.clone(), request.notes.clone());
+    let _lock = state.lock_version(&version).await;
+    state
+        .publish(
+            async move { publish::create_version(&config, &request.version, &request.notes).await },
+        )
+        .await?;
 
-                let permissions = Permissions::from_mode(0o755);
+    state.latest_version_cache.invalidate().await;
+    state.notify_published(&version, &notes);
 
-                // set permission
-                fs::set_permissions(kiosk_version_directory.clone(), permissions)
-                    .await
-                    .inspect_err(|e| tracing::error!("failed to set permission: {}", e))?;
+    Ok(StatusCode::OK)
+}
 
-                // writes note into txt file
-                let content = request.notes.clone();
-                fs::write(
-                    kiosk_version_directory.clone() + &String::from("/") + "notes.txt",
-                    content,
-                )
-                .await
-                .inspect_err(|e| {
-                    tracing::error!("failed to write file: {}", e);
-                })?;
-
-                for platform in platforms {
-                    let kiosk_version_platform_directory =
-                        kiosk_version_directory.clone() + &String::from("/") + &platform;
-                    fs::create_dir(kiosk_version_platform_directory.clone())
-                        .await
-                        .inspect_err(|e| {
-                            tracing::error!("failed to create kiosk directory: {:?}", e)
-                        })?;
-                }
-            }
+#[derive(Serialize, Deserialize)]
+pub struct CreateKioskVersionIfChangedRequest {
+    #[serde(default)]
+    pub notes: String,
+    // platform folder name to hex encoded sha256 of its artifact
+    pub checksums: HashMap<String, String>,
+}
+
+// lets CI skip republishing an identical build, 304 when every checksum matches
+// the artifacts of the version currently being served
+pub async fn create_kiosk_version_if_changed(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+    request: Json<CreateKioskVersionIfChangedRequest>,
+) -> Result<StatusCode, APIError> {
+    // with nothing to compare every build would look unchanged
+    if request.checksums.is_empty() {
+        return Err(APIError::MissingChecksums);
+    }
+    if let Ok(latest) = resolve_latest_version(&state.config).await {
+        if !has_checksum_changed(&state, &latest.version, &request.checksums).await? {
+            tracing::info!(
+                "not creating version {}, artifacts are identical to {}",
+                version,
+                latest.version
+            );
+            return Ok(StatusCode::NOT_MODIFIED);
         }
+    }
+
+    let config = state.config.clone();
+    let notes = request.notes.clone();
+    let published = version.clone();
+    let _lock = state.lock_version(&version).await;
+    state
+        .publish(async move { publish::create_version(&config, &published, &request.notes).await })
+        .await?;
+    state.latest_version_cache.invalidate().await;
+    state.notify_published(&version, &notes);
+
+    Ok(StatusCode::OK)
+}
+
+// registers a CI built tauri manifest for an existing version, it's served as
+// is afterwards. every target has to be one of PLATFORMS and the artifact its
+// url names has to be in the platform folder already, next to its `.sig`
+pub async fn register_manifest(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+    Json(manifest): Json<KioskVersionResponse>,
+) -> Result<StatusCode, APIError> {
+    if manifest.version != version {
+        tracing::warn!(
+            "manifest for {} was posted to version {}",
+            manifest.version,
+            version
+        );
+        return Err(APIError::InvalidVersion);
+    }
+    chrono::DateTime::parse_from_rfc3339(&manifest.pub_date)
+        .map_err(|_| APIError::InvalidTimestamp)?;
+
+    let version_directory = versions::existing_version_directory(&state, &version).await?;
+    for (target, details) in manifest.platforms.iter() {
+        let Some(platform) = state
+            .config
+            .platforms
+            .iter()
+            .find(|platform| scanner::manifest_key(platform) == *target)
+        else {
+            return Err(APIError::PlatformNotFound(
+                target.clone(),
+                state
+                    .config
+                    .platforms
+                    .iter()
+                    .map(|platform| scanner::manifest_key(platform))
+                    .collect(),
+            ));
+        };
+        ensure_manifest_files(&state.config, &version, platform, &details.url).await?;
+    }
+
+    let _lock = state.lock_version(&version).await;
+    let content = serde_json::to_vec_pretty(&manifest)?;
+    state
+        .publish(async move {
+            tokio::fs::write(version_directory.join(scanner::MANIFEST_FILE), content)
+                .await
+                .inspect_err(|e| tracing::error!("failed to store manifest: {}", e))?;
+            Ok(())
+        })
+        .await?;
+    state.latest_version_cache.invalidate().await;
+    tracing::info!("registered manifest for version {}", version);
+
+    Ok(StatusCode::OK)
+}
+
+// the manifest registered with register_manifest and its pub_date, None when
+// the version has none
+async fn stored_manifest(
+    config: &Config,
+    version: &str,
+) -> Result<Option<(KioskVersionResponse, SystemTime)>, APIError> {
+    let path = std::path::Path::new(&config.kiosk_directory)
+        .join(version)
+        .join(scanner::MANIFEST_FILE);
+    let content = match tokio::fs::read(&path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
         Err(e) => {
-            tracing::error!("failed to check if folder exists: {}", e);
-            return Err(APIError::Internal);
+            tracing::error!("failed to read {}: {}", path.display(), e);
+            return Err(e.into());
         }
+    };
+
+    let mut manifest: KioskVersionResponse = serde_json::from_slice(&content)
+        .inspect_err(|e| tracing::error!("invalid manifest {}: {}", path.display(), e))?;
+    // a version without a single platform is never complete
+    if manifest.platforms.is_empty() {
+        tracing::warn!("ignoring {}, it lists no platforms", path.display());
+        return Ok(None);
     }
+    let pub_date = chrono::DateTime::parse_from_rfc3339(&manifest.pub_date)
+        .map_err(|_| APIError::InvalidTimestamp)?
+        .with_timezone(&Utc)
+        .into();
+    manifest.required =
+        scanner::is_required(&std::path::Path::new(&config.kiosk_directory).join(version)).await;
 
-    Ok(StatusCode::OK)
+    Ok(Some((manifest, pub_date)))
+}
+
+async fn has_checksum_changed(
+    state: &AppState,
+    version: &str,
+    checksums: &HashMap<String, String>,
+) -> Result<bool, APIError> {
+    for (platform, checksum) in checksums {
+        let platform = Platform::parse(&state.config, platform)?;
+        let files = match scanner::read_platform(&state.config, version, &platform).await? {
+            Some(files) => files,
+            None => return Ok(true),
+        };
+        let Some(artifact) = files.artifact else {
+            return Ok(true);
+        };
+
+        let current = state
+            .hash_cache
+            .sha256(&files.folder.join(artifact))
+            .await?;
+        if !current.eq_ignore_ascii_case(checksum) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+// the artifact a registered manifest points `platform` at and its signature
+// are in the platform folder, the file name is the last segment of the url
+async fn ensure_manifest_files(
+    config: &Config,
+    version: &str,
+    platform: &str,
+    url: &str,
+) -> Result<(), APIError> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let artifact = path.rsplit('/').next().unwrap_or_default();
+    let folder = scanner::locate_platform_directory(config, version, platform).await?;
+    let (Some(folder), true) = (folder, scanner::is_plain_file_name(artifact)) else {
+        tracing::warn!(
+            "manifest of {} points {} at {}, which isn't in the version",
+            version,
+            platform,
+            url
+        );
+        return Err(APIError::FileOrPathNotExist);
+    };
+
+    for file in [artifact.to_string(), format!("{}.sig", artifact)] {
+        if !tokio::fs::metadata(folder.join(&file))
+            .await
+            .is_ok_and(|metadata| metadata.is_file())
+        {
+            tracing::warn!(
+                "manifest of {} references {}/{}, which doesn't exist",
+                version,
+                platform,
+                file
+            );
+            return Err(APIError::FileOrPathNotExist);
+        }
+    }
+
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlatformDetails {
     pub signature: String,
     pub url: String,
     pub name: Option<String>,
+    // artifact size in bytes for metered clients, ignored by the tauri updater
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Platforms {
-    #[serde(rename = "linux-x86_64")]
-    pub linux_x86_64: PlatformDetails,
-    #[serde(rename = "windows-x86_64")]
-    pub windows_x86_64: PlatformDetails,
-    #[serde(rename = "darwin-x86_64")]
-    pub darwin_x86_64: PlatformDetails,
-    #[serde(rename = "darwin-aarch64")]
-    pub darwin_aarch64: PlatformDetails,
-}
+// keyed by the tauri target (`linux-x86_64`), in the order of PLATFORMS so the
+// json output is the same on every call
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Platforms(IndexMap<String, PlatformDetails>);
 
 impl Platforms {
-    fn iter(&self) -> impl Iterator<Item = (&str, &PlatformDetails)> {
-        vec![
-            ("linux_x86_64", &self.linux_x86_64),
-            ("windows_x86_64", &self.windows_x86_64),
-            ("darwin_x86_64", &self.darwin_x86_64),
-            ("darwin_aarch64", &self.darwin_aarch64),
-        ]
-        .into_iter()
+    // the servable platforms of an inspected version, absent ones left out
+    fn from_inspection(config: &Config, version: &str, inspection: &VersionInspection) -> Self {
+        Self(
+            inspection
+                .platforms
+                .iter()
+                .filter(|(_, platform)| platform.is_available())
+                .map(|(name, platform)| {
+                    (
+                        scanner::manifest_key(name),
+                        platform_details(config, version, name, platform),
+                    )
+                })
+                .collect(),
+        )
     }
-}
 
-impl Platforms {
-    // Returns a mutable iterator over all PlatformDetails
-    fn iter_mut(&mut self) -> impl Iterator<Item = &mut PlatformDetails> {
-        vec![
-            &mut self.linux_x86_64,
-            &mut self.windows_x86_64,
-            &mut self.darwin_x86_64,
-            &mut self.darwin_aarch64,
-        ]
-        .into_iter()
+    fn iter(&self) -> impl Iterator<Item = (&String, &PlatformDetails)> {
+        self.0.iter()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn get(&self, target: &str) -> Option<&PlatformDetails> {
+        self.0.get(target)
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// the tauri v2 updater shape served by /latest-version, tauri v1 clients use
+// /v1/latest-version (see tauri_v1)
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KioskVersionResponse {
     pub version: String,
     pub notes: String,
     #[serde(rename = "pub_date")]
     pub pub_date: String,
     pub platforms: Platforms,
+    // set when the version carries the REQUIRED_MARKER, the client must
+    // install it before continuing
+    #[serde(default)]
+    pub required: bool,
+    // maintenance banner, independent of the version being offered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_severity: Option<MessageSeverity>,
 }
 
 // TODO
@@ -205,304 +764,2329 @@ pub struct KioskVersionResponse {
 // - [x] check isi folder terbaru
 // - [x] jika isi folder terbaru kosong maka return folder terbaru yang ada isinya
 
-pub async fn get_latest_version() -> Result<Json<KioskVersionResponse>, APIError> {
-    let kiosk_directory = dotenv::var("KIOSK_DIRECTORY").unwrap();
-    let mut modified_date: SystemTime = SystemTime::UNIX_EPOCH;
-    let kiosk_url = dotenv::var("KIOSK_DOWNLOADABLE_URL").unwrap();
+#[derive(Default, Deserialize)]
+pub struct LatestVersionQuery {
+    pub lang: Option<String>,
+    // rfc3339 timestamp, answers what a client would have been served then
+    pub as_of: Option<String>,
+    // tauri's `{{target}}` and `{{arch}}`, see get_latest_version
+    pub target: Option<String>,
+    pub arch: Option<String>,
+}
 
-    let mut platforms = Platforms {
-        linux_x86_64: PlatformDetails {
-            signature: "".to_string(),
-            url: "".to_string(),
-            name: Some("linux_x86_64".to_string()),
-        },
-        windows_x86_64: PlatformDetails {
-            signature: "".to_string(),
-            url: "".to_string(),
-            name: Some("windows_x86_64".to_string()),
-        },
-        darwin_x86_64: PlatformDetails {
-            signature: "".to_string(),
-            url: "".to_string(),
-            name: Some("darwin_x86_64".to_string()),
-        },
-        darwin_aarch64: PlatformDetails {
-            signature: "".to_string(),
-            url: "".to_string(),
-            name: Some("darwin_aarch64".to_string()),
-        },
+// older kiosks send neither `target` nor `arch` and get the manifest with
+// every platform. newer ones send both and get only their platform's block
+// in the shape of /latest-version/{platform}, 204 when the latest version has
+// no build for it. both paths share the resolution, cache, rollout window and
+// banner of latest_manifest
+pub async fn get_latest_version(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LatestVersionQuery>,
+    headers: HeaderMap,
+) -> Result<axum::response::Response, APIError> {
+    // checked first so a misconfigured client gets a 404 and not a 204 from
+    // outside the rollout window
+    let platform = match (&query.target, &query.arch) {
+        (None, None) => None,
+        (Some(target), Some(arch)) => Some(
+            Platform::parse(&state.config, &format!("{}_{}", target, arch)).map_err(|_| {
+                APIError::PlatformNotFound(
+                    format!("{}/{}", target, arch),
+                    state.config.platforms.clone(),
+                )
+            })?,
+        ),
+        (Some(name), None) | (None, Some(name)) => {
+            return Err(APIError::PlatformNotFound(
+                name.clone(),
+                state.config.platforms.clone(),
+            ))
+        }
     };
 
-    let mut entries = fs::read_dir(kiosk_directory.clone()).await?;
-    let mut versions = Vec::new();
+    let format = negotiate::Format::from_headers(&headers);
+    let response = latest_manifest(&state, &query, &headers).await?;
+    let Some(platform) = platform else {
+        return format.respond(&response);
+    };
+    format.respond(&PlatformVersionResponse::from_manifest(
+        response, &platform,
+    )?)
+}
 
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
-        if path.is_dir() {
-            if let Some(folder_name) = path.file_name().and_then(|n| n.to_str()) {
-                if let Ok(ver) = folder_name.parse::<Version>() {
-                    versions.push((ver, folder_name.to_string()));
-                }
-            }
+// the full manifest /latest-version and /v1/latest-version answer with
+pub async fn latest_manifest(
+    state: &AppState,
+    query: &LatestVersionQuery,
+    headers: &HeaderMap,
+) -> Result<KioskVersionResponse, APIError> {
+    // kiosks keep what they run until the window opens, `as_of` lookups
+    // aren't update checks so they bypass it
+    if let (Some(window), None) = (&state.config.rollout_window, &query.as_of) {
+        if !window.contains(Utc::now()) {
+            tracing::debug!("outside the rollout window, not offering an update");
+            state
+                .rollout_log
+                .record(headers, None, RolloutDecision::OutsideWindow);
+            return Err(APIError::NoUpdate);
         }
     }
 
-    // Sort in descending order (latest first)
-    versions.sort_by(|a, b| b.0.cmp(&a.0));
-    let version_names: Vec<String> = versions.into_iter().map(|(_, name)| name).collect();
+    let as_of = query
+        .as_of
+        .as_deref()
+        .map(|as_of| {
+            chrono::DateTime::parse_from_rfc3339(as_of).map_err(|_| APIError::InvalidTimestamp)
+        })
+        .transpose()?
+        .map(|as_of| as_of.with_timezone(&Utc));
+    let key = ManifestKey {
+        as_of,
+        languages: match state.config.include_notes_in_manifest {
+            true => notes::preferred_languages(headers, query.lang.as_deref()),
+            false => Vec::new(),
+        },
+    };
 
-    for version in version_names.iter() {
-        let latest_folder = format!("{}/{}", kiosk_directory.clone(), version);
-        // count platform total
-        let platform_amount = platforms.iter().count();
-        let mut platform_amount_counter = 0;
-        for platform in platforms.iter_mut() {
-            let platform_name = match platform.name.clone() {
-                Some(name) => name,
-                None => {
-                    tracing::error!("failed to get platform name");
-                    return Err(APIError::FileOrPathNotExist);
-                }
+    let mut response = match state.latest_version_cache.get(&key).await {
+        Some(response) => response,
+        None => {
+            let mut response = match as_of {
+                Some(as_of) => resolve_latest_version_at(&state.config, as_of.into()).await?,
+                None => resolve_latest_version(&state.config).await?,
             };
-
-            let mut platforms_directory =
-                match fs::read_dir(latest_folder.clone() + &String::from("/") + &platform_name)
-                    .await
-                    .inspect_err(|e| {
-                        tracing::error!(
-                            "failed to read directory: {}",
-                            latest_folder.clone() + &String::from("/") + &platform_name
-                        );
-                    }) {
-                    Ok(entries) => entries,
-                    Err(e) => {
-                        // Handle the error here
-                        tracing::error!("failed to read directory: {}", e);
-                        return Err(APIError::FileOrPathNotExist);
-                    }
-                };
-
-            // checking file inside platform directory
-            let mut is_platform_folder_not_empty = false;
-            let mut is_signature_exist = false;
-            let mut is_downladble_file_exist = false;
-            while let Some(entry) = platforms_directory.next_entry().await? {
-                let metadata = entry.metadata().await?;
-                modified_date = metadata.created().or_else(|_| metadata.modified())?;
-                let path = entry.path();
-                // checking signature file
-                if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("sig") {
-                    // Read the content as string
-                    let content = fs::read_to_string(path.display().to_string())
-                        .await
-                        .map_err(|_| {
-                            tracing::error!(
-                                "failed to read file: {}",
-                                latest_folder.clone() + &String::from("/") + &platform_name
-                            );
-                            return APIError::FileOrPathNotExist;
-                        })?;
-                    platform.signature = content;
-                    is_signature_exist = true;
-                }
-
-                // checking file besides sig extension
-                if path.is_file() && path.extension().and_then(|e| e.to_str()) != Some("sig") {
-                    platform.url = format!(
-                        "{}/download/{}/{}/{}",
-                        kiosk_url,
-                        version,
-                        platform_name,
-                        path.file_name()
-                            .and_then(|s| s.to_str())
-                            .map_or("".to_string(), |s| s.to_string())
-                    );
-                    is_downladble_file_exist = true;
-                }
-                is_platform_folder_not_empty = is_signature_exist && is_downladble_file_exist;
+            if !key.languages.is_empty() {
+                let version_directory =
+                    std::path::Path::new(&state.config.kiosk_directory).join(&response.version);
+                (_, response.notes) =
+                    notes::read_localized_notes(&version_directory, &key.languages).await?;
             }
-            if is_platform_folder_not_empty {
-                platform_amount_counter += 1;
-            }
-        }
-        println!(
-            "platform_amount_counter: {} platform_amount: {}",
-            platform_amount_counter, platform_amount
-        );
-        if platform_amount == platform_amount_counter {
-            let dt: chrono::DateTime<Utc> = modified_date.into();
-            let pub_date = dt.to_rfc3339();
-            return Ok(Json(KioskVersionResponse {
-                version: version.to_string(),
-                notes: "ini notes".to_string(),
-                pub_date: pub_date.to_string(),
-                platforms: platforms,
-            }));
+            state.latest_version_cache.set(key, response.clone()).await;
+            response
         }
+    };
+
+    if !state.config.include_notes_in_manifest {
+        // clients fetch them from /versions/{version}/notes instead
+        response.notes = String::new();
+    }
+    // not cached so a banner shows up on the next poll
+    if let Some((message, severity)) = banner::current_message(&state.config).await {
+        response.message = Some(message);
+        response.message_severity = Some(severity);
+    }
+    if query.as_of.is_none() {
+        state
+            .rollout_log
+            .record(headers, Some(&response.version), RolloutDecision::Offered);
     }
 
-    Err(APIError::FileOrPathNotExist)
+    Ok(response)
 }
 
-#[derive(Serialize, Deserialize)]
+// which versions a lookup may resolve to, prereleases are only part of `Any`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Stable,
+    Any,
+}
 
-pub struct PlatformVersionResponse {
-    pub version: String,
-    pub notes: String,
-    pub pub_date: String,
-    pub url: String,
-    pub signature: String,
+impl Channel {
+    fn includes(self, config: &Config, version: &str) -> bool {
+        match self {
+            Channel::Any => true,
+            Channel::Stable => scanner::parse_version(config, version)
+                .is_some_and(|version| version.pre.is_empty()),
+        }
+    }
 }
 
-async fn get_latest_version_by_platform(
-    Path((platform)): Path<(String)>,
-) -> Result<Json<PlatformVersionResponse>, APIError> {
-    let kiosk_directory = dotenv::var("KIOSK_DIRECTORY").unwrap();
-    let mut modified_date: SystemTime = SystemTime::UNIX_EPOCH;
-    let kiosk_url = dotenv::var("KIOSK_DOWNLOADABLE_URL").unwrap();
-    let platform_name = &platform;
-
-    let mut entries = fs::read_dir(kiosk_directory.clone()).await?;
-    let mut versions = Vec::new();
-
-    while let Some(entry) = entries.next_entry().await? {
-        let path = entry.path();
-        if path.is_dir() {
-            if let Some(folder_name) = path.file_name().and_then(|n| n.to_str()) {
-                if let Ok(ver) = folder_name.parse::<Version>() {
-                    versions.push((ver, folder_name.to_string()));
-                }
+pub async fn resolve_latest_version(config: &Config) -> Result<KioskVersionResponse, APIError> {
+    resolve_latest_version_in(config, Channel::Any).await
+}
+
+pub async fn resolve_latest_version_in(
+    config: &Config,
+    channel: Channel,
+) -> Result<KioskVersionResponse, APIError> {
+    scanner::ensure_storage(&config.kiosk_directory).await?;
+    // a rollback pin wins as long as the pinned version is still servable
+    if let Some(pinned) = rollback::read_pin(config)
+        .await?
+        .filter(|pinned| channel.includes(config, pinned))
+    {
+        let pinned_folder = std::path::Path::new(&config.kiosk_directory).join(&pinned);
+        if !scanner::is_yanked(&pinned_folder).await {
+            if let Some((response, _)) = version_manifest(config, &pinned).await? {
+                return Ok(response);
             }
         }
+        tracing::warn!(
+            "pinned version {} isn't servable, ignoring the rollback pin",
+            pinned
+        );
     }
 
-    // Sort in descending order (latest first)
-    versions.sort_by(|a, b| b.0.cmp(&a.0));
-    let version_names: Vec<String> = versions.into_iter().map(|(_, name)| name).collect();
+    resolve_channel_at(config, SystemTime::now(), channel).await
+}
 
-    for version in version_names.iter() {
-        let latest_folder = format!("{}/{}", kiosk_directory.clone(), version);
-        // count platform total
-        let mut platform_amount_counter = 0;
-        let mut platform_directory =
-            match fs::read_dir(latest_folder.clone() + &String::from("/") + &platform_name)
-                .await
-                .inspect_err(|e| {
-                    tracing::error!(
-                        "failed to read directory: {}",
-                        latest_folder.clone() + &String::from("/") + &platform_name
-                    );
-                }) {
-                Ok(entries) => entries,
-                Err(e) => {
-                    // Handle the error here
-                    tracing::error!("failed to read directory: {}", e);
-                    return Err(APIError::FileOrPathNotExist);
-                }
-            };
+// the version that was servable at `at`, versions published later are skipped
+pub async fn resolve_latest_version_at(
+    config: &Config,
+    at: SystemTime,
+) -> Result<KioskVersionResponse, APIError> {
+    resolve_channel_at(config, at, Channel::Any).await
+}
 
-        // checking file inside platform directory
-        let mut is_platform_folder_not_empty = false;
-        let mut is_signature_exist = false;
-        let mut is_downladble_file_exist = false;
-        let mut signature = "".to_string();
-        let mut url = "".to_string();
-        while let Some(entry) = platform_directory.next_entry().await? {
-            let metadata = entry.metadata().await?;
-            modified_date = metadata.created().or_else(|_| metadata.modified())?;
-            let path = entry.path();
-            // checking signature file
-            if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("sig") {
-                // Read the content as string
-                let content = fs::read_to_string(path.display().to_string())
-                    .await
-                    .map_err(|_| {
-                        tracing::error!(
-                            "failed to read file: {}",
-                            latest_folder.clone() + &String::from("/") + &platform_name
-                        );
-                        return APIError::FileOrPathNotExist;
-                    })?;
-                signature = content;
-                is_signature_exist = true;
-            }
+async fn resolve_channel_at(
+    config: &Config,
+    at: SystemTime,
+    channel: Channel,
+) -> Result<KioskVersionResponse, APIError> {
+    let kiosk_directory = &config.kiosk_directory;
 
-            // checking file besides sig extension
-            if path.is_file() && path.extension().and_then(|e| e.to_str()) != Some("sig") {
-                url = format!(
-                    "{}/download/{}/{}/{}",
-                    kiosk_url,
-                    version,
-                    platform_name,
-                    path.file_name()
-                        .and_then(|s| s.to_str())
-                        .map_or("".to_string(), |s| s.to_string())
-                );
-                is_downladble_file_exist = true;
-            }
-            is_platform_folder_not_empty = is_signature_exist && is_downladble_file_exist;
+    let version_names: Vec<String> = scanner::list_versions(config)
+        .await?
+        .versions
+        .into_iter()
+        .map(|(_, name)| name)
+        .filter(|name| channel.includes(config, name))
+        .collect();
+
+    for version in version_names
+        .iter()
+        .take(scan_limit(config, &version_names))
+    {
+        let latest_folder = format!("{}/{}", kiosk_directory, version);
+        if scanner::is_yanked(std::path::Path::new(&latest_folder)).await {
+            tracing::debug!("skipping yanked version {}", version);
+            continue;
         }
-        if is_platform_folder_not_empty {
-            platform_amount_counter += 1;
+        if let Some((response, modified_date)) = version_manifest(config, version).await? {
+            if !is_publish_delay_elapsed(config, version, modified_date, at) {
+                continue;
+            }
+            return Ok(response);
         }
+    }
 
-        if platform_amount_counter == 1 {
-            let dt: chrono::DateTime<Utc> = modified_date.into();
-            let pub_date = dt.to_rfc3339();
-            return Ok(Json(PlatformVersionResponse {
-                version: version.to_string(),
-                pub_date,
-                notes: "".to_string(),
-                url: url.clone(),
-                signature: signature.clone(),
-            }));
+    warn_scan_limit_reached(config, &version_names);
+
+    // a known good version pinned for incidents where nothing newer is servable
+    if let Some(fallback) = config
+        .fallback_version
+        .as_ref()
+        .filter(|fallback| channel.includes(config, fallback))
+    {
+        let fallback_folder = format!("{}/{}", kiosk_directory, fallback);
+        if tokio::fs::try_exists(&fallback_folder).await?
+            && !scanner::is_yanked(std::path::Path::new(&fallback_folder)).await
+        {
+            if let Some((response, _)) = version_manifest(config, fallback).await? {
+                tracing::warn!(
+                    "no complete version found, serving FALLBACK_VERSION {}",
+                    fallback
+                );
+                return Ok(response);
+            }
         }
+        tracing::error!(
+            "FALLBACK_VERSION {} doesn't exist or isn't complete",
+            fallback
+        );
     }
 
     Err(APIError::FileOrPathNotExist)
 }
 
-async fn download_file(
-    Path((version, platform, filename)): Path<(String, String, String)>,
-) -> Result<Response<Body>, APIError> {
-    let kiosk_directory = dotenv::var("KIOSK_DIRECTORY").unwrap();
-    let path = std::path::Path::new(&kiosk_directory)
-        .join(&version)
-        .join(&platform)
-        .join(&filename);
+// the manifest of one version and when its last artifact landed, None when a
+// platform is incomplete
+async fn version_manifest(
+    config: &Config,
+    version: &str,
+) -> Result<Option<(KioskVersionResponse, SystemTime)>, APIError> {
+    let version_directory = std::path::Path::new(&config.kiosk_directory).join(version);
+    if scanner::is_pending(&version_directory).await {
+        tracing::debug!("version {} is waiting for approval", version);
+        return Ok(None);
+    }
+    if let Some(stored) = stored_manifest(config, version).await? {
+        return Ok(Some(stored));
+    }
 
-    // // Check if file exists
-    if !path.clone().exists() {
-        return Err(APIError::NotFound);
+    let latest_folder = format!("{}/{}", config.kiosk_directory, version);
+    let inspection = scanner::inspect_version(config, version).await?;
+    let incomplete_reasons: Vec<String> = inspection
+        .platforms
+        .iter()
+        .filter(|(_, platform)| platform.is_available() && !platform.is_complete())
+        .map(|(name, platform)| format!("{} {}", name, platform.describe()))
+        .collect();
+    if !incomplete_reasons.is_empty() {
+        tracing::debug!(
+            "version {} is incomplete: {}",
+            version,
+            incomplete_reasons.join(", ")
+        );
+        return Ok(None);
     }
 
-    let mime_type = mime_guess::from_path(&path).first_or_octet_stream();
-    let file = tokio::fs::File::open(path)
-        .await
-        .inspect_err(|e| tracing::error!("failed to open file: {:?}", e))?;
-    let stream = tokio_util::io::ReaderStream::new(file);
+    let notes = scanner::read_notes(std::path::Path::new(&latest_folder)).await?;
+    let required = scanner::is_required(std::path::Path::new(&latest_folder)).await;
+    let Some(manifest) = to_manifest(config, version, &inspection, notes, required) else {
+        tracing::debug!("version {} has no platform folders", version);
+        return Ok(None);
+    };
 
-    let mut headers = HeaderMap::new();
-    // headers.insert(header::CONTENT_TYPE, mime_type.as_ref().parse().unwrap());
-    headers.insert(
-        header::CONTENT_TYPE,
-        mime_type.as_ref().parse().map_err(|e| {
-            tracing::error!("failed to parse mime type {}", e);
-            APIError::Internal
-        })?,
-    );
-    headers.insert(
-        header::CONTENT_DISPOSITION,
-        format!("attachment; filename=\"{}\"", filename)
-            .parse()
-            .map_err(|e| {
-                tracing::error!("failed to parse content disposition {}", e);
-                APIError::Internal
-            })?,
-    );
+    Ok(Some((manifest, inspection.modified)))
+}
 
-    let mut response = Response::new(Body::from_stream(stream));
-    *response.headers_mut() = headers;
+// the manifest of an inspected version, None when no platform has a build.
+// /latest-version, /versions/{version} and the per-platform endpoint all
+// assemble urls and signatures through here and platform_details
+fn to_manifest(
+    config: &Config,
+    version: &str,
+    inspection: &VersionInspection,
+    notes: String,
+    required: bool,
+) -> Option<KioskVersionResponse> {
+    let platforms = Platforms::from_inspection(config, version, inspection);
+    if platforms.is_empty() {
+        return None;
+    }
 
-    Ok(response)
+    let pub_date: chrono::DateTime<Utc> = inspection.modified.into();
+    Some(KioskVersionResponse {
+        version: version.to_string(),
+        notes,
+        pub_date: pub_date.to_rfc3339(),
+        platforms,
+        required,
+        message: None,
+        message_severity: None,
+    })
+}
+
+fn platform_details(
+    config: &Config,
+    version: &str,
+    platform: &str,
+    inspection: &PlatformInspection,
+) -> PlatformDetails {
+    PlatformDetails {
+        signature: inspection
+            .signature
+            .as_ref()
+            .map(|signature| manifest_signature(config, version, platform, signature))
+            .unwrap_or_default(),
+        url: artifact_url(config, version, platform, inspection).unwrap_or_default(),
+        name: Some(platform.to_string()),
+        size: inspection.artifact_size(),
+    }
+}
+
+fn artifact_url(
+    config: &Config,
+    version: &str,
+    platform: &str,
+    inspection: &PlatformInspection,
+) -> Option<String> {
+    inspection
+        .artifact()
+        .map(|artifact| download_url(config, version, platform, artifact))
+}
+
+// bounds how many version folders a single request inspects when none of the
+// newest ones are complete
+fn scan_limit(config: &Config, version_names: &[String]) -> usize {
+    config
+        .max_scan_versions
+        .unwrap_or(version_names.len())
+        .min(version_names.len())
+}
+
+fn warn_scan_limit_reached(config: &Config, version_names: &[String]) {
+    if scan_limit(config, version_names) < version_names.len() {
+        tracing::warn!(
+            "stopped after the newest {} of {} versions without finding a complete one, MAX_SCAN_VERSIONS reached",
+            scan_limit(config, version_names),
+            version_names.len()
+        );
+    }
+}
+
+// absolute by default since the tauri updater expects it, RELATIVE_URLS lets
+// clients resolve the path against whichever host served the manifest
+fn url_base(config: &Config) -> String {
+    if config.relative_urls {
+        config.route_prefix.clone()
+    } else {
+        format!("{}{}", config.kiosk_downloadable_url, config.route_prefix)
+    }
+}
+
+fn download_url(config: &Config, version: &str, platform: &str, filename: &str) -> String {
+    let base = url_base(config);
+    let query = signing::signed_query(config, version, platform, filename);
+
+    format!(
+        "{}/download/{}/{}/{}{}",
+        base, version, platform, filename, query
+    )
+}
+
+fn manifest_signature(config: &Config, version: &str, platform: &str, signature: &str) -> String {
+    match config.signature_mode {
+        SignatureMode::Inline => signature.to_string(),
+        SignatureMode::Url => format!(
+            "{}/versions/{}/{}/signature",
+            url_base(config),
+            version,
+            platform
+        ),
+    }
+}
+
+// the same file on every MIRROR_BASE_URLS host, laid out like the cdn, for
+// clients that failed the primary url
+fn mirror_urls(config: &Config, version: &str, platform: &str, filename: &str) -> Vec<String> {
+    let query = signing::signed_query(config, version, platform, filename);
+    config
+        .mirror_base_urls
+        .iter()
+        .map(|base| {
+            format!(
+                "{}/{}/{}/{}{}",
+                base.trim_end_matches('/'),
+                version,
+                platform,
+                filename,
+                query
+            )
+        })
+        .collect()
+}
+
+// a complete version is only served once its newest file is at least
+// PUBLISH_DELAY_SECS old, giving CDNs time to pick up the artifacts
+fn is_publish_delay_elapsed(
+    config: &Config,
+    version: &str,
+    pub_date: SystemTime,
+    now: SystemTime,
+) -> bool {
+    let Ok(age) = now.duration_since(pub_date) else {
+        tracing::debug!("version {} wasn't published yet", version);
+        return false;
+    };
+    if age < config.publish_delay {
+        tracing::info!(
+            "withholding version {} for another {}s, PUBLISH_DELAY_SECS not elapsed",
+            version,
+            (config.publish_delay - age).as_secs()
+        );
+        return false;
+    }
+
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+
+pub struct PlatformVersionResponse {
+    pub version: String,
+    pub notes: String,
+    pub pub_date: String,
+    pub url: String,
+    pub signature: String,
+    // artifact size in bytes so a metered kiosk can decide before downloading
+    pub size: u64,
+    // same as the manifest's, the kiosk has to install before continuing
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl PlatformVersionResponse {
+    // the block of one platform, NoUpdate when the version has no build for it
+    fn from_manifest(
+        response: KioskVersionResponse,
+        platform: &Platform,
+    ) -> Result<Self, APIError> {
+        let details = response
+            .platforms
+            .get(&scanner::manifest_key(platform))
+            .ok_or(APIError::NoUpdate)?;
+
+        Ok(Self {
+            url: details.url.clone(),
+            signature: details.signature.clone(),
+            size: details.size.unwrap_or_default(),
+            version: response.version,
+            notes: response.notes,
+            pub_date: response.pub_date,
+            required: response.required,
+        })
+    }
+}
+
+async fn get_latest_version_by_platform(
+    State(state): State<Arc<AppState>>,
+    Path(platform): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<PlatformVersionResponse>, APIError> {
+    let platform = Platform::parse(&state.config, &platform)?;
+    latest_version_for_platform(&state, &platform, &headers).await
+}
+
+// the tauri `{{target}}/{{arch}}` endpoint template, `linux/x86_64` is the
+// linux_x86_64 platform. a pair matching no platform is a misconfigured
+// client and gets the supported platforms back
+async fn get_latest_version_by_target(
+    State(state): State<Arc<AppState>>,
+    Path((target, arch)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Json<PlatformVersionResponse>, APIError> {
+    let platform =
+        Platform::parse(&state.config, &format!("{}_{}", target, arch)).map_err(|_| {
+            APIError::PlatformNotFound(
+                format!("{}/{}", target, arch),
+                state.config.platforms.clone(),
+            )
+        })?;
+    latest_version_for_platform(&state, &platform, &headers).await
+}
+
+// the platform's block of what /latest-version serves, so the rollback pin
+// and everything else latest_manifest applies hold here too
+async fn latest_version_for_platform(
+    state: &AppState,
+    platform: &Platform,
+    headers: &HeaderMap,
+) -> Result<Json<PlatformVersionResponse>, APIError> {
+    let response = latest_manifest(state, &LatestVersionQuery::default(), headers).await?;
+
+    Ok(Json(PlatformVersionResponse::from_manifest(
+        response, platform,
+    )?))
+}
+
+// the name offered in Content-Disposition, without control characters, quotes
+// or backslashes that could break out of the header and shortened to
+// MAX_DISPOSITION_FILENAME_LENGTH characters keeping the extension
+fn disposition_filename(config: &Config, filename: &str) -> String {
+    let sanitized: String = filename
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .collect();
+    let max_length = config.max_disposition_filename_length;
+    if sanitized.chars().count() <= max_length {
+        return sanitized;
+    }
+
+    // multi part extensions like `.tar.gz` come from ARTIFACT_EXTENSIONS
+    let lowercase = sanitized.to_ascii_lowercase();
+    let extension_length = config
+        .artifact_extensions
+        .iter()
+        .filter(|extension| lowercase.ends_with(&extension.to_ascii_lowercase()))
+        .map(|extension| extension.len())
+        .max()
+        .or_else(|| sanitized.rfind('.').map(|dot| sanitized.len() - dot))
+        .unwrap_or(0);
+    let (stem, extension) = sanitized.split_at(sanitized.len() - extension_length);
+    let extension: String = extension.chars().take(max_length).collect();
+    let stem: String = stem
+        .chars()
+        .take(max_length.saturating_sub(extension.chars().count()))
+        .collect();
+
+    stem + &extension
+}
+
+async fn download_file(
+    State(state): State<Arc<AppState>>,
+    Path((version, platform, filename)): Path<(String, String, String)>,
+    Query(signed): Query<signing::SignedQuery>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    method: Method,
+) -> Result<Response<Body>, APIError> {
+    if state.config.signed_urls
+        && !signing::is_valid(&state.config, &version, &platform, &filename, &signed)
+    {
+        tracing::warn!(
+            "rejected download of {}/{}/{} with an expired or invalid signature",
+            version,
+            platform,
+            filename
+        );
+        return Err(APIError::Forbidden);
+    }
+
+    let platform = match platform.as_str() {
+        scanner::ATTACHMENTS => platform,
+        name => Platform::parse(&state.config, name)?.to_string(),
+    };
+
+    if state.config.block_incomplete_downloads && !is_published(&state.config, &version).await? {
+        tracing::warn!(
+            "blocked download of {}/{}/{}, the version isn't published",
+            version,
+            platform,
+            filename
+        );
+        return Err(APIError::NotFound);
+    }
+
+    // checked before redirecting too, the cdn location and the download
+    // counters only ever see real versions and plain file names
+    if !scanner::is_plain_file_name(&filename)
+        || scanner::parse_version(&state.config, &version).is_none()
+    {
+        return Err(APIError::NotFound);
+    }
+
+    if state.config.download_mode == DownloadMode::Redirect {
+        return redirect_to_cdn(&state, &version, &platform, &filename, &method);
+    }
+
+    let in_flight = state.start_download();
+    let path = scanner::artifact_path(&state.config, &version, &platform, &filename)
+        .await?
+        .ok_or(APIError::NotFound)?;
+
+    // // Check if file exists
+    if !path.clone().exists() {
+        return Err(APIError::NotFound);
+    }
+
+    let is_signature = scanner::is_signature_file(&filename);
+    // some browsers only show a download dialog for octet-stream
+    let mime_type = if is_signature {
+        mime_guess::mime::TEXT_PLAIN_UTF_8
+    } else if state.config.force_octet_stream {
+        mime_guess::mime::APPLICATION_OCTET_STREAM
+    } else {
+        mime_guess::from_path(&path).first_or_octet_stream()
+    };
+    // legacy verifiers check this, the hash comes from the sidecar so the body
+    // is still streamed straight from disk. a HEAD only gets it when it is
+    // already known, a probe shouldn't hash a whole installer
+    let content_md5 = match (state.config.emit_content_md5, method == Method::HEAD) {
+        (false, _) => None,
+        (true, true) => state.hash_cache.peek_content_md5(&path).await?,
+        (true, false) => Some(
+            state
+                .hash_cache
+                .content_md5(&path)
+                .await
+                .inspect_err(|e| tracing::error!("failed to hash {}: {}", path.display(), e))?,
+        ),
+    };
+    let length = tokio::fs::metadata(&path)
+        .await
+        .inspect_err(|e| tracing::error!("failed to stat file: {:?}", e))?
+        .len();
+
+    let mut headers = HeaderMap::new();
+    // headers.insert(header::CONTENT_TYPE, mime_type.as_ref().parse().unwrap());
+    headers.insert(
+        header::CONTENT_TYPE,
+        mime_type.as_ref().parse().map_err(|e| {
+            tracing::error!("failed to parse mime type {}", e);
+            APIError::Internal
+        })?,
+    );
+    let disposition = if is_signature {
+        "inline".to_string()
+    } else {
+        format!(
+            "attachment; filename=\"{}\"",
+            disposition_filename(&state.config, &filename)
+        )
+    };
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        disposition.parse().map_err(|e| {
+            tracing::error!("failed to parse content disposition {}", e);
+            APIError::Internal
+        })?,
+    );
+    if let Some(content_md5) = content_md5 {
+        headers.insert(
+            "content-md5",
+            content_md5.parse().map_err(|e| {
+                tracing::error!("failed to parse content md5 {}", e);
+                APIError::Internal
+            })?,
+        );
+    }
+
+    headers.insert(header::CONTENT_LENGTH, length.into());
+
+    // monitoring probes with HEAD, they get the headers without the file being
+    // opened or counted as a download
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        let file = tokio::fs::File::open(path)
+            .await
+            .inspect_err(|e| tracing::error!("failed to open file: {:?}", e))?;
+        let mut progress = DownloadProgress::start(
+            &state.download_events,
+            &version,
+            &platform,
+            &filename,
+            length,
+            client,
+        );
+        // the guards live as long as the body stream
+        let stream = tokio_util::io::ReaderStream::new(file).map(move |chunk| {
+            let _ = &in_flight;
+            if let Ok(bytes) = &chunk {
+                progress.add(bytes.len());
+            }
+            chunk
+        });
+        state.record_download(&version);
+        Body::from_stream(stream)
+    };
+
+    let mut response = Response::new(body);
+    *response.headers_mut() = headers;
+
+    Ok(response)
+}
+
+// complete, not yanked and past PUBLISH_DELAY_SECS, i.e. a version
+// /latest-version could serve
+async fn is_published(config: &Config, version: &str) -> Result<bool, APIError> {
+    if scanner::parse_version(config, version).is_none() {
+        return Ok(false);
+    }
+    let version_directory = std::path::Path::new(&config.kiosk_directory).join(version);
+    if scanner::is_yanked(&version_directory).await {
+        return Ok(false);
+    }
+
+    Ok(match version_manifest(config, version).await? {
+        Some((_, modified_date)) => {
+            is_publish_delay_elapsed(config, version, modified_date, SystemTime::now())
+        }
+        None => false,
+    })
+}
+
+// a missing folder or a file here would otherwise only show up as confusing
+// errors on the first request
+fn check_kiosk_directory(kiosk_directory: &str) -> Result<(), String> {
+    match std::fs::metadata(kiosk_directory) {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => Err(format!(
+            "KIOSK_DIRECTORY {} is not a directory",
+            kiosk_directory
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(format!("KIOSK_DIRECTORY {} doesn't exist", kiosk_directory))
+        }
+        Err(e) => Err(format!(
+            "KIOSK_DIRECTORY {} is unavailable: {}",
+            kiosk_directory, e
+        )),
+    }
+}
+
+// the cdn mirrors KIOSK_DIRECTORY, so the artifact lives at the same relative
+// path and the file never has to be opened here
+fn redirect_to_cdn(
+    state: &AppState,
+    version: &str,
+    platform: &str,
+    filename: &str,
+    method: &Method,
+) -> Result<Response<Body>, APIError> {
+    let cdn_base_url = state.config.cdn_base_url.as_deref().unwrap_or_default();
+    // a fresh signature so the cdn can check the url the same way
+    let query = signing::signed_query(&state.config, version, platform, filename);
+    let location = format!(
+        "{}/{}/{}/{}{}",
+        cdn_base_url.trim_end_matches('/'),
+        version,
+        platform,
+        filename,
+        query
+    );
+    // like proxied downloads a HEAD isn't counted
+    if method != Method::HEAD {
+        state.record_download(version);
+    }
+
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header(header::LOCATION, location)
+        .body(Body::empty())
+        .map_err(|e| {
+            tracing::error!("failed to build redirect {}", e);
+            APIError::Internal
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use md5::Digest;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    #[tokio::test]
+    async fn serves_a_mis_cased_platform_under_its_canonical_name() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_platform("1.0.0", "Windows_x86_64")
+            .add_platform("1.0.0", "linux_x86_64");
+        let config = kiosk.config(&[("PLATFORMS", "windows_x86_64,linux_x86_64")]);
+
+        let response = resolve_latest_version(&config).await.unwrap();
+
+        assert_eq!(response.version, "1.0.0");
+        assert!(response.platforms.get("windows-x86_64").is_some());
+    }
+
+    #[tokio::test]
+    async fn stops_scanning_at_max_scan_versions() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        // newer versions still waiting for their signatures
+        for version in ["1.1.0", "1.2.0", "1.3.0"] {
+            kiosk.write(&format!("{}/linux_x86_64/app.tar.gz", version), "bin");
+        }
+
+        let capped = kiosk.config(&[("MAX_SCAN_VERSIONS", "3")]);
+        assert!(matches!(
+            resolve_latest_version(&capped).await,
+            Err(APIError::FileOrPathNotExist)
+        ));
+
+        let uncapped = kiosk.config(&[("MAX_SCAN_VERSIONS", "4")]);
+        assert_eq!(
+            resolve_latest_version(&uncapped).await.unwrap().version,
+            "1.0.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn withholds_a_version_until_the_publish_delay_elapsed() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let config = kiosk.config(&[("PUBLISH_DELAY_SECS", "3600")]);
+
+        assert!(resolve_latest_version(&config).await.is_err());
+
+        let later = SystemTime::now() + Duration::from_secs(3601);
+        let response = resolve_latest_version_at(&config, later).await.unwrap();
+        assert_eq!(response.version, "1.0.0");
+    }
+
+    #[tokio::test]
+    async fn redirects_only_valid_downloads() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[
+            ("DOWNLOAD_MODE", "redirect"),
+            ("CDN_BASE_URL", "https://cdn.example.com"),
+        ]))
+        .await
+        .unwrap();
+
+        for (version, filename) in [("1.0.0", ".."), ("1.0.0", "a\\b"), ("x\"y", "app.tar.gz")] {
+            let result =
+                test_support::download(&state, Method::GET, "linux_x86_64", version, filename)
+                    .await;
+            assert!(
+                matches!(result, Err(APIError::NotFound)),
+                "{} {}",
+                version,
+                filename
+            );
+        }
+        assert!(state.download_counts.lock().unwrap().is_empty());
+
+        let response = test_support::download(
+            &state,
+            Method::GET,
+            "linux_x86_64",
+            "1.0.0",
+            "app-linux_x86_64.tar.gz",
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers()[header::LOCATION],
+            "https://cdn.example.com/1.0.0/linux_x86_64/app-linux_x86_64.tar.gz"
+        );
+        assert_eq!(state.download_counts.lock().unwrap()["1.0.0"], 1);
+    }
+
+    #[test]
+    fn kiosk_directory_must_be_an_existing_directory() {
+        let kiosk = Kiosk::new();
+        kiosk.write("file", "");
+        let path = |relative: &str| kiosk.path().join(relative).to_str().unwrap().to_string();
+
+        assert!(check_kiosk_directory(&path("")).is_ok());
+        assert_eq!(
+            check_kiosk_directory(&path("file")),
+            Err(format!(
+                "KIOSK_DIRECTORY {} is not a directory",
+                path("file")
+            ))
+        );
+        assert_eq!(
+            check_kiosk_directory(&path("missing")),
+            Err(format!("KIOSK_DIRECTORY {} doesn't exist", path("missing")))
+        );
+    }
+
+    #[tokio::test]
+    async fn a_kiosk_directory_replaced_by_a_file_is_unavailable() {
+        let kiosk = Kiosk::new();
+        kiosk.write("file", "");
+        let mut config = kiosk.config(&[]);
+        config.kiosk_directory = kiosk.path().join("file").to_str().unwrap().to_string();
+
+        assert!(matches!(
+            resolve_latest_version(&config).await,
+            Err(APIError::StorageUnavailable)
+        ));
+    }
+
+    #[tokio::test]
+    async fn a_required_version_is_flagged_in_every_manifest() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.write("1.0.0/.required", "");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        assert!(
+            resolve_latest_version(&state.config)
+                .await
+                .unwrap()
+                .required
+        );
+
+        let Json(platform) = get_latest_version_by_platform(
+            State(state.clone()),
+            Path("linux_x86_64".into()),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(platform.version, "1.0.0");
+        assert!(platform.required);
+    }
+
+    #[tokio::test]
+    async fn the_per_platform_routes_follow_the_rollback_pin() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        kiosk.write(rollback::PIN_FILE, "1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let Json(platform) = get_latest_version_by_platform(
+            State(state.clone()),
+            Path("linux_x86_64".into()),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(platform.version, "1.0.0");
+
+        let Json(target) = get_latest_version_by_target(
+            State(state.clone()),
+            Path(("linux".into(), "x86_64".into())),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(target.version, "1.0.0");
+    }
+
+    #[test]
+    fn shortens_long_disposition_filenames_keeping_the_extension() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("MAX_DISPOSITION_FILENAME_LENGTH", "20")]);
+
+        let long = format!("{}.tar.gz", "a".repeat(50));
+        assert_eq!(
+            disposition_filename(&config, &long),
+            format!("{}.tar.gz", "a".repeat(13))
+        );
+        assert_eq!(disposition_filename(&config, "app.tar.gz"), "app.tar.gz");
+    }
+
+    #[test]
+    fn strips_quotes_and_line_breaks_from_disposition_filenames() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[]);
+
+        assert_eq!(
+            disposition_filename(&config, "app\"\r\nSet-Cookie: a\\.exe"),
+            "appSet-Cookie: a.exe"
+        );
+    }
+
+    #[tokio::test]
+    async fn downloads_a_long_name_under_a_short_disposition() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let long = format!("{}.tar.gz", "a".repeat(50));
+        kiosk.write(&format!("1.0.0/linux_x86_64/{}", long), "bin");
+        let state = AppState::build(kiosk.config(&[("MAX_DISPOSITION_FILENAME_LENGTH", "20")]))
+            .await
+            .unwrap();
+
+        let response = test_support::download(&state, Method::GET, "linux_x86_64", "1.0.0", &long)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            format!("attachment; filename=\"{}.tar.gz\"", "a".repeat(13))
+        );
+    }
+
+    // `HH:MM-HH:MM` in utc from `start` to `end` hours away from now, a day
+    // is added so only the time of day matters
+    fn window_around_now(start: u64, end: u64) -> String {
+        let at = |hours: u64| {
+            chrono::DateTime::<Utc>::from(SystemTime::now() + Duration::from_secs(hours * 3600))
+                .format("%H:%M")
+        };
+        format!("{}-{}", at(start), at(end))
+    }
+
+    #[tokio::test]
+    async fn the_rollout_window_applies_to_every_latest_route() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let inside =
+            AppState::build(kiosk.config(&[("ROLLOUT_WINDOW", &window_around_now(23, 25))]))
+                .await
+                .unwrap();
+        let outside =
+            AppState::build(kiosk.config(&[("ROLLOUT_WINDOW", &window_around_now(1, 2))]))
+                .await
+                .unwrap();
+
+        let query = LatestVersionQuery::default();
+        assert_eq!(
+            latest_manifest(&inside, &query, &HeaderMap::new())
+                .await
+                .unwrap()
+                .version,
+            "1.0.0"
+        );
+        assert!(matches!(
+            latest_manifest(&outside, &query, &HeaderMap::new()).await,
+            Err(APIError::NoUpdate)
+        ));
+
+        let by_platform = |state: &Arc<AppState>| {
+            get_latest_version_by_platform(
+                State(state.clone()),
+                Path("linux_x86_64".into()),
+                HeaderMap::new(),
+            )
+        };
+        assert!(by_platform(&inside).await.is_ok());
+        assert!(matches!(
+            by_platform(&outside).await,
+            Err(APIError::NoUpdate)
+        ));
+
+        let by_target = |state: &Arc<AppState>| {
+            get_latest_version_by_target(
+                State(state.clone()),
+                Path(("linux".into(), "x86_64".into())),
+                HeaderMap::new(),
+            )
+        };
+        assert!(by_target(&inside).await.is_ok());
+        assert!(matches!(by_target(&outside).await, Err(APIError::NoUpdate)));
+    }
+
+    #[tokio::test]
+    async fn query_profiles_cache_separately_and_a_publish_clears_them() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.write("1.0.0/notes.de.txt", "notizen 1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let german = LatestVersionQuery {
+            lang: Some("de".into()),
+            ..Default::default()
+        };
+        let german_key = ManifestKey {
+            languages: vec!["de".into()],
+            ..Default::default()
+        };
+
+        let plain = latest_manifest(&state, &LatestVersionQuery::default(), &HeaderMap::new())
+            .await
+            .unwrap();
+        let localized = latest_manifest(&state, &german, &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(plain.notes, "notes 1.0.0");
+        assert_eq!(localized.notes, "notizen 1.0.0");
+        let cache = &state.latest_version_cache;
+        assert_eq!(
+            cache.get(&ManifestKey::default()).await.unwrap().notes,
+            "notes 1.0.0"
+        );
+        assert_eq!(cache.get(&german_key).await.unwrap().notes, "notizen 1.0.0");
+
+        create_kiosk_version(
+            State(state.clone()),
+            Json(CreateKioskVersionRequest {
+                version: "1.1.0".into(),
+                notes: "notes 1.1.0".into(),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(!cache.is_populated(&ManifestKey::default()).await);
+        assert!(!cache.is_populated(&german_key).await);
+    }
+
+    #[tokio::test]
+    async fn head_only_sends_an_md5_that_is_already_known() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[("EMIT_CONTENT_MD5", "true")]))
+            .await
+            .unwrap();
+        let artifact = "app-linux_x86_64.tar.gz";
+
+        let head = test_support::download(&state, Method::HEAD, "linux_x86_64", "1.0.0", artifact)
+            .await
+            .unwrap();
+        assert_eq!(head.status(), StatusCode::OK);
+        assert!(head.headers().get("content-md5").is_none());
+        assert!(test_support::body(head).await.is_empty());
+        assert!(state.download_counts.lock().unwrap().is_empty());
+
+        let get = test_support::download(&state, Method::GET, "linux_x86_64", "1.0.0", artifact)
+            .await
+            .unwrap();
+        let md5 = get.headers()["content-md5"].clone();
+
+        let head = test_support::download(&state, Method::HEAD, "linux_x86_64", "1.0.0", artifact)
+            .await
+            .unwrap();
+        assert_eq!(head.headers()["content-md5"], md5);
+        assert_eq!(state.download_counts.lock().unwrap()["1.0.0"], 1);
+    }
+
+    #[tokio::test]
+    async fn a_redirected_head_is_not_counted() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[
+            ("DOWNLOAD_MODE", "redirect"),
+            ("CDN_BASE_URL", "https://cdn.example.com"),
+        ]))
+        .await
+        .unwrap();
+
+        let head = test_support::download(
+            &state,
+            Method::HEAD,
+            "linux_x86_64",
+            "1.0.0",
+            "app-linux_x86_64.tar.gz",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(head.status(), StatusCode::FOUND);
+        assert!(state.download_counts.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn head_is_answered_without_a_body() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for uri in ["/health", "/latest-version", "/versions"] {
+            let get = app(&state)
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let head = app(&state)
+                .oneshot(Request::head(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+
+            assert_eq!(head.status(), StatusCode::OK, "{}", uri);
+            assert_eq!(
+                head.headers().get(header::CONTENT_TYPE),
+                get.headers().get(header::CONTENT_TYPE),
+                "{}",
+                uri
+            );
+            assert!(test_support::body(head).await.is_empty(), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_wrong_method_gets_the_error_envelope() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let response = app(&state)
+            .oneshot(
+                Request::post("/latest-version")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[header::ALLOW], "GET,HEAD");
+        let body = test_support::json(response).await;
+        assert_eq!(body["kiosk_version_error"]["code"], "MethodNotAllowed");
+    }
+
+    #[tokio::test]
+    async fn latest_version_without_target_and_arch_lists_every_platform() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let response = test_support::get(&state, "/latest-version").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let manifest = test_support::json(response).await;
+        assert_eq!(manifest["version"], "1.0.0");
+        let platforms = manifest["platforms"].as_object().unwrap();
+        assert_eq!(platforms.len(), test_support::PLATFORMS.len());
+        assert!(platforms.contains_key("darwin-aarch64"));
+        assert!(manifest.get("url").is_none());
+    }
+
+    #[tokio::test]
+    async fn latest_version_with_target_and_arch_answers_one_platform() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for platform in test_support::PLATFORMS {
+            let (target, arch) = platform.split_once('_').unwrap();
+            let query = test_support::get(
+                &state,
+                &format!("/latest-version?target={}&arch={}", target, arch),
+            )
+            .await;
+            let path =
+                test_support::get(&state, &format!("/latest-version/{}/{}", target, arch)).await;
+
+            assert_eq!(query.status(), StatusCode::OK, "{}", platform);
+            let block = test_support::json(query).await;
+            assert_eq!(block["version"], "1.0.0");
+            assert!(block.get("platforms").is_none());
+            assert!(block["url"]
+                .as_str()
+                .unwrap()
+                .ends_with(&format!("/app-{}.tar.gz", platform)));
+            assert_eq!(block, test_support::json(path).await, "{}", platform);
+        }
+    }
+
+    #[tokio::test]
+    async fn latest_version_refuses_an_unknown_target_and_arch() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for uri in [
+            "/latest-version?target=linux&arch=riscv64",
+            "/latest-version?target=linux",
+            "/latest-version?arch=x86_64",
+            "/latest-version/solaris/sparc",
+        ] {
+            let response = test_support::get(&state, uri).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn an_unknown_target_and_arch_lists_the_valid_platforms() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[("PLATFORMS", "linux_x86_64,windows_x86_64")]))
+            .await
+            .unwrap();
+
+        let response = test_support::get(&state, "/latest-version/solaris/sparc").await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            test_support::json(response).await,
+            serde_json::json!({
+                "kiosk_version_error": {
+                    "code": "PlatformNotFound",
+                    "data": {
+                        "platform": "solaris/sparc",
+                        "valid_platforms": ["linux_x86_64", "windows_x86_64"],
+                    },
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn latest_version_for_a_platform_without_a_build_has_no_update() {
+        let kiosk = Kiosk::new();
+        kiosk.add_platform("1.0.0", "linux_x86_64");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let response =
+            test_support::get(&state, "/latest-version?target=windows&arch=x86_64").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = test_support::get(&state, "/latest-version?target=linux&arch=x86_64").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    fn built(artifact: &str, size: u64, signature: &str) -> PlatformInspection {
+        PlatformInspection {
+            files: Some(scanner::PlatformFiles {
+                folder: std::path::PathBuf::from("unused"),
+                signature: Some((std::path::PathBuf::from("unused.sig"), signature.into())),
+                pgp_signature: None,
+                artifact: Some(artifact.into()),
+                artifact_size: Some(size),
+                modified: SystemTime::UNIX_EPOCH,
+            }),
+            signature: Some(signature.into()),
+            missing: Vec::new(),
+            absent: false,
+        }
+    }
+
+    fn absent() -> PlatformInspection {
+        PlatformInspection {
+            files: None,
+            signature: None,
+            missing: vec![
+                scanner::MissingPart::Binary,
+                scanner::MissingPart::Signature,
+            ],
+            absent: true,
+        }
+    }
+
+    fn synthetic_inspection() -> VersionInspection {
+        VersionInspection {
+            platforms: vec![
+                ("linux_x86_64".into(), built("app.tar.gz", 42, "sig linux")),
+                ("windows_x86_64".into(), absent()),
+                (
+                    "darwin_aarch64".into(),
+                    built("app.app.tar.gz", 7, "sig darwin"),
+                ),
+            ],
+            modified: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn builds_the_manifest_of_an_inspection() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("KIOSK_DOWNLOADABLE_URL", "https://updates.example.com")]);
+
+        let manifest = to_manifest(
+            &config,
+            "1.2.0",
+            &synthetic_inspection(),
+            "notes".into(),
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.version, "1.2.0");
+        assert_eq!(manifest.notes, "notes");
+        assert_eq!(manifest.pub_date, "2023-11-14T22:13:20+00:00");
+        assert!(manifest.required);
+        assert_eq!(
+            manifest
+                .platforms
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect::<Vec<_>>(),
+            ["linux-x86_64", "darwin-aarch64"]
+        );
+        let linux = manifest.platforms.get("linux-x86_64").unwrap();
+        assert_eq!(
+            linux.url,
+            "https://updates.example.com/download/1.2.0/linux_x86_64/app.tar.gz"
+        );
+        assert_eq!(linux.signature, "sig linux");
+        assert_eq!(linux.size, Some(42));
+        assert_eq!(linux.name.as_deref(), Some("linux_x86_64"));
+    }
+
+    #[test]
+    fn links_the_signature_in_url_mode() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[
+            ("KIOSK_DOWNLOADABLE_URL", "https://updates.example.com"),
+            ("SIGNATURE_MODE", "url"),
+        ]);
+
+        let manifest = to_manifest(
+            &config,
+            "1.2.0",
+            &synthetic_inspection(),
+            String::new(),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest.platforms.get("darwin-aarch64").unwrap().signature,
+            "https://updates.example.com/versions/1.2.0/darwin_aarch64/signature"
+        );
+    }
+
+    #[test]
+    fn an_inspection_without_builds_has_no_manifest() {
+        let kiosk = Kiosk::new();
+        let inspection = VersionInspection {
+            platforms: vec![("linux_x86_64".into(), absent())],
+            modified: SystemTime::UNIX_EPOCH,
+        };
+
+        assert!(to_manifest(
+            &kiosk.config(&[]),
+            "1.2.0",
+            &inspection,
+            String::new(),
+            false
+        )
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn echoes_or_generates_the_request_id() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[("REQUEST_ID_HEADER", "x-trace-id")]))
+            .await
+            .unwrap();
+        let with_id = |value: &str| {
+            Request::get("/health")
+                .header("x-trace-id", value)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app(&state).oneshot(with_id("gateway-123")).await.unwrap();
+        assert_eq!(response.headers()["x-trace-id"], "gateway-123");
+
+        for response in [
+            test_support::get(&state, "/health").await,
+            app(&state)
+                .oneshot(with_id("not a valid id"))
+                .await
+                .unwrap(),
+        ] {
+            let generated = response.headers()["x-trace-id"].to_str().unwrap();
+            assert!(uuid::Uuid::parse_str(generated).is_ok(), "{}", generated);
+        }
+        assert_ne!(
+            test_support::get(&state, "/health").await.headers()["x-trace-id"],
+            test_support::get(&state, "/health").await.headers()["x-trace-id"]
+        );
+    }
+
+    async fn create_if_changed(
+        state: &Arc<AppState>,
+        version: &str,
+        checksums: &[(&str, &str)],
+    ) -> Result<StatusCode, APIError> {
+        create_kiosk_version_if_changed(
+            State(state.clone()),
+            Path(version.to_string()),
+            Json(CreateKioskVersionIfChangedRequest {
+                notes: format!("notes {}", version),
+                checksums: checksums
+                    .iter()
+                    .map(|(platform, checksum)| (platform.to_string(), checksum.to_string()))
+                    .collect(),
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn skips_a_publish_with_identical_checksums() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let current = checksum::sha256_file(
+            &kiosk
+                .path()
+                .join("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz"),
+        )
+        .await
+        .unwrap();
+
+        let status = create_if_changed(&state, "1.1.0", &[("linux_x86_64", &current)])
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(!kiosk.path().join("1.1.0").exists());
+
+        // checksums are compared case-insensitively
+        let status = create_if_changed(
+            &state,
+            "1.1.0",
+            &[("linux_x86_64", &current.to_ascii_uppercase())],
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn publishes_when_a_checksum_differs() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let current = checksum::sha256_file(
+            &kiosk
+                .path()
+                .join("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz"),
+        )
+        .await
+        .unwrap();
+
+        let status = create_if_changed(
+            &state,
+            "1.1.0",
+            &[
+                ("linux_x86_64", &current),
+                ("windows_x86_64", &"0".repeat(64)),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(kiosk.path().join("1.1.0/linux_x86_64").is_dir());
+        assert_eq!(
+            std::fs::read_to_string(kiosk.path().join("1.1.0/notes.txt")).unwrap(),
+            "notes 1.1.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_an_if_changed_publish_without_checksums() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let result = create_if_changed(&state, "1.1.0", &[]).await;
+
+        assert!(matches!(result, Err(APIError::MissingChecksums)));
+        assert_eq!(
+            APIError::MissingChecksums.into_response().status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(!kiosk.path().join("1.1.0").exists());
+    }
+
+    #[tokio::test]
+    async fn yaml_bodies_match_the_json_ones() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for uri in ["/latest-version", "/versions"] {
+            let yaml = app(&state)
+                .oneshot(
+                    Request::get(uri)
+                        .header(header::ACCEPT, "application/yaml")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(yaml.headers()[header::CONTENT_TYPE], "application/yaml");
+            assert_eq!(yaml.headers()[header::VARY], "accept");
+            let yaml: serde_json::Value =
+                serde_yaml::from_str(&test_support::body(yaml).await).unwrap();
+
+            let json = test_support::json(test_support::get(&state, uri).await).await;
+            assert_eq!(yaml, json, "{}", uri);
+        }
+    }
+
+    fn ci_manifest(artifact: &str) -> KioskVersionResponse {
+        serde_json::from_value(serde_json::json!({
+            "version": "1.0.0",
+            "notes": "built by ci",
+            "pub_date": "2026-01-02T03:04:05Z",
+            "platforms": {
+                "linux-x86_64": {
+                    "signature": "ci signature",
+                    "url": format!("https://ci.example.com/1.0.0/{}?token=abc", artifact),
+                    "name": "linux_x86_64",
+                }
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn serves_a_registered_manifest() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let status = register_manifest(
+            State(state.clone()),
+            Path("1.0.0".into()),
+            Json(ci_manifest("app-linux_x86_64.tar.gz")),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+
+        let served = test_support::json(test_support::get(&state, "/latest-version").await).await;
+        let registered = serde_json::to_value(ci_manifest("app-linux_x86_64.tar.gz")).unwrap();
+        assert_eq!(served, registered);
+    }
+
+    #[tokio::test]
+    async fn refuses_a_manifest_pointing_at_missing_files() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.write("1.0.0/linux_x86_64/unsigned.tar.gz", "bin");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for artifact in ["missing.tar.gz", "unsigned.tar.gz", ""] {
+            let result = register_manifest(
+                State(state.clone()),
+                Path("1.0.0".into()),
+                Json(ci_manifest(artifact)),
+            )
+            .await;
+            assert!(
+                matches!(result, Err(APIError::FileOrPathNotExist)),
+                "{}: {:?}",
+                artifact,
+                result
+            );
+        }
+        assert!(!kiosk
+            .path()
+            .join("1.0.0")
+            .join(scanner::MANIFEST_FILE)
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn relative_urls_leave_out_the_host() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let vars = [("KIOSK_DOWNLOADABLE_URL", "https://updates.example.com")];
+        let absolute = AppState::build(kiosk.config(&vars)).await.unwrap();
+        let relative = AppState::build(kiosk.config(&[vars[0], ("RELATIVE_URLS", "true")]))
+            .await
+            .unwrap();
+        let url = |manifest: serde_json::Value| {
+            manifest["platforms"]["linux-x86_64"]["url"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let manifest =
+            test_support::json(test_support::get(&relative, "/latest-version").await).await;
+        assert_eq!(
+            url(manifest),
+            "/download/1.0.0/linux_x86_64/app-linux_x86_64.tar.gz"
+        );
+        let manifest =
+            test_support::json(test_support::get(&absolute, "/latest-version").await).await;
+        assert_eq!(
+            url(manifest),
+            "https://updates.example.com/download/1.0.0/linux_x86_64/app-linux_x86_64.tar.gz"
+        );
+    }
+
+    #[tokio::test]
+    async fn tells_systemd_it_is_ready_once_bound() {
+        let kiosk = Kiosk::new();
+        let socket_path = kiosk.path().join("notify.sock");
+        let socket = std::os::unix::net::UnixDatagram::bind(&socket_path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        // nothing else in the tests reads NOTIFY_SOCKET
+        std::env::set_var("NOTIFY_SOCKET", &socket_path);
+        let mut message = [0; 64];
+
+        let off = kiosk.config(&[("APP_URL", "127.0.0.1:0")]);
+        let listener = listen(&off).await.unwrap();
+        assert!(listener.local_addr().is_ok());
+        assert_eq!(
+            socket.recv(&mut message).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+
+        let on = kiosk.config(&[("APP_URL", "127.0.0.1:0"), ("SYSTEMD_NOTIFY", "true")]);
+        let listener = listen(&on).await.unwrap();
+        let received = socket.recv(&mut message).unwrap();
+        std::env::remove_var("NOTIFY_SOCKET");
+
+        assert_eq!(&message[..received], b"READY=1\n");
+        tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn force_octet_stream_overrides_the_guessed_type() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .write("1.0.0/linux_x86_64/app.zip", "zip");
+        let content_type = |vars: &'static [(&'static str, &'static str)], filename| {
+            let config = kiosk.config(vars);
+            async move {
+                let state = AppState::build(config).await.unwrap();
+                let response =
+                    test_support::download(&state, Method::GET, "linux_x86_64", "1.0.0", filename)
+                        .await
+                        .unwrap();
+                response.headers()[header::CONTENT_TYPE].clone()
+            }
+        };
+
+        assert_eq!(content_type(&[], "app.zip").await, "application/zip");
+        assert_eq!(
+            content_type(&[("FORCE_OCTET_STREAM", "true")], "app.zip").await,
+            "application/octet-stream"
+        );
+        // signatures are read as text either way
+        assert_eq!(
+            content_type(
+                &[("FORCE_OCTET_STREAM", "true")],
+                "app-linux_x86_64.tar.gz.sig"
+            )
+            .await,
+            "text/plain; charset=utf-8"
+        );
+    }
+
+    #[tokio::test]
+    async fn health_reports_uptime_unless_turned_off() {
+        let kiosk = Kiosk::new();
+        let mut state = AppState::build(kiosk.config(&[])).await.unwrap();
+        Arc::get_mut(&mut state).unwrap().started_at -= Duration::from_secs(5);
+
+        let first = test_support::json(test_support::get(&state, "/health").await).await;
+        assert_eq!(first["status"], "ok");
+        assert_eq!(first["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(first.as_object().unwrap().len(), 3);
+        let uptime = first["uptime_secs"].as_u64().unwrap();
+        assert!(uptime >= 5, "{}", uptime);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let later = test_support::json(test_support::get(&state, "/health").await).await;
+        assert!(later["uptime_secs"].as_u64().unwrap() > uptime);
+
+        let plain = AppState::build(kiosk.config(&[("HEALTH_JSON", "false")]))
+            .await
+            .unwrap();
+        let response = test_support::get(&plain, "/health").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test_support::body(response).await, "OK");
+    }
+
+    #[tokio::test]
+    async fn streams_a_large_attachment_back_unchanged() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        // a few MiB without a repeating pattern
+        let content: Vec<u8> = (0..3 * 1024 * 1024 + 17u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        std::fs::write(kiosk.path().join("1.0.0/manual.pdf"), &content).unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let details = test_support::json(test_support::get(&state, "/versions/1.0.0").await).await;
+        assert_eq!(details["attachments"][0]["filename"], "manual.pdf");
+        assert_eq!(details["attachments"][0]["size"], content.len());
+
+        let response = test_support::download(
+            &state,
+            Method::GET,
+            scanner::ATTACHMENTS,
+            "1.0.0",
+            "manual.pdf",
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_LENGTH],
+            content.len().to_string()
+        );
+        let downloaded = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(downloaded == content, "attachment came back altered");
+    }
+
+    #[tokio::test]
+    async fn serves_the_fallback_when_nothing_newer_is_complete() {
+        let kiosk = Kiosk::new();
+        for version in ["1.0.0", "1.1.0", "1.2.0"] {
+            kiosk.add_version(version);
+        }
+        for version in ["1.1.0", "1.2.0"] {
+            std::fs::remove_file(
+                kiosk
+                    .path()
+                    .join(version)
+                    .join("linux_x86_64/app-linux_x86_64.tar.gz.sig"),
+            )
+            .unwrap();
+        }
+        let latest = |vars: &[(&str, &str)]| {
+            let config = kiosk.config(vars);
+            async move { resolve_latest_version(&config).await }
+        };
+
+        // the scan stops before reaching 1.0.0
+        assert!(matches!(
+            latest(&[("MAX_SCAN_VERSIONS", "2")]).await,
+            Err(APIError::FileOrPathNotExist)
+        ));
+        let fallback = latest(&[("MAX_SCAN_VERSIONS", "2"), ("FALLBACK_VERSION", "1.0.0")])
+            .await
+            .unwrap();
+        assert_eq!(fallback.version, "1.0.0");
+
+        // an incomplete or missing fallback isn't served either
+        for missing in ["1.1.0", "0.9.0"] {
+            assert!(matches!(
+                latest(&[("MAX_SCAN_VERSIONS", "2"), ("FALLBACK_VERSION", missing)]).await,
+                Err(APIError::FileOrPathNotExist)
+            ));
+        }
+
+        kiosk.add_version("1.2.0");
+        let complete = latest(&[("MAX_SCAN_VERSIONS", "2"), ("FALLBACK_VERSION", "1.0.0")])
+            .await
+            .unwrap();
+        assert_eq!(complete.version, "1.2.0");
+    }
+
+    #[tokio::test]
+    async fn adds_the_security_headers_to_every_response() {
+        let kiosk = Kiosk::new();
+        let defaults = AppState::build(kiosk.config(&[])).await.unwrap();
+        for uri in ["/health", "/versions/9.9.9"] {
+            let response = test_support::get(&defaults, uri).await;
+            let headers = response.headers();
+            assert_eq!(headers["x-content-type-options"], "nosniff", "{}", uri);
+            assert_eq!(headers["x-frame-options"], "DENY", "{}", uri);
+            assert_eq!(headers["referrer-policy"], "no-referrer", "{}", uri);
+            assert_eq!(
+                headers["strict-transport-security"], "max-age=31536000",
+                "{}",
+                uri
+            );
+        }
+
+        let overridden = AppState::build(kiosk.config(&[(
+            "SECURITY_HEADERS",
+            "X-Frame-Options=SAMEORIGIN, Strict-Transport-Security=, Permissions-Policy=camera=()",
+        )]))
+        .await
+        .unwrap();
+        let response = test_support::get(&overridden, "/health").await;
+        let headers = response.headers();
+        assert_eq!(headers["x-frame-options"], "SAMEORIGIN");
+        assert_eq!(headers["permissions-policy"], "camera=()");
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert!(headers.get("strict-transport-security").is_none());
+    }
+
+    #[tokio::test]
+    async fn as_of_serves_what_was_latest_then() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let first = test_support::json(test_support::get(&state, "/versions/1.0.0").await).await;
+        let first_published = SystemTime::from(
+            chrono::DateTime::parse_from_rfc3339(first["pub_date"].as_str().unwrap()).unwrap(),
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        kiosk.add_version("1.1.0");
+        let as_of = |at: SystemTime| {
+            format!(
+                "/latest-version?as_of={}",
+                chrono::DateTime::<Utc>::from(at).format("%Y-%m-%dT%H:%M:%S%.6fZ")
+            )
+        };
+
+        let now = test_support::json(test_support::get(&state, "/latest-version").await).await;
+        assert_eq!(now["version"], "1.1.0");
+        let then = test_support::json(
+            test_support::get(&state, &as_of(first_published + Duration::from_millis(10))).await,
+        )
+        .await;
+        assert_eq!(then["version"], "1.0.0");
+        assert_eq!(then["pub_date"], first["pub_date"]);
+
+        let before =
+            test_support::get(&state, &as_of(first_published - Duration::from_secs(1))).await;
+        assert_eq!(before.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let invalid = test_support::get(&state, "/latest-version?as_of=yesterday").await;
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn only_requests_over_the_threshold_are_slow() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("SLOW_REQUEST_MS", "250")]);
+
+        assert!(!is_slow_request(&config, Duration::from_millis(100)));
+        assert!(!is_slow_request(&config, Duration::from_millis(250)));
+        assert!(is_slow_request(&config, Duration::from_millis(251)));
+
+        let unset = kiosk.config(&[]);
+        assert!(!is_slow_request(&unset, Duration::from_secs(3600)));
+    }
+
+    #[tokio::test]
+    async fn lists_manifest_platforms_once_in_configured_order() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let config = kiosk.config(&[(
+            "PLATFORMS",
+            "darwin_aarch64,linux_x86_64,darwin_aarch64,windows_x86_64",
+        )]);
+
+        let manifest = resolve_latest_version(&config).await.unwrap();
+
+        let keys: Vec<&String> = manifest.platforms.iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["darwin-aarch64", "linux-x86_64", "windows-x86_64"]);
+        let json = serde_json::to_string(&manifest.platforms).unwrap();
+        assert!(json.starts_with("{\"darwin-aarch64\":"), "{}", json);
+    }
+
+    #[tokio::test]
+    async fn scans_and_downloads_the_flat_layout() {
+        let kiosk = Kiosk::new();
+        for version in ["1.0.0", "1.1.0"] {
+            kiosk.write(
+                &format!("{}/notes.txt", version),
+                &format!("notes {}", version),
+            );
+            for platform in test_support::PLATFORMS {
+                let artifact = format!("{}/{}/app-{}.tar.gz", platform, version, platform);
+                kiosk
+                    .write(&artifact, &format!("bin {} {}", version, platform))
+                    .write(&format!("{}.sig", artifact), test_support::SIGNATURE);
+            }
+        }
+        // 1.1.0 is missing a signature
+        std::fs::remove_file(
+            kiosk
+                .path()
+                .join("linux_x86_64/1.1.0/app-linux_x86_64.tar.gz.sig"),
+        )
+        .unwrap();
+        let state = AppState::build(kiosk.config(&[("LAYOUT", "flat")]))
+            .await
+            .unwrap();
+
+        let folders = scanner::list_versions(&state.config).await.unwrap();
+        let names: Vec<&str> = folders
+            .versions
+            .iter()
+            .map(|(_, name)| name.as_str())
+            .collect();
+        assert_eq!(names, ["1.1.0", "1.0.0"]);
+        assert!(folders.ignored.is_empty());
+
+        let latest = resolve_latest_version(&state.config).await.unwrap();
+        assert_eq!(latest.version, "1.0.0");
+        assert_eq!(latest.notes, "notes 1.0.0");
+        assert!(latest
+            .platforms
+            .get("darwin-aarch64")
+            .unwrap()
+            .url
+            .ends_with("/download/1.0.0/darwin_aarch64/app-darwin_aarch64.tar.gz"));
+
+        let response = test_support::download(
+            &state,
+            Method::GET,
+            "darwin_aarch64",
+            "1.0.0",
+            "app-darwin_aarch64.tar.gz",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            test_support::body(response).await,
+            "bin 1.0.0 darwin_aarch64"
+        );
+    }
+
+    #[tokio::test]
+    async fn recomputes_the_content_md5_after_an_overwrite() {
+        let expected = |content: &str| {
+            base64::engine::general_purpose::STANDARD.encode(md5::Md5::digest(content))
+        };
+        // with and without the in-memory cache in front of the sidecar
+        for capacity in ["64", "0"] {
+            let kiosk = Kiosk::new();
+            kiosk.add_version("1.0.0");
+            let state = AppState::build(kiosk.config(&[
+                ("EMIT_CONTENT_MD5", "true"),
+                ("HASH_CACHE_CAPACITY", capacity),
+            ]))
+            .await
+            .unwrap();
+            let content_md5 = || async {
+                test_support::download(
+                    &state,
+                    Method::GET,
+                    "linux_x86_64",
+                    "1.0.0",
+                    "app-linux_x86_64.tar.gz",
+                )
+                .await
+                .unwrap()
+                .headers()["content-md5"]
+                    .clone()
+            };
+
+            assert_eq!(content_md5().await, expected("bin 1.0.0 linux_x86_64"));
+            assert_eq!(content_md5().await, expected("bin 1.0.0 linux_x86_64"));
+
+            // past the filesystem's timestamp granularity so the overwrite
+            // is newer than the sidecar
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            kiosk.write("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz", "rebuilt");
+            assert_eq!(content_md5().await, expected("rebuilt"), "{}", capacity);
+        }
+
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let response = test_support::download(
+            &state,
+            Method::GET,
+            "linux_x86_64",
+            "1.0.0",
+            "app-linux_x86_64.tar.gz",
+        )
+        .await
+        .unwrap();
+        assert!(response.headers().get("content-md5").is_none());
+    }
+
+    #[tokio::test]
+    async fn an_unknown_platform_lists_the_valid_ones() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[("PLATFORMS", "linux_x86_64,windows_x86_64")]))
+            .await
+            .unwrap();
+
+        let unknown = test_support::download(
+            &state,
+            Method::GET,
+            "solaris_sparc",
+            "1.0.0",
+            "app-linux_x86_64.tar.gz",
+        )
+        .await
+        .unwrap_err()
+        .into_response();
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            test_support::json(unknown).await,
+            serde_json::json!({
+                "kiosk_version_error": {
+                    "code": "PlatformNotFound",
+                    "data": {
+                        "platform": "solaris_sparc",
+                        "valid_platforms": ["linux_x86_64", "windows_x86_64"],
+                    },
+                },
+            })
+        );
+
+        // a known platform without the file is a plain 404
+        let missing = test_support::download(
+            &state,
+            Method::GET,
+            "linux_x86_64",
+            "1.0.0",
+            "app-missing.tar.gz",
+        )
+        .await
+        .unwrap_err()
+        .into_response();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        assert!(test_support::body(missing).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn serves_the_signature_inline_or_as_a_link() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let latest_signature = |mode: &'static str| {
+            let config = kiosk.config(&[
+                ("KIOSK_DOWNLOADABLE_URL", "https://updates.example.com"),
+                ("SIGNATURE_MODE", mode),
+            ]);
+            async move {
+                let state = AppState::build(config).await.unwrap();
+                let latest =
+                    test_support::json(test_support::get(&state, "/latest-version").await).await;
+                (
+                    state,
+                    latest["platforms"]["windows-x86_64"]["signature"].clone(),
+                )
+            }
+        };
+
+        let (_, inline) = latest_signature("inline").await;
+        assert_eq!(inline, test_support::SIGNATURE);
+
+        let (state, linked) = latest_signature("url").await;
+        let linked = linked.as_str().unwrap();
+        let path = linked.strip_prefix("https://updates.example.com").unwrap();
+        assert_eq!(path, "/versions/1.0.0/windows_x86_64/signature");
+        let fetched = test_support::get(&state, path).await;
+        assert_eq!(test_support::body(fetched).await, test_support::SIGNATURE);
+    }
+
+    #[tokio::test]
+    async fn reports_the_artifact_size_of_the_clients_platform() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").write(
+            "1.0.0/windows_x86_64/app-windows_x86_64.tar.gz",
+            &"x".repeat(4096),
+        );
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let size = |uri: &'static str| async {
+            test_support::json(test_support::get(&state, uri).await).await["size"].clone()
+        };
+
+        assert_eq!(size("/latest-version/windows_x86_64").await, 4096);
+        assert_eq!(size("/latest-version/windows/x86_64").await, 4096);
+        assert_eq!(
+            size("/latest-version?target=linux&arch=x86_64").await,
+            "bin 1.0.0 linux_x86_64".len()
+        );
+
+        let manifest = test_support::json(test_support::get(&state, "/latest-version").await).await;
+        assert_eq!(manifest["platforms"]["windows-x86_64"]["size"], 4096);
+        assert_eq!(
+            manifest["platforms"]["darwin-aarch64"]["size"],
+            "bin 1.0.0 darwin_aarch64".len()
+        );
+    }
+
+    #[tokio::test]
+    async fn leaves_the_notes_out_of_the_manifest_when_disabled() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .write("1.0.0/notes.de.txt", "hinweise 1.0.0");
+        let notes = |vars: &'static [(&'static str, &'static str)]| {
+            let config = kiosk.config(vars);
+            async move {
+                let state = AppState::build(config).await.unwrap();
+                let mut notes = Vec::new();
+                for uri in [
+                    "/latest-version",
+                    "/latest-version?lang=de",
+                    "/latest-version/linux_x86_64",
+                ] {
+                    let manifest = test_support::json(test_support::get(&state, uri).await).await;
+                    notes.push(manifest["notes"].as_str().unwrap().to_string());
+                }
+                let separate = test_support::get(&state, "/versions/1.0.0/notes").await;
+                (notes, test_support::body(separate).await)
+            }
+        };
+
+        let (included, separate) = notes(&[]).await;
+        assert_eq!(included, ["notes 1.0.0", "hinweise 1.0.0", "notes 1.0.0"]);
+        assert_eq!(separate, "notes 1.0.0");
+
+        let (omitted, separate) = notes(&[("INCLUDE_NOTES_IN_MANIFEST", "false")]).await;
+        assert_eq!(omitted, ["", "", ""]);
+        assert_eq!(separate, "notes 1.0.0");
+    }
+
+    #[tokio::test]
+    async fn downloads_signatures_as_inline_text() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").write(
+            "1.0.0/linux_x86_64/app-linux_x86_64.tar.gz.minisig",
+            "minisign signature",
+        );
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for (filename, content) in [
+            ("app-linux_x86_64.tar.gz.sig", test_support::SIGNATURE),
+            ("app-linux_x86_64.tar.gz.minisig", "minisign signature"),
+        ] {
+            let response =
+                test_support::download(&state, Method::GET, "linux_x86_64", "1.0.0", filename)
+                    .await
+                    .unwrap();
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "text/plain; charset=utf-8",
+                "{}",
+                filename
+            );
+            assert!(response.headers()[header::CONTENT_DISPOSITION]
+                .to_str()
+                .unwrap()
+                .starts_with("inline"));
+            assert_eq!(test_support::body(response).await, content);
+        }
+    }
+
+    #[tokio::test]
+    async fn answers_only_under_the_route_prefix() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[
+            ("KIOSK_DOWNLOADABLE_URL", "https://updates.example.com"),
+            ("ROUTE_PREFIX", "/kiosk-updater/"),
+        ]))
+        .await
+        .unwrap();
+        assert_eq!(state.config.route_prefix, "/kiosk-updater");
+
+        let prefixed = test_support::get(&state, "/kiosk-updater/latest-version").await;
+        assert_eq!(prefixed.status(), StatusCode::OK);
+        let manifest = test_support::json(prefixed).await;
+        assert_eq!(
+            manifest["platforms"]["linux-x86_64"]["url"],
+            "https://updates.example.com/kiosk-updater/download/1.0.0/linux_x86_64/app-linux_x86_64.tar.gz"
+        );
+        let health = test_support::get(&state, "/kiosk-updater/health").await;
+        assert_eq!(health.status(), StatusCode::OK);
+
+        for uri in ["/latest-version", "/health", "/versions/1.0.0"] {
+            let unprefixed = test_support::get(&state, uri).await;
+            assert_eq!(unprefixed.status(), StatusCode::NOT_FOUND, "{}", uri);
+            // the middleware still runs outside the prefix
+            assert_eq!(unprefixed.headers()["x-content-type-options"], "nosniff");
+        }
+    }
+
+    #[tokio::test]
+    async fn blocks_downloads_from_an_incomplete_version() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        std::fs::remove_file(
+            kiosk
+                .path()
+                .join("1.0.0/windows_x86_64/app-windows_x86_64.tar.gz.sig"),
+        )
+        .unwrap();
+
+        for (block, expected) in [("true", None), ("false", Some("bin 1.0.0 linux_x86_64"))] {
+            let state = AppState::build(kiosk.config(&[("BLOCK_INCOMPLETE_DOWNLOADS", block)]))
+                .await
+                .unwrap();
+
+            let result = test_support::download(
+                &state,
+                Method::GET,
+                "linux_x86_64",
+                "1.0.0",
+                "app-linux_x86_64.tar.gz",
+            )
+            .await;
+
+            match expected {
+                None => assert!(matches!(result, Err(APIError::NotFound)), "{:?}", result),
+                Some(content) => {
+                    let response = result.unwrap();
+                    assert_eq!(response.status(), StatusCode::OK);
+                    assert_eq!(test_support::body(response).await, content);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn compresses_only_bodies_over_the_minimum() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.write("1.0.0/notes.txt", &"fixes ".repeat(500));
+
+        for (minimum, compressed) in [("65535", false), ("256", true)] {
+            let state = AppState::build(kiosk.config(&[("COMPRESS_MIN_BYTES", minimum)]))
+                .await
+                .unwrap();
+
+            let response = test_support::send(
+                &state,
+                Request::get("/latest-version")
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get(header::CONTENT_ENCODING).is_some(),
+                compressed,
+                "{}",
+                minimum
+            );
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json = match compressed {
+                true => {
+                    let mut decoded = String::new();
+                    std::io::Read::read_to_string(
+                        &mut flate2::read::GzDecoder::new(&body[..]),
+                        &mut decoded,
+                    )
+                    .unwrap();
+                    decoded
+                }
+                false => String::from_utf8(body.to_vec()).unwrap(),
+            };
+            let manifest: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(manifest["notes"], "fixes ".repeat(500));
+        }
+    }
 }
@@ -0,0 +1,76 @@
+use std::{
+    fmt::Write,
+    sync::{atomic::Ordering, Arc},
+};
+
+use axum::{extract::State, http::header, response::IntoResponse};
+
+use crate::state::AppState;
+
+// prometheus text exposition format
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut body = String::new();
+
+    writeln!(body, "# TYPE kiosk_downloads_in_flight gauge").unwrap();
+    writeln!(
+        body,
+        "kiosk_downloads_in_flight {}",
+        state.downloads_in_flight()
+    )
+    .unwrap();
+
+    writeln!(body, "# TYPE kiosk_downloads_total counter").unwrap();
+    for (version, count) in state.download_counts.lock().unwrap().iter() {
+        writeln!(
+            body,
+            "kiosk_downloads_total{{version=\"{}\"}} {}",
+            escape_label(version),
+            count
+        )
+        .unwrap();
+    }
+
+    writeln!(body, "# TYPE kiosk_corruption_detected_total counter").unwrap();
+    writeln!(
+        body,
+        "kiosk_corruption_detected_total {}",
+        state.corruption_detected.load(Ordering::Relaxed)
+    )
+    .unwrap();
+
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
+
+// label values may not contain a raw backslash, quote or newline
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label("1.0.0"), "1.0.0");
+        assert_eq!(escape_label("a\\b\"c\nd"), "a\\\\b\\\"c\\nd");
+    }
+
+    #[tokio::test]
+    async fn exposes_download_counts() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        state.record_download("1.0.0");
+        state.record_download("1.0.0");
+        state.record_download("1\"0");
+
+        let body = test_support::body(metrics_handler(State(state)).await.into_response()).await;
+
+        assert!(body.contains("kiosk_downloads_total{version=\"1.0.0\"} 2\n"));
+        assert!(body.contains("kiosk_downloads_total{version=\"1\\\"0\"} 1\n"));
+    }
+}
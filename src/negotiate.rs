@@ -0,0 +1,155 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::error::APIError;
+
+const JSON_TYPES: [&str; 3] = ["application/json", "application/*", "*/*"];
+const YAML_TYPES: [&str; 3] = ["application/yaml", "application/x-yaml", "text/yaml"];
+
+// what /latest-version and /versions serialize to, json unless the client
+// asks for yaml in Accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+impl Format {
+    // the format with the highest q-value, json on a tie or when Accept names
+    // neither. `q=0` rules a media range out
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut json = None;
+        let mut yaml = None;
+        for (media_type, quality) in headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(media_range)
+        {
+            let best = match media_type.as_str() {
+                t if JSON_TYPES.contains(&t) => &mut json,
+                t if YAML_TYPES.contains(&t) => &mut yaml,
+                _ => continue,
+            };
+            *best = Some(best.map_or(quality, |q: f32| q.max(quality)));
+        }
+
+        // a format Accept doesn't name ranks like q=0
+        match yaml.unwrap_or(0.0) > json.unwrap_or(0.0) {
+            true => Format::Yaml,
+            false => Format::Json,
+        }
+    }
+
+    // tells caches the body depends on Accept
+    pub fn respond<T: Serialize>(self, value: &T) -> Result<Response, APIError> {
+        let mut response = match self {
+            Format::Json => Json(value).into_response(),
+            Format::Yaml => {
+                let yaml = serde_yaml::to_string(value)
+                    .inspect_err(|e| tracing::error!("failed to serialize yaml: {}", e))
+                    .map_err(|_| APIError::Internal)?;
+                ([(header::CONTENT_TYPE, YAML_TYPES[0])], yaml).into_response()
+            }
+        };
+        response
+            .headers_mut()
+            .insert(header::VARY, HeaderValue::from_static("accept"));
+
+        Ok(response)
+    }
+}
+
+// the lowercased media type and q-value of one Accept entry, q defaults to 1
+// and an unparsable one drops the entry
+fn media_range(range: &str) -> Option<(String, f32)> {
+    let mut parts = range.split(';');
+    let media_type = parts.next()?.trim().to_ascii_lowercase();
+    if media_type.is_empty() {
+        return None;
+    }
+
+    let mut quality = 1.0;
+    for parameter in parts {
+        if let Some((name, value)) = parameter.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("q") {
+                quality = value
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|q| (0.0..=1.0).contains(q))?;
+            }
+        }
+    }
+
+    Some((media_type, quality))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(accept: &[&str]) -> Format {
+        let mut headers = HeaderMap::new();
+        for value in accept {
+            headers.append(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        }
+        Format::from_headers(&headers)
+    }
+
+    #[test]
+    fn defaults_to_json() {
+        assert_eq!(format(&[]), Format::Json);
+        assert_eq!(format(&["text/html"]), Format::Json);
+        assert_eq!(format(&["*/*"]), Format::Json);
+    }
+
+    #[test]
+    fn picks_the_highest_quality() {
+        assert_eq!(format(&["application/yaml"]), Format::Yaml);
+        assert_eq!(
+            format(&["application/json;q=0.5, application/yaml"]),
+            Format::Yaml
+        );
+        assert_eq!(
+            format(&["application/yaml;q=0.5, application/json;q=0.9"]),
+            Format::Json
+        );
+        assert_eq!(format(&["text/yaml; q=0.8", "*/*;q=0.1"]), Format::Yaml);
+        assert_eq!(format(&["Application/X-YAML;Q=0.7"]), Format::Yaml);
+    }
+
+    #[test]
+    fn json_wins_a_tie() {
+        assert_eq!(
+            format(&["application/yaml, application/json"]),
+            Format::Json
+        );
+        assert_eq!(format(&["application/yaml;q=0.5, */*;q=0.5"]), Format::Json);
+    }
+
+    #[test]
+    fn q_zero_is_not_acceptable() {
+        assert_eq!(format(&["application/yaml;q=0"]), Format::Json);
+        assert_eq!(
+            format(&["application/json;q=0, application/yaml;q=0.1"]),
+            Format::Yaml
+        );
+        // the higher of two entries for the same format counts
+        assert_eq!(
+            format(&["application/yaml;q=0, text/yaml;q=0.4, application/json;q=0.3"]),
+            Format::Yaml
+        );
+    }
+
+    #[test]
+    fn ignores_an_unparsable_quality() {
+        assert_eq!(format(&["application/yaml;q=high"]), Format::Json);
+        assert_eq!(format(&["application/yaml;q=2"]), Format::Json);
+    }
+}
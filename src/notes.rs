@@ -0,0 +1,179 @@
+use std::{path::Path, sync::Arc};
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+use crate::{error::APIError, scanner, state::AppState, versions};
+
+#[derive(Deserialize)]
+pub struct NotesQuery {
+    pub lang: Option<String>,
+}
+
+// `?lang=` wins over Accept-Language, regional tags also try the primary
+// language so `id-ID` finds `notes.id.txt`
+pub fn preferred_languages(headers: &HeaderMap, lang: Option<&str>) -> Vec<String> {
+    let mut tags: Vec<(String, f32)> = match lang {
+        Some(lang) => vec![(lang.to_string(), 1.0)],
+        None => headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|part| {
+                let mut pieces = part.trim().split(';');
+                let tag = pieces.next()?.trim();
+                let quality = pieces
+                    .find_map(|piece| piece.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                Some((tag.to_string(), quality))
+            })
+            .collect(),
+    };
+    tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut languages = Vec::new();
+    for (tag, _) in tags {
+        // the tag ends up in a file name
+        if tag.is_empty()
+            || tag == "*"
+            || !tag.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            continue;
+        }
+        let tag = tag.to_ascii_lowercase();
+        let primary = tag.split('-').next().unwrap_or_default().to_string();
+        for language in [tag, primary] {
+            if !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+    }
+
+    languages
+}
+
+// the first `notes.<lang>.txt` that exists, otherwise the default notes.txt,
+// the language is None when the default was used
+pub async fn read_localized_notes(
+    version_directory: &Path,
+    languages: &[String],
+) -> Result<(Option<String>, String), APIError> {
+    for language in languages {
+        let path = version_directory.join(format!("notes.{}.txt", language));
+        if let Some(notes) = scanner::read_text_file(&path).await? {
+            return Ok((Some(language.clone()), notes));
+        }
+    }
+
+    Ok((None, scanner::read_notes(version_directory).await?))
+}
+
+// plain text browsers render instead of downloading, unlike the artifacts
+pub fn inline_text(text: String) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, "inline"),
+        ],
+        text,
+    )
+        .into_response()
+}
+
+// the notes as text, Content-Language tells which translation was picked
+pub async fn get_version_notes(
+    State(state): State<Arc<AppState>>,
+    UrlPath(version): UrlPath<String>,
+    Query(query): Query<NotesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, APIError> {
+    let version_directory = versions::existing_version_directory(&state, &version).await?;
+    let (language, notes) = read_localized_notes(
+        &version_directory,
+        &preferred_languages(&headers, query.lang.as_deref()),
+    )
+    .await?;
+
+    let mut response = inline_text(notes);
+    if let Some(language) = language.and_then(|l| HeaderValue::from_str(&l).ok()) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LANGUAGE, language);
+    }
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    #[tokio::test]
+    async fn picks_the_translation_the_client_asks_for() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .write("1.0.0/notes.id.txt", "catatan")
+            .write("1.0.0/notes.de.txt", "hinweise");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let state = &state;
+        let notes = |uri: &'static str, accept_language: Option<&'static str>| async move {
+            let mut request = Request::get(uri);
+            if let Some(accept_language) = accept_language {
+                request = request.header(header::ACCEPT_LANGUAGE, accept_language);
+            }
+            let response = test_support::send(state, request.body(Body::empty()).unwrap()).await;
+            let language = response
+                .headers()
+                .get(header::CONTENT_LANGUAGE)
+                .map(|value| value.to_str().unwrap().to_string());
+            (language, test_support::body(response).await)
+        };
+
+        assert_eq!(
+            notes("/versions/1.0.0/notes", Some("id-ID, de;q=0.5")).await,
+            (Some("id".to_string()), "catatan".to_string())
+        );
+        assert_eq!(
+            notes("/versions/1.0.0/notes", Some("fr;q=0.9, de;q=0.8")).await,
+            (Some("de".to_string()), "hinweise".to_string())
+        );
+        // `?lang=` wins over the header
+        assert_eq!(
+            notes("/versions/1.0.0/notes?lang=de", Some("id")).await,
+            (Some("de".to_string()), "hinweise".to_string())
+        );
+        assert_eq!(
+            notes("/versions/1.0.0/notes", Some("fr")).await,
+            (None, "notes 1.0.0".to_string())
+        );
+        assert_eq!(
+            notes("/versions/1.0.0/notes?lang=../notes", None).await,
+            (None, "notes 1.0.0".to_string())
+        );
+    }
+
+    #[test]
+    fn orders_languages_by_quality() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT_LANGUAGE,
+            HeaderValue::from_static("de;q=0.2, en-GB, *;q=0.1, fr;q=0.5"),
+        );
+
+        assert_eq!(
+            preferred_languages(&headers, None),
+            ["en-gb", "en", "fr", "de"]
+        );
+        assert_eq!(preferred_languages(&headers, Some("ID")), ["id"]);
+    }
+}
@@ -0,0 +1,68 @@
+use std::{fmt, ops::Deref};
+
+use crate::{config::Config, error::APIError};
+
+// a name from PLATFORMS in its configured spelling, so `Linux_X86_64` in a url
+// and `linux_x86_64` on disk are the same platform. only `parse` creates one,
+// the configured set lives in the runtime config so a plain FromStr can't
+// check against it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Platform(String);
+
+impl Platform {
+    pub fn parse(config: &Config, name: &str) -> Result<Self, APIError> {
+        config
+            .platforms
+            .iter()
+            .find(|platform| platform.eq_ignore_ascii_case(name.trim()))
+            .map(|platform| Platform(platform.clone()))
+            .ok_or_else(|| APIError::PlatformNotFound(name.to_string(), config.platforms.clone()))
+    }
+}
+
+impl Deref for Platform {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    #[test]
+    fn parses_configured_platforms_in_their_canonical_spelling() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[]);
+
+        let platform = Platform::parse(&config, "windows_x86_64").unwrap();
+        assert_eq!(platform.to_string(), "windows_x86_64");
+
+        let mis_cased = Platform::parse(&config, " Windows_X86_64 ").unwrap();
+        assert_eq!(mis_cased, platform);
+        assert_eq!(&*mis_cased, "windows_x86_64");
+    }
+
+    #[test]
+    fn rejects_unknown_platforms_with_the_supported_ones() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("PLATFORMS", "linux_x86_64,windows_x86_64")]);
+
+        match Platform::parse(&config, "darwin_aarch64") {
+            Err(APIError::PlatformNotFound(name, supported)) => {
+                assert_eq!(name, "darwin_aarch64");
+                assert_eq!(supported, ["linux_x86_64", "windows_x86_64"]);
+            }
+            other => panic!("expected PlatformNotFound, got {:?}", other),
+        }
+    }
+}
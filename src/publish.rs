@@ -0,0 +1,251 @@
+use std::{fs::Permissions, os::unix::fs::PermissionsExt};
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::{
+    config::{Config, Layout},
+    error::{APIError, ExistingVersion},
+    scanner, version_manifest,
+};
+
+// lets CI tell a rerun of the same publish from a clashing version
+async fn existing_version(config: &Config, version: &str) -> Result<ExistingVersion, APIError> {
+    let version_directory = std::path::Path::new(&config.kiosk_directory).join(version);
+    let notes = scanner::read_notes(&version_directory).await?;
+
+    Ok(ExistingVersion {
+        pub_date: version_manifest(config, version)
+            .await?
+            .map(|(manifest, _)| manifest.pub_date),
+        notes_sha256: hex::encode(Sha256::digest(notes)),
+    })
+}
+
+// DEFAULT_NOTES for a publish without notes, the notes as given otherwise
+pub fn notes_or_default(config: &Config, version: &str, notes: &str) -> String {
+    match &config.default_notes {
+        Some(template) if notes.trim().is_empty() => template.replace("{version}", version),
+        _ => notes.to_string(),
+    }
+}
+
+// creates the version folder with its notes and an empty folder per platform,
+// shared by the http handler and the `publish` subcommand
+pub async fn create_version(config: &Config, version: &str, notes: &str) -> Result<(), APIError> {
+    // a name list_versions would skip could never be served
+    if scanner::parse_version(config, version).is_none() {
+        return Err(APIError::InvalidVersion);
+    }
+    scanner::ensure_storage(&config.kiosk_directory).await?;
+    // only folders and the notes, the artifacts are checked when uploaded
+    scanner::ensure_free_space(config, notes.len() as u64).await?;
+
+    let kiosk_directory = config.kiosk_directory.clone();
+    let folder_version_name = version.to_string();
+    let kiosk_version_directory =
+        kiosk_directory.clone() + &String::from("/") + &folder_version_name;
+
+    // find folder if exist
+    match fs::try_exists(kiosk_version_directory.clone()).await {
+        Ok(exists) => {
+            if exists {
+                let notes_path =
+                    std::path::Path::new(&kiosk_version_directory).join(scanner::NOTES_FILE);
+                if scanner::is_not_a_file(&notes_path).await {
+                    tracing::error!(
+                        "refused to create version {}, {} is not a regular file",
+                        folder_version_name,
+                        notes_path.display()
+                    );
+                    return Err(APIError::NotesNotAFile(folder_version_name));
+                }
+                tracing::error!(
+                    "failed to create folder {} because folder already exists",
+                    folder_version_name
+                );
+                return Err(APIError::FolderExist(
+                    existing_version(config, &folder_version_name).await?,
+                ));
+            } else {
+                fs::create_dir(kiosk_version_directory.clone())
+                    .await
+                    .inspect_err(|e| {
+                        tracing::error!("failed to create kiosk directory: {:?}", e)
+                    })?;
+
+                let permissions = Permissions::from_mode(0o755);
+
+                // set permission
+                fs::set_permissions(kiosk_version_directory.clone(), permissions)
+                    .await
+                    .inspect_err(|e| tracing::error!("failed to set permission: {}", e))?;
+
+                // before anything else lands so the version is never servable
+                // without an approval
+                if config.require_approval {
+                    fs::write(
+                        std::path::Path::new(&kiosk_version_directory)
+                            .join(scanner::PENDING_MARKER),
+                        b"",
+                    )
+                    .await
+                    .inspect_err(|e| tracing::error!("failed to mark version pending: {}", e))?;
+                }
+
+                // writes note into txt file
+                let content = notes_or_default(config, version, notes);
+                fs::write(
+                    kiosk_version_directory.clone() + &String::from("/") + scanner::NOTES_FILE,
+                    content,
+                )
+                .await
+                .inspect_err(|e| {
+                    tracing::error!("failed to write file: {}", e);
+                })?;
+
+                for platform in &config.platforms {
+                    let kiosk_version_platform_directory =
+                        scanner::platform_path(config, version, platform);
+                    // with the flat layout the platform root may not exist yet
+                    fs::create_dir_all(kiosk_version_platform_directory)
+                        .await
+                        .inspect_err(|e| {
+                            tracing::error!("failed to create kiosk directory: {:?}", e)
+                        })?;
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("failed to check if folder exists: {}", e);
+            return Err(APIError::Internal);
+        }
+    }
+
+    Ok(())
+}
+
+// removes a version folder and everything in it, plus its platform folders
+// with the flat layout
+pub async fn delete_version(config: &Config, version: &str) -> Result<(), APIError> {
+    // only version names so the argument can't point outside KIOSK_DIRECTORY
+    if scanner::parse_version(config, version).is_none() {
+        return Err(APIError::NotFound);
+    }
+
+    if config.layout == Layout::Flat {
+        for platform in &config.platforms {
+            if let Some(folder) =
+                scanner::locate_platform_directory(config, version, platform).await?
+            {
+                fs::remove_dir_all(&folder).await.inspect_err(|e| {
+                    tracing::error!("failed to delete {}: {}", folder.display(), e)
+                })?;
+            }
+        }
+    }
+
+    let version_directory = std::path::Path::new(&config.kiosk_directory).join(version);
+    match fs::remove_dir_all(&version_directory).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(APIError::NotFound),
+        Err(e) => {
+            tracing::error!("failed to delete version {}: {}", version, e);
+            Err(APIError::Internal)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, response::IntoResponse};
+
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    #[tokio::test]
+    async fn refuses_a_version_whose_notes_are_a_directory() {
+        let kiosk = Kiosk::new();
+        std::fs::create_dir_all(kiosk.path().join("1.0.0").join(scanner::NOTES_FILE)).unwrap();
+
+        let result = create_version(&kiosk.config(&[]), "1.0.0", "notes").await;
+
+        assert!(
+            matches!(result, Err(APIError::NotesNotAFile(ref version)) if version == "1.0.0"),
+            "{:?}",
+            result
+        );
+        assert!(kiosk.path().join("1.0.0/notes.txt").is_dir());
+    }
+
+    #[tokio::test]
+    async fn creates_the_notes_as_a_file() {
+        let kiosk = Kiosk::new();
+
+        create_version(&kiosk.config(&[]), "1.0.0", "notes 1.0.0")
+            .await
+            .unwrap();
+
+        let notes = kiosk.path().join("1.0.0").join(scanner::NOTES_FILE);
+        assert_eq!(std::fs::read_to_string(notes).unwrap(), "notes 1.0.0");
+    }
+
+    #[tokio::test]
+    async fn a_clash_reports_the_existing_version() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let config = kiosk.config(&[]);
+        let (manifest, _) = version_manifest(&config, "1.0.0").await.unwrap().unwrap();
+
+        let response = create_version(&config, "1.0.0", "other notes")
+            .await
+            .unwrap_err()
+            .into_response();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            test_support::json(response).await,
+            serde_json::json!({
+                "kiosk_version_error": {
+                    "code": "FolderExist",
+                    "data": {
+                        "pub_date": manifest.pub_date,
+                        "notes_sha256": hex::encode(Sha256::digest("notes 1.0.0")),
+                    },
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn a_clash_with_an_incomplete_version_has_no_pub_date() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[]);
+        create_version(&config, "1.0.0", "notes").await.unwrap();
+
+        let result = create_version(&config, "1.0.0", "notes").await;
+
+        match result {
+            Err(APIError::FolderExist(existing)) => {
+                assert_eq!(existing.pub_date, None);
+                assert_eq!(existing.notes_sha256, hex::encode(Sha256::digest("notes")));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn writes_the_default_notes_for_empty_notes() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("DEFAULT_NOTES", "Release {version}")]);
+
+        create_version(&config, "1.0.0", " \n").await.unwrap();
+        create_version(&config, "1.1.0", "fixes").await.unwrap();
+
+        let notes = |version: &str| {
+            std::fs::read_to_string(kiosk.path().join(version).join(scanner::NOTES_FILE)).unwrap()
+        };
+        assert_eq!(notes("1.0.0"), "Release 1.0.0");
+        assert_eq!(notes("1.1.0"), "fixes");
+    }
+}
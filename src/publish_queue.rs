@@ -0,0 +1,97 @@
+use std::{future::Future, pin::Pin};
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::error::APIError;
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+// runs publish operations one at a time on a single worker so a burst of
+// publishes doesn't thrash the disk, at most `capacity` jobs wait in line
+pub struct PublishQueue {
+    sender: mpsc::Sender<Job>,
+}
+
+impl PublishQueue {
+    pub fn start(capacity: usize) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<Job>(capacity);
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                job.await;
+            }
+        });
+
+        Self { sender }
+    }
+
+    // waits until the worker ran `operation`, fails right away with QueueFull
+    // when the buffer is full
+    pub async fn run<T: Send + 'static>(
+        &self,
+        operation: impl Future<Output = Result<T, APIError>> + Send + 'static,
+    ) -> Result<T, APIError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let job: Job = Box::pin(async move {
+            let _ = result_sender.send(operation.await);
+        });
+
+        self.sender.try_send(job).map_err(|e| match e {
+            mpsc::error::TrySendError::Full(_) => {
+                tracing::warn!("publish queue is full, rejecting publish");
+                APIError::QueueFull
+            }
+            mpsc::error::TrySendError::Closed(_) => APIError::Internal,
+        })?;
+
+        result_receiver.await.map_err(|_| APIError::Internal)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn a_full_queue_rejects_new_publishes() {
+        let queue = Arc::new(PublishQueue::start(1));
+        let started = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+
+        // keeps the worker busy until released
+        let running = tokio::spawn({
+            let (queue, started, release) = (queue.clone(), started.clone(), release.clone());
+            async move {
+                queue
+                    .run(async move {
+                        started.notify_one();
+                        release.notified().await;
+                        Ok(1)
+                    })
+                    .await
+            }
+        });
+        started.notified().await;
+        // takes the only free slot
+        let waiting = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.run(async { Ok(2) }).await }
+        });
+        while queue.sender.capacity() > 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(matches!(
+            queue.run(async { Ok(3) }).await,
+            Err(APIError::QueueFull)
+        ));
+
+        release.notify_one();
+        assert_eq!(running.await.unwrap().unwrap(), 1);
+        assert_eq!(waiting.await.unwrap().unwrap(), 2);
+        assert_eq!(queue.run(async { Ok(4) }).await.unwrap(), 4);
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::{atomic::Ordering, Arc};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use tokio::fs;
+
+use crate::state::AppState;
+
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub storage: CheckStatus,
+    pub database: CheckStatus,
+    pub cache: CheckStatus,
+}
+
+// unlike /health this only returns 200 once the server can both serve and
+// publish versions
+pub async fn readiness_handler(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let storage = check_storage(&state.config.kiosk_directory).await;
+
+    let database = match &state.db {
+        Some(db) => match db.ping().await {
+            Ok(_) => CheckStatus::Ok,
+            Err(e) => {
+                tracing::warn!("readiness database ping failed: {}", e);
+                CheckStatus::Failed
+            }
+        },
+        None => CheckStatus::Skipped,
+    };
+
+    // once warmed the cache refills on demand, an expired or invalidated
+    // entry doesn't make the server unready again. a warmup that failed at
+    // startup (no complete version yet) is retried here
+    if state.config.warm_cache_on_start && !state.cache_warmed.load(Ordering::SeqCst) {
+        state.warm_latest_version_cache().await;
+    }
+    let cache = if !state.config.warm_cache_on_start {
+        CheckStatus::Skipped
+    } else if state.cache_warmed.load(Ordering::SeqCst) {
+        CheckStatus::Ok
+    } else {
+        CheckStatus::Failed
+    };
+
+    let ready = [&storage, &database, &cache]
+        .iter()
+        .all(|status| **status != CheckStatus::Failed);
+    let status_code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            ready,
+            storage,
+            database,
+            cache,
+        }),
+    )
+}
+
+async fn check_storage(kiosk_directory: &str) -> CheckStatus {
+    let probe =
+        std::path::Path::new(kiosk_directory).join(format!(".ready-check-{}", std::process::id()));
+
+    if let Err(e) = fs::write(&probe, b"ready").await {
+        tracing::warn!("readiness storage check failed: {}", e);
+        return CheckStatus::Failed;
+    }
+    if let Err(e) = fs::remove_file(&probe).await {
+        tracing::warn!("failed to remove readiness probe file: {}", e);
+    }
+
+    CheckStatus::Ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::ManifestKey, test_support::Kiosk};
+
+    #[tokio::test]
+    async fn ready_when_every_check_passes() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[("WARM_CACHE_ON_START", "true")]))
+            .await
+            .unwrap();
+
+        let (status, Json(response)) = readiness_handler(State(state)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.ready);
+        assert!(response.storage == CheckStatus::Ok);
+        assert!(response.database == CheckStatus::Skipped);
+        assert!(response.cache == CheckStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn stays_ready_after_the_cache_is_invalidated() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[("WARM_CACHE_ON_START", "true")]))
+            .await
+            .unwrap();
+
+        state.latest_version_cache.invalidate().await;
+        assert!(
+            !state
+                .latest_version_cache
+                .is_populated(&ManifestKey::default())
+                .await
+        );
+        let (status, Json(response)) = readiness_handler(State(state)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(response.cache == CheckStatus::Ok);
+    }
+
+    #[tokio::test]
+    async fn degraded_until_a_complete_version_exists() {
+        let kiosk = Kiosk::new();
+        // an artifact still waiting for its signature
+        kiosk.write("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz", "bin");
+        let state = AppState::build(kiosk.config(&[("WARM_CACHE_ON_START", "true")]))
+            .await
+            .unwrap();
+
+        let (status, Json(response)) = readiness_handler(State(state.clone())).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(!response.ready);
+        assert!(response.cache == CheckStatus::Failed);
+
+        kiosk.add_platform("1.0.0", "linux_x86_64");
+        let (status, _) = readiness_handler(State(state)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn degraded_when_storage_is_missing() {
+        let kiosk = Kiosk::new();
+        let mut config = kiosk.config(&[]);
+        config.kiosk_directory = kiosk.path().join("missing").to_str().unwrap().to_string();
+        let state = AppState::build(config).await.unwrap();
+
+        let (status, Json(response)) = readiness_handler(State(state)).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.storage == CheckStatus::Failed);
+        assert!(response.cache == CheckStatus::Skipped);
+    }
+}
@@ -0,0 +1,192 @@
+use std::{path::PathBuf, sync::Arc, time::SystemTime};
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{
+    config::Config, error::APIError, resolve_latest_version_at, scanner, state::AppState,
+    version_manifest,
+};
+
+// file in KIOSK_DIRECTORY holding the version /latest-version serves instead of
+// the newest one, the newer folders stay untouched
+pub const PIN_FILE: &str = ".pinned-version";
+
+#[derive(Serialize)]
+pub struct RollbackResponse {
+    // the version clients are pointed at now
+    pub version: String,
+    pub pinned: bool,
+}
+
+fn pin_path(config: &Config) -> PathBuf {
+    std::path::Path::new(&config.kiosk_directory).join(PIN_FILE)
+}
+
+pub async fn read_pin(config: &Config) -> Result<Option<String>, APIError> {
+    match fs::read_to_string(pin_path(config)).await {
+        // only version names so an edited file can't point outside KIOSK_DIRECTORY
+        Ok(version) => Ok(Some(version.trim().to_string())
+            .filter(|version| scanner::parse_version(config, version).is_some())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => {
+            tracing::error!("failed to read {}: {}", PIN_FILE, e);
+            Err(e.into())
+        }
+    }
+}
+
+// pins the newest servable version older than the one that would be served
+// without a pin
+pub async fn rollback(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RollbackResponse>, APIError> {
+    let config = &state.config;
+    let current = resolve_latest_version_at(config, SystemTime::now()).await?;
+    let current_version = scanner::parse_version(config, &current.version);
+
+    let folders = scanner::list_versions(config).await?;
+    for (version, name) in folders.versions {
+        let is_older = current_version.as_ref().is_some_and(|current_version| {
+            scanner::compare_folders(
+                config,
+                (&version, &name),
+                (current_version, &current.version),
+            )
+            .is_lt()
+        });
+        if !is_older {
+            continue;
+        }
+        let version_directory = std::path::Path::new(&config.kiosk_directory).join(&name);
+        if scanner::is_yanked(&version_directory).await {
+            continue;
+        }
+        if version_manifest(config, &name).await?.is_none() {
+            continue;
+        }
+
+        fs::write(pin_path(config), &name)
+            .await
+            .inspect_err(|e| tracing::error!("failed to write {}: {}", PIN_FILE, e))?;
+        state.latest_version_cache.invalidate().await;
+        tracing::info!(
+            "rolled back from {} to {}, {} stays on disk",
+            current.version,
+            name,
+            current.version
+        );
+
+        return Ok(Json(RollbackResponse {
+            version: name,
+            pinned: true,
+        }));
+    }
+
+    tracing::warn!(
+        "rollback requested but no servable version older than {}",
+        current.version
+    );
+    Err(APIError::FileOrPathNotExist)
+}
+
+// drops the pin so the newest servable version is served again
+pub async fn rollforward(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<RollbackResponse>, APIError> {
+    let config = &state.config;
+    match fs::remove_file(pin_path(config)).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            tracing::error!("failed to remove {}: {}", PIN_FILE, e);
+            return Err(APIError::Internal);
+        }
+    }
+    state.latest_version_cache.invalidate().await;
+
+    let latest = resolve_latest_version_at(config, SystemTime::now()).await?;
+    tracing::info!("rolled forward to {}", latest.version);
+
+    Ok(Json(RollbackResponse {
+        version: latest.version,
+        pinned: false,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    async fn served(state: &Arc<AppState>) -> serde_json::Value {
+        test_support::json(test_support::get(state, "/latest-version").await).await["version"]
+            .clone()
+    }
+
+    // 1.3.0 is served, 1.2.0 lacks a signature and 1.1.0 is yanked
+    fn kiosk() -> Kiosk {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .add_version("1.1.0")
+            .add_version("1.3.0");
+        kiosk.write("1.1.0/.yanked", "");
+        kiosk.write("1.2.0/linux_x86_64/app.tar.gz", "bin 1.2.0");
+        kiosk
+    }
+
+    #[tokio::test]
+    async fn pins_the_previous_servable_version() {
+        let kiosk = kiosk();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        assert_eq!(served(&state).await, "1.3.0");
+
+        let Json(response) = rollback(State(state.clone())).await.unwrap();
+
+        assert_eq!(response.version, "1.0.0");
+        assert!(response.pinned);
+        assert_eq!(
+            std::fs::read_to_string(kiosk.path().join(PIN_FILE)).unwrap(),
+            "1.0.0"
+        );
+        assert_eq!(served(&state).await, "1.0.0");
+        // nothing is deleted, rolling forward has to find it again
+        assert!(kiosk
+            .path()
+            .join("1.3.0/linux_x86_64/app-linux_x86_64.tar.gz")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn rollforward_serves_the_newest_version_again() {
+        let kiosk = kiosk();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        assert_eq!(
+            rollback(State(state.clone())).await.unwrap().version,
+            "1.0.0"
+        );
+
+        let Json(response) = rollforward(State(state.clone())).await.unwrap();
+
+        assert_eq!(response.version, "1.3.0");
+        assert!(!response.pinned);
+        assert!(!kiosk.path().join(PIN_FILE).exists());
+        assert_eq!(served(&state).await, "1.3.0");
+        // without a pin there is nothing to remove
+        assert!(rollforward(State(state.clone())).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn refuses_a_rollback_without_an_older_version() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let result = rollback(State(state)).await;
+
+        assert!(matches!(result, Err(APIError::FileOrPathNotExist)));
+        assert!(!kiosk.path().join(PIN_FILE).exists());
+    }
+}
@@ -0,0 +1,177 @@
+use std::{collections::VecDeque, sync::Arc, sync::Mutex};
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    Json,
+};
+use sea_orm::sqlx::types::chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::state::AppState;
+
+// the header a kiosk identifies itself with on update checks
+pub const CLIENT_ID_HEADER: &str = "x-client-id";
+
+// update checks kept for /admin/rollout-events, the oldest go first
+pub const CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RolloutDecision {
+    Offered,
+    OutsideWindow,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RolloutEvent {
+    pub client_id: String,
+    // None when nothing was offered
+    pub version: Option<String>,
+    pub decision: RolloutDecision,
+    pub at: String,
+}
+
+// which client was offered what since the process started, in memory only
+#[derive(Default)]
+pub struct RolloutLog {
+    events: Mutex<VecDeque<RolloutEvent>>,
+}
+
+impl RolloutLog {
+    // checks without a client id aren't recorded
+    pub fn record(&self, headers: &HeaderMap, version: Option<&str>, decision: RolloutDecision) {
+        let Some(client_id) = client_id(headers) else {
+            return;
+        };
+        tracing::info!(
+            "rollout: client {} {:?} {}",
+            client_id,
+            decision,
+            version.unwrap_or("-")
+        );
+
+        let mut events = self.events.lock().unwrap();
+        if events.len() == CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(RolloutEvent {
+            client_id,
+            version: version.map(String::from),
+            decision,
+            at: Utc::now().to_rfc3339(),
+        });
+    }
+
+    fn events(&self, version: Option<&str>) -> Vec<RolloutEvent> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| version.is_none() || event.version.as_deref() == version)
+            .cloned()
+            .collect()
+    }
+}
+
+fn client_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CLIENT_ID_HEADER)?
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|client_id| !client_id.is_empty())
+        .map(String::from)
+}
+
+#[derive(Deserialize)]
+pub struct RolloutEventsQuery {
+    pub version: Option<String>,
+}
+
+// oldest first, only the checks that offered `version` when it is given
+pub async fn get_rollout_events(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RolloutEventsQuery>,
+) -> Json<Vec<RolloutEvent>> {
+    Json(state.rollout_log.events(query.version.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    fn from(client_id: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CLIENT_ID_HEADER, client_id.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn only_records_identified_clients() {
+        let log = RolloutLog::default();
+
+        log.record(&HeaderMap::new(), Some("1.0.0"), RolloutDecision::Offered);
+        log.record(&from("  "), Some("1.0.0"), RolloutDecision::Offered);
+        log.record(&from(" kiosk-7 "), None, RolloutDecision::OutsideWindow);
+
+        let events = log.events(None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].client_id, "kiosk-7");
+        assert_eq!(events[0].version, None);
+        assert_eq!(events[0].decision, RolloutDecision::OutsideWindow);
+    }
+
+    #[test]
+    fn drops_the_oldest_event_when_full() {
+        let log = RolloutLog::default();
+
+        for client in 0..=CAPACITY {
+            log.record(
+                &from(&format!("kiosk-{}", client)),
+                Some("1.0.0"),
+                RolloutDecision::Offered,
+            );
+        }
+
+        let events = log.events(None);
+        assert_eq!(events.len(), CAPACITY);
+        assert_eq!(events[0].client_id, "kiosk-1");
+        assert_eq!(
+            events[CAPACITY - 1].client_id,
+            format!("kiosk-{}", CAPACITY)
+        );
+    }
+
+    #[tokio::test]
+    async fn filters_events_by_version() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        state
+            .rollout_log
+            .record(&from("a"), Some("1.0.0"), RolloutDecision::Offered);
+        state
+            .rollout_log
+            .record(&from("b"), Some("1.1.0"), RolloutDecision::Offered);
+        state
+            .rollout_log
+            .record(&from("c"), None, RolloutDecision::OutsideWindow);
+        let clients = |version: Option<&str>| {
+            let state = state.clone();
+            let version = version.map(String::from);
+            async move {
+                let Json(events) =
+                    get_rollout_events(State(state), Query(RolloutEventsQuery { version })).await;
+                events
+                    .into_iter()
+                    .map(|event| event.client_id)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(clients(None).await, ["a", "b", "c"]);
+        assert_eq!(clients(Some("1.1.0")).await, ["b"]);
+        assert!(clients(Some("2.0.0")).await.is_empty());
+    }
+}
@@ -0,0 +1,1216 @@
+use std::{
+    cmp::Ordering,
+    future::Future,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use semver::Version;
+use serde::Serialize;
+use tokio::fs;
+
+use crate::{
+    checksum,
+    config::{Config, Layout, PrereleaseOrder, SymlinkedVersions, VersionScheme},
+    error::APIError,
+};
+
+// key of a platform folder in the tauri manifest, `linux_x86_64` becomes
+// `linux-x86_64` (the arch part keeps its underscore)
+pub fn manifest_key(platform: &str) -> String {
+    platform.replacen('_', "-", 1)
+}
+
+// release notes of a version, `notes.<lang>.txt` next to it are translations
+pub const NOTES_FILE: &str = "notes.txt";
+
+// marker file that keeps a version on disk but stops it from being served
+pub const YANKED_MARKER: &str = ".yanked";
+
+// marker file of a version published under REQUIRE_APPROVAL that nobody
+// approved yet, it isn't served until then
+pub const PENDING_MARKER: &str = ".pending";
+
+// who approved a pending version and when, written when the marker goes
+pub const APPROVED_FILE: &str = ".approved";
+
+// marker file that tells clients the version is a mandatory update
+pub const REQUIRED_MARKER: &str = ".required";
+
+// tauri manifest registered by CI, served instead of scanning the platforms
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+// extension of artifacts that are still being uploaded
+pub const PART_EXTENSION: &str = "part";
+
+// armor lines around a detached pgp signature
+const PGP_SIGNATURE_BEGIN: &str = "-----BEGIN PGP SIGNATURE-----";
+const PGP_SIGNATURE_END: &str = "-----END PGP SIGNATURE-----";
+
+pub struct VersionFolders {
+    // sorted in descending order (latest first)
+    pub versions: Vec<(Version, String)>,
+    // folders skipped because their name isn't a version under VERSION_SCHEME
+    pub ignored: Vec<IgnoredFolder>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IgnoredFolder {
+    pub name: String,
+    // why the name isn't a version, e.g. what semver choked on
+    pub reason: String,
+}
+
+// KIOSK_DIRECTORY has to be a directory, if it went missing or was replaced by
+// a file at runtime every request fails with StorageUnavailable instead of an
+// unrelated io error
+pub async fn ensure_storage(kiosk_directory: &str) -> Result<(), APIError> {
+    match fs::metadata(kiosk_directory).await {
+        Ok(metadata) if metadata.is_dir() => Ok(()),
+        Ok(_) => {
+            tracing::error!("KIOSK_DIRECTORY {} is not a directory", kiosk_directory);
+            Err(APIError::StorageUnavailable)
+        }
+        Err(e) => {
+            tracing::error!("KIOSK_DIRECTORY {} is unavailable: {}", kiosk_directory, e);
+            Err(APIError::StorageUnavailable)
+        }
+    }
+}
+
+// refuses a write of `required` bytes up front when it would leave less than
+// MIN_FREE_SPACE_BYTES, so a full disk can't leave a half written version
+pub async fn ensure_free_space(config: &Config, required: u64) -> Result<(), APIError> {
+    let kiosk_directory = config.kiosk_directory.clone();
+    let available = tokio::task::spawn_blocking(move || fs2::available_space(kiosk_directory))
+        .await
+        .map_err(|_| APIError::Internal)?
+        .inspect_err(|e| tracing::error!("failed to query free space: {}", e))?;
+
+    let needed = required.saturating_add(config.min_free_space_bytes);
+    if available < needed {
+        tracing::error!(
+            "refusing to write {} bytes, {} bytes free but {} have to stay free",
+            required,
+            available,
+            config.min_free_space_bytes
+        );
+        return Err(APIError::InsufficientStorage);
+    }
+
+    Ok(())
+}
+
+pub async fn list_versions(config: &Config) -> Result<VersionFolders, APIError> {
+    let kiosk_directory = config.kiosk_directory.as_str();
+    ensure_storage(kiosk_directory).await?;
+
+    let mut entries = fs::read_dir(kiosk_directory).await?;
+    let mut versions = Vec::new();
+    let mut ignored = Vec::new();
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if entry.file_type().await?.is_symlink() {
+            let is_alias = match config.symlinked_versions {
+                SymlinkedVersions::Ignore => true,
+                SymlinkedVersions::Alias => match path.file_name().and_then(|n| n.to_str()) {
+                    Some(name) => resolve_alias(config, name).await.is_some(),
+                    None => false,
+                },
+            };
+            // otherwise the target would be offered twice
+            if is_alias {
+                continue;
+            }
+        }
+        if path.is_dir() {
+            if let Some(folder_name) = path.file_name().and_then(|n| n.to_str()) {
+                match parse_version(config, folder_name) {
+                    _ if folder_name.starts_with('.') => {}
+                    // with the flat layout the platform folders sit next to the versions
+                    _ if is_platform_root(config, folder_name) => {}
+                    Some(ver) => versions.push((ver, folder_name.to_string())),
+                    None => ignored.push(IgnoredFolder {
+                        name: folder_name.to_string(),
+                        reason: ignore_reason(config, folder_name),
+                    }),
+                }
+            }
+        }
+    }
+
+    versions.sort_by(|a, b| compare_folders(config, (&b.0, &b.1), (&a.0, &a.1)));
+    ignored.sort_by(|a, b| a.name.cmp(&b.name));
+
+    if !ignored.is_empty() {
+        tracing::warn!(
+            "skipped folders in {} that are not valid {:?} versions: {}",
+            kiosk_directory,
+            config.version_scheme,
+            ignored
+                .iter()
+                .map(|folder| format!("{} ({})", folder.name, folder.reason))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    Ok(VersionFolders { versions, ignored })
+}
+
+// the version folder a symlink in KIOSK_DIRECTORY points at, None for
+// anything else or with SYMLINKED_VERSIONS=ignore
+pub async fn resolve_alias(config: &Config, name: &str) -> Option<String> {
+    if config.symlinked_versions != SymlinkedVersions::Alias || !is_plain_file_name(name) {
+        return None;
+    }
+    let kiosk_directory = Path::new(&config.kiosk_directory);
+    let path = kiosk_directory.join(name);
+    if !fs::symlink_metadata(&path).await.ok()?.is_symlink() {
+        return None;
+    }
+
+    // only into KIOSK_DIRECTORY itself, chains of symlinks included
+    let target = fs::canonicalize(&path).await.ok()?;
+    if target.parent()? != fs::canonicalize(kiosk_directory).await.ok()? || !target.is_dir() {
+        return None;
+    }
+    let version = target.file_name()?.to_str()?;
+    parse_version(config, version)?;
+
+    Some(version.to_string())
+}
+
+// the sort key of a version folder under VERSION_SCHEME, None for names that
+// aren't versions. calver `YYYY.MM.DD.N` becomes `YYYY.(MM * 100 + DD).N` and
+// lexical gives every name the same key so only the folder name ranks them
+pub fn parse_version(config: &Config, name: &str) -> Option<Version> {
+    match config.version_scheme {
+        VersionScheme::Semver => name.parse().ok(),
+        VersionScheme::Calver => parse_calver(name),
+        VersionScheme::Lexical => {
+            (is_plain_file_name(name) && name != ATTACHMENTS).then(|| Version::new(0, 0, 0))
+        }
+    }
+}
+
+// what parse_version didn't like about a folder name
+fn ignore_reason(config: &Config, name: &str) -> String {
+    match config.version_scheme {
+        VersionScheme::Semver => match name.parse::<Version>() {
+            Err(e) => e.to_string(),
+            Ok(_) => "not a version".to_string(),
+        },
+        VersionScheme::Calver => "not a YYYY.MM.DD[.N] date".to_string(),
+        VersionScheme::Lexical => "not a plain folder name".to_string(),
+    }
+}
+
+fn parse_calver(name: &str) -> Option<Version> {
+    let number = |part: &str| {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        part.parse::<u64>().ok()
+    };
+
+    let parts: Vec<&str> = name.split('.').collect();
+    let (year, month, day, build) = match parts.as_slice() {
+        [year, month, day] => (*year, *month, *day, None),
+        [year, month, day, build] => (*year, *month, *day, Some(number(build)?)),
+        _ => return None,
+    };
+    if year.len() != 4 || month.len() > 2 || day.len() > 2 {
+        return None;
+    }
+    let (year, month, day) = (number(year)?, number(month)?, number(day)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(Version::new(year, month * 100 + day, build.unwrap_or(0)))
+}
+
+// folder names break ties so versions that rank equal always come out in the
+// same order and /latest-version can't flip between them
+pub fn compare_folders(config: &Config, a: (&Version, &str), b: (&Version, &str)) -> Ordering {
+    compare_versions(config, a.0, b.0).then_with(|| a.1.cmp(b.1))
+}
+
+// ordering of versions under PRERELEASE_ORDER, latest is greatest
+pub fn compare_versions(config: &Config, a: &Version, b: &Version) -> Ordering {
+    match config.prerelease_order {
+        PrereleaseOrder::Semver => a.cmp(b),
+        PrereleaseOrder::PrereleaseFirst => (a.major, a.minor, a.patch)
+            .cmp(&(b.major, b.minor, b.patch))
+            // a prerelease ranks above the release of the same base
+            .then_with(|| b.pre.is_empty().cmp(&a.pre.is_empty()))
+            .then_with(|| a.cmp(b)),
+    }
+}
+
+fn is_platform_root(config: &Config, folder_name: &str) -> bool {
+    config.layout == Layout::Flat
+        && config
+            .platforms
+            .iter()
+            .any(|platform| platform.eq_ignore_ascii_case(folder_name))
+}
+
+// where the folder of `platform` in `version` belongs for the configured
+// layout, used when creating it
+pub fn platform_path(config: &Config, version: &str, platform: &str) -> PathBuf {
+    let kiosk_directory = Path::new(&config.kiosk_directory);
+    match config.layout {
+        Layout::Nested => kiosk_directory.join(version).join(platform),
+        Layout::Flat => kiosk_directory.join(platform).join(version),
+    }
+}
+
+// the existing folder of `platform` in `version` for the configured layout,
+// the platform name is matched case-insensitively like find_platform_directory
+pub async fn locate_platform_directory(
+    config: &Config,
+    version: &str,
+    platform: &str,
+) -> std::io::Result<Option<PathBuf>> {
+    let kiosk_directory = Path::new(&config.kiosk_directory);
+    match config.layout {
+        Layout::Nested => find_platform_directory(&kiosk_directory.join(version), platform).await,
+        Layout::Flat => {
+            let Some(platform_root) = find_platform_directory(kiosk_directory, platform).await?
+            else {
+                return Ok(None);
+            };
+            let folder = platform_root.join(version);
+            Ok(fs::metadata(&folder)
+                .await
+                .is_ok_and(|m| m.is_dir())
+                .then_some(folder))
+        }
+    }
+}
+
+// returns the folder for `platform` inside a version folder, falling back to a
+// case-insensitive match so `Windows_x86_64` is still picked up (with a warning)
+pub async fn find_platform_directory(
+    version_directory: &Path,
+    platform: &str,
+) -> std::io::Result<Option<PathBuf>> {
+    let exact = version_directory.join(platform);
+    if fs::metadata(&exact).await.is_ok_and(|m| m.is_dir()) {
+        return Ok(Some(exact));
+    }
+
+    let mut entries = match fs::read_dir(version_directory).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let folder_name = entry.file_name();
+        let Some(folder_name) = folder_name.to_str() else {
+            continue;
+        };
+        if folder_name.eq_ignore_ascii_case(platform) && entry.file_type().await?.is_dir() {
+            tracing::warn!(
+                "platform folder {} in {} should be named {}",
+                folder_name,
+                version_directory.display(),
+                platform
+            );
+            return Ok(Some(entry.path()));
+        }
+    }
+
+    Ok(None)
+}
+
+// platform folders of a version that aren't in PLATFORMS, e.g. an
+// `android_aarch64` build the server doesn't serve
+pub async fn extra_platforms(config: &Config, version: &str) -> std::io::Result<Vec<String>> {
+    let kiosk_directory = Path::new(&config.kiosk_directory);
+    let candidates_directory = match config.layout {
+        Layout::Nested => kiosk_directory.join(version),
+        Layout::Flat => kiosk_directory.to_path_buf(),
+    };
+
+    let mut extra = Vec::new();
+    let mut entries = fs::read_dir(&candidates_directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let Some(name) = entry.file_name().to_str().map(String::from) else {
+            continue;
+        };
+        let is_known = config
+            .platforms
+            .iter()
+            .any(|platform| platform.eq_ignore_ascii_case(&name));
+        if is_known
+            || name == ATTACHMENTS
+            || name.starts_with('.')
+            || (config.layout == Layout::Flat && parse_version(config, &name).is_some())
+            || !entry.file_type().await?.is_dir()
+        {
+            continue;
+        }
+        if config.layout == Layout::Flat
+            && !fs::metadata(entry.path().join(version))
+                .await
+                .is_ok_and(|m| m.is_dir())
+        {
+            continue;
+        }
+        extra.push(name);
+    }
+    extra.sort();
+
+    Ok(extra)
+}
+
+// download and upload path segment that stands for the version folder root
+// instead of a platform folder
+pub const ATTACHMENTS: &str = "_attachments";
+
+// a single file name from a path parameter, no separators and no hidden files
+// so it can't point outside the folder it's joined to
+pub fn is_plain_file_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+// folder that holds the downloadable files for `platform`, the version root for
+// ATTACHMENTS, None when the version or platform doesn't exist
+pub async fn artifact_directory(
+    config: &Config,
+    version: &str,
+    platform: &str,
+) -> std::io::Result<Option<PathBuf>> {
+    // only version names so the version can't escape KIOSK_DIRECTORY
+    if parse_version(config, version).is_none() {
+        return Ok(None);
+    }
+
+    if platform == ATTACHMENTS {
+        let version_directory = Path::new(&config.kiosk_directory).join(version);
+        return Ok(fs::metadata(&version_directory)
+            .await
+            .is_ok_and(|m| m.is_dir())
+            .then_some(version_directory));
+    }
+
+    locate_platform_directory(config, version, platform).await
+}
+
+// full path of a downloadable file, None when its version or platform folder
+// doesn't exist, `file` has to pass is_plain_file_name
+pub async fn artifact_path(
+    config: &Config,
+    version: &str,
+    platform: &str,
+    file: &str,
+) -> std::io::Result<Option<PathBuf>> {
+    Ok(artifact_directory(config, version, platform)
+        .await?
+        .map(|directory| directory.join(file)))
+}
+
+// regular files in the version root other than notes and markers, e.g. a pdf
+// with the release notes, as name and size
+pub async fn list_attachments(version_directory: &Path) -> std::io::Result<Vec<(String, u64)>> {
+    let mut attachments = Vec::new();
+    let mut entries = fs::read_dir(version_directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        let Some(name) = entry.file_name().to_str().map(String::from) else {
+            continue;
+        };
+        let is_notes = name.starts_with("notes.") && name.ends_with(".txt");
+        let is_sidecar = name.ends_with(&format!(".{}", PART_EXTENSION))
+            || name.ends_with(&format!(".{}", checksum::MD5_EXTENSION))
+            || name.ends_with(&format!(".{}", checksum::SHA256_EXTENSION));
+        let is_manifest = name == MANIFEST_FILE;
+        if metadata.is_file()
+            && is_plain_file_name(&name)
+            && !is_notes
+            && !is_sidecar
+            && !is_manifest
+        {
+            attachments.push((name, metadata.len()));
+        }
+    }
+    attachments.sort();
+
+    Ok(attachments)
+}
+
+pub async fn is_yanked(version_directory: &Path) -> bool {
+    fs::try_exists(version_directory.join(YANKED_MARKER))
+        .await
+        .unwrap_or(false)
+}
+
+pub async fn is_pending(version_directory: &Path) -> bool {
+    fs::try_exists(version_directory.join(PENDING_MARKER))
+        .await
+        .unwrap_or(false)
+}
+
+pub async fn is_required(version_directory: &Path) -> bool {
+    fs::try_exists(version_directory.join(REQUIRED_MARKER))
+        .await
+        .unwrap_or(false)
+}
+
+// total size of every regular file below `directory`
+pub async fn directory_size(directory: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    let mut pending = vec![directory.to_path_buf()];
+
+    while let Some(directory) = pending.pop() {
+        let mut entries = fs::read_dir(&directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file() {
+                total += entry.metadata().await?.len();
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+// bounds a filesystem call so a stuck NFS mount fails the call with
+// `TimedOut` instead of hanging the request, the blocking thread underneath
+// may still be stuck but the request moves on
+pub async fn timed<T>(
+    timeout: Duration,
+    operation: impl Future<Output = std::io::Result<T>>,
+) -> std::io::Result<T> {
+    tokio::time::timeout(timeout, operation)
+        .await
+        .unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into()))
+}
+
+pub struct PlatformFiles {
+    pub folder: PathBuf,
+    // path and content of the `.sig` file
+    pub signature: Option<(PathBuf, String)>,
+    // content of an optional detached `.asc` pgp signature
+    pub pgp_signature: Option<String>,
+    // file name of the downloadable artifact
+    pub artifact: Option<String>,
+    // size of the artifact in bytes
+    pub artifact_size: Option<u64>,
+    // newest created (or modified) time of the files in the folder
+    pub modified: SystemTime,
+}
+
+pub async fn read_platform_folder(
+    config: &Config,
+    platform_folder: &Path,
+) -> std::io::Result<PlatformFiles> {
+    let timeout = config.fs_op_timeout;
+    let mut files = PlatformFiles {
+        folder: platform_folder.to_path_buf(),
+        signature: None,
+        pgp_signature: None,
+        artifact: None,
+        artifact_size: None,
+        modified: SystemTime::UNIX_EPOCH,
+    };
+
+    let mut entries = timed(timeout, fs::read_dir(platform_folder)).await?;
+    while let Some(entry) = timed(timeout, entries.next_entry()).await? {
+        let metadata = timed(timeout, entry.metadata()).await?;
+        if !metadata.is_file() {
+            continue;
+        }
+        files.modified = files
+            .modified
+            .max(metadata.created().or_else(|_| metadata.modified())?);
+
+        let path = entry.path();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("sig") => {
+                let content = timed(timeout, fs::read_to_string(&path)).await?;
+                files.signature = Some((path, content));
+            }
+            Some("asc") => {
+                let content = timed(timeout, fs::read_to_string(&path)).await?;
+                if is_pgp_signature(&content) {
+                    files.pgp_signature = Some(content);
+                } else {
+                    tracing::warn!(
+                        "ignoring {}, not an ascii armored pgp signature",
+                        path.display()
+                    );
+                }
+            }
+            Some(PART_EXTENSION)
+            | Some(checksum::MD5_EXTENSION)
+            | Some(checksum::SHA256_EXTENSION) => {}
+            _ => {
+                let Some(file_name) = path.file_name().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                if is_artifact(config, file_name) {
+                    files.artifact = Some(file_name.to_string());
+                    files.artifact_size = Some(metadata.len());
+                } else {
+                    tracing::debug!(
+                        "ignoring {} in {}, not an allowed artifact extension",
+                        file_name,
+                        platform_folder.display()
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+// stray files like `.DS_Store` or editor backups must not end up as the
+// download url, so only files ending in ARTIFACT_EXTENSIONS count
+pub fn is_artifact(config: &Config, file_name: &str) -> bool {
+    let file_name = file_name.to_ascii_lowercase();
+    config
+        .artifact_extensions
+        .iter()
+        .any(|extension| file_name.ends_with(&extension.to_ascii_lowercase()))
+}
+
+// minisign signatures, read by updaters as text rather than saved to disk
+pub fn is_signature_file(file_name: &str) -> bool {
+    let file_name = file_name.to_ascii_lowercase();
+    file_name.ends_with(".sig") || file_name.ends_with(".minisig")
+}
+
+// reads one platform folder of a version, None means the platform is
+// unavailable (missing folder or a filesystem call timed out)
+pub async fn read_platform(
+    config: &Config,
+    version: &str,
+    platform_name: &str,
+) -> Result<Option<PlatformFiles>, APIError> {
+    Ok(
+        match read_platform_state(config, version, platform_name).await? {
+            PlatformState::Files(files) => Some(files),
+            PlatformState::Absent | PlatformState::Unavailable => None,
+        },
+    )
+}
+
+enum PlatformState {
+    Files(PlatformFiles),
+    // the version has no folder for the platform at all
+    Absent,
+    // a filesystem call timed out
+    Unavailable,
+}
+
+async fn read_platform_state(
+    config: &Config,
+    version: &str,
+    platform_name: &str,
+) -> Result<PlatformState, APIError> {
+    let platform_folder = match timed(
+        config.fs_op_timeout,
+        locate_platform_directory(config, version, platform_name),
+    )
+    .await
+    {
+        Ok(Some(folder)) => folder,
+        Ok(None) => {
+            tracing::debug!(
+                "version {} has no {} platform folder",
+                version,
+                platform_name
+            );
+            return Ok(PlatformState::Absent);
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+            tracing::warn!(
+                "timed out looking up the {} folder of version {}",
+                platform_name,
+                version
+            );
+            return Ok(PlatformState::Unavailable);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    match read_platform_folder(config, &platform_folder).await {
+        Ok(files) => Ok(PlatformState::Files(files)),
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+            tracing::warn!(
+                "timed out reading {}, treating it as unavailable",
+                platform_folder.display()
+            );
+            Ok(PlatformState::Unavailable)
+        }
+        Err(e) => {
+            tracing::error!(
+                "failed to read directory {}: {}",
+                platform_folder.display(),
+                e
+            );
+            Err(APIError::FileOrPathNotExist)
+        }
+    }
+}
+
+// a detached `.asc` has to be an armored signature, a key or a minisign
+// signature saved under the wrong extension is left out
+fn is_pgp_signature(content: &str) -> bool {
+    let content = content.trim();
+    content.starts_with(PGP_SIGNATURE_BEGIN) && content.ends_with(PGP_SIGNATURE_END)
+}
+
+// a signature outside the configured bounds is treated as missing so the
+// platform counts as incomplete
+fn is_signature_length_valid(config: &Config, path: &Path, content: &str) -> bool {
+    let length = content.len();
+    if length < config.signature_min_bytes || length > config.signature_max_bytes {
+        tracing::warn!(
+            "ignoring signature {} with {} bytes, expected between {} and {}",
+            path.display(),
+            length,
+            config.signature_min_bytes,
+            config.signature_max_bytes
+        );
+        return false;
+    }
+
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingPart {
+    Binary,
+    Signature,
+}
+
+pub struct PlatformInspection {
+    // None when the platform folder is missing or couldn't be read in time
+    pub files: Option<PlatformFiles>,
+    // signature content, only set when it passed validation
+    pub signature: Option<String>,
+    pub missing: Vec<MissingPart>,
+    // the version deliberately has no build for the platform (no folder), a
+    // folder that exists but lacks files is a broken build instead
+    pub absent: bool,
+}
+
+impl PlatformInspection {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    pub fn is_available(&self) -> bool {
+        !self.absent
+    }
+
+    pub fn pgp_signature(&self) -> Option<&str> {
+        self.files
+            .as_ref()
+            .and_then(|files| files.pgp_signature.as_deref())
+    }
+
+    pub fn artifact_size(&self) -> Option<u64> {
+        self.files.as_ref().and_then(|files| files.artifact_size)
+    }
+
+    pub fn artifact(&self) -> Option<&str> {
+        self.files
+            .as_ref()
+            .and_then(|files| files.artifact.as_deref())
+    }
+
+    pub fn describe(&self) -> String {
+        let missing: Vec<&str> = self
+            .missing
+            .iter()
+            .map(|part| match part {
+                MissingPart::Binary => "binary",
+                MissingPart::Signature => "signature",
+            })
+            .collect();
+
+        format!("missing {}", missing.join(" and "))
+    }
+}
+
+// works out whether a platform of a version is servable and if not, why
+pub async fn inspect_platform(
+    config: &Config,
+    version: &str,
+    platform_name: &str,
+) -> Result<PlatformInspection, APIError> {
+    let (files, absent) = match read_platform_state(config, version, platform_name).await? {
+        PlatformState::Files(files) => (Some(files), false),
+        PlatformState::Absent => (None, true),
+        PlatformState::Unavailable => (None, false),
+    };
+
+    let signature = files
+        .as_ref()
+        .and_then(|files| files.signature.as_ref())
+        .filter(|(path, content)| is_signature_length_valid(config, path, content))
+        .map(|(_, content)| content.clone());
+
+    let mut missing = Vec::new();
+    if files
+        .as_ref()
+        .and_then(|files| files.artifact.as_ref())
+        .is_none()
+    {
+        missing.push(MissingPart::Binary);
+    }
+    if signature.is_none() {
+        missing.push(MissingPart::Signature);
+    }
+
+    Ok(PlatformInspection {
+        files,
+        signature,
+        missing,
+        absent,
+    })
+}
+
+pub struct VersionInspection {
+    pub platforms: Vec<(String, PlatformInspection)>,
+    // newest file across all platforms, i.e. when the last artifact landed
+    pub modified: SystemTime,
+}
+
+impl VersionInspection {
+    // servable when every platform is complete or deliberately absent and at
+    // least one platform has a build
+    pub fn is_complete(&self) -> bool {
+        is_servable(self.platforms.iter().map(|(_, inspection)| inspection))
+    }
+}
+
+pub fn is_servable<'a>(platforms: impl Iterator<Item = &'a PlatformInspection> + Clone) -> bool {
+    platforms
+        .clone()
+        .all(|inspection| inspection.is_complete() || inspection.absent)
+        && platforms.into_iter().any(PlatformInspection::is_complete)
+}
+
+pub async fn inspect_version(
+    config: &Config,
+    version: &str,
+) -> Result<VersionInspection, APIError> {
+    let mut platforms = Vec::new();
+    let mut modified = SystemTime::UNIX_EPOCH;
+    for platform in &config.platforms {
+        let inspection = inspect_platform(config, version, platform).await?;
+        if let Some(files) = &inspection.files {
+            modified = modified.max(files.modified);
+        }
+        platforms.push((platform.clone(), inspection));
+    }
+
+    Ok(VersionInspection {
+        platforms,
+        modified,
+    })
+}
+
+// release notes written when the version was created, empty when there are none
+pub async fn read_notes(version_directory: &Path) -> Result<String, APIError> {
+    match read_text_file(&version_directory.join(NOTES_FILE)).await {
+        Ok(notes) => Ok(notes.unwrap_or_default()),
+        Err(e) => {
+            tracing::error!(
+                "failed to read notes of {}: {}",
+                version_directory.display(),
+                e
+            );
+            Err(e.into())
+        }
+    }
+}
+
+// None when the file is missing, or isn't a regular file like a notes.txt an
+// operator created as a directory by accident, which is warned about instead
+// of failing the whole manifest
+pub async fn read_text_file(path: &Path) -> std::io::Result<Option<String>> {
+    match fs::metadata(path).await {
+        Ok(metadata) if !metadata.is_file() => {
+            tracing::warn!(
+                "{} is not a regular file, treating it as missing",
+                path.display()
+            );
+            return Ok(None);
+        }
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    match fs::read_to_string(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// something other than a regular file sits at `path`
+pub async fn is_not_a_file(path: &Path) -> bool {
+    fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| !metadata.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        state::AppState,
+        test_support::{self, Kiosk},
+    };
+
+    #[tokio::test]
+    async fn finds_a_mis_cased_platform_folder() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_platform("1.0.0", "Windows_x86_64")
+            .add_platform("1.0.0", "linux_x86_64")
+            .add_platform("1.0.0", "darwin_x86_64")
+            .add_platform("1.0.0", "darwin_aarch64");
+        let config = kiosk.config(&[]);
+
+        let folder = find_platform_directory(&kiosk.path().join("1.0.0"), "windows_x86_64")
+            .await
+            .unwrap();
+        assert_eq!(folder, Some(kiosk.path().join("1.0.0/Windows_x86_64")));
+
+        let inspection = inspect_version(&config, "1.0.0").await.unwrap();
+        assert!(inspection.is_complete());
+        assert!(
+            inspection
+                .platforms
+                .iter()
+                .any(|(platform, inspection)| platform == "windows_x86_64"
+                    && inspection.is_complete())
+        );
+    }
+
+    async fn listed(kiosk: &Kiosk, vars: &[(&str, &str)]) -> Vec<String> {
+        list_versions(&kiosk.config(vars))
+            .await
+            .unwrap()
+            .versions
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn prerelease_order_policies() {
+        let kiosk = Kiosk::new();
+        for version in [
+            "1.1.0",
+            "1.2.0-beta.1",
+            "1.2.0",
+            "1.2.0-beta.2",
+            "1.3.0-rc.1",
+        ] {
+            kiosk.add_version(version);
+        }
+
+        assert_eq!(
+            listed(&kiosk, &[]).await,
+            [
+                "1.3.0-rc.1",
+                "1.2.0",
+                "1.2.0-beta.2",
+                "1.2.0-beta.1",
+                "1.1.0"
+            ]
+        );
+        // the betas of 1.2.0 outrank its release, but not the newer base
+        assert_eq!(
+            listed(&kiosk, &[("PRERELEASE_ORDER", "prerelease_first")]).await,
+            [
+                "1.3.0-rc.1",
+                "1.2.0-beta.2",
+                "1.2.0-beta.1",
+                "1.2.0",
+                "1.1.0"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn equal_versions_rank_by_folder_name() {
+        // created in both orders so the directory order can't decide
+        for folders in [["2024.1.2", "2024.01.02"], ["2024.01.02", "2024.1.2"]] {
+            let kiosk = Kiosk::new();
+            for folder in folders {
+                kiosk.add_version(folder);
+            }
+            let config = kiosk.config(&[("VERSION_SCHEME", "calver")]);
+            assert_eq!(
+                parse_version(&config, "2024.1.2"),
+                parse_version(&config, "2024.01.02")
+            );
+
+            assert_eq!(
+                listed(&kiosk, &[("VERSION_SCHEME", "calver")]).await,
+                ["2024.1.2", "2024.01.02"]
+            );
+            assert_eq!(
+                crate::resolve_latest_version(&config)
+                    .await
+                    .unwrap()
+                    .version,
+                "2024.1.2"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn calver_orders_by_date_then_build() {
+        let kiosk = Kiosk::new();
+        for version in [
+            "2024.07.01",
+            "2024.12.31",
+            "2024.07.01.2",
+            "2025.01.01",
+            "2024.13.01",
+            "1.2.0",
+        ] {
+            kiosk.add_version(version);
+        }
+        let vars = [("VERSION_SCHEME", "calver")];
+
+        assert_eq!(
+            listed(&kiosk, &vars).await,
+            ["2025.01.01", "2024.12.31", "2024.07.01.2", "2024.07.01"]
+        );
+        let folders = list_versions(&kiosk.config(&vars)).await.unwrap();
+        let ignored: Vec<&str> = folders
+            .ignored
+            .iter()
+            .map(|folder| folder.name.as_str())
+            .collect();
+        assert_eq!(ignored, ["1.2.0", "2024.13.01"]);
+        assert_eq!(
+            crate::resolve_latest_version(&kiosk.config(&vars))
+                .await
+                .unwrap()
+                .version,
+            "2025.01.01"
+        );
+        // under semver the zero-padded dates aren't versions at all
+        assert_eq!(listed(&kiosk, &[]).await, ["2024.12.31", "1.2.0"]);
+    }
+
+    #[tokio::test]
+    async fn lexical_orders_by_folder_name() {
+        let kiosk = Kiosk::new();
+        for version in ["release-a", "1.10.0", "release-b", "1.9.0", "2024.07.01"] {
+            kiosk.add_version(version);
+        }
+        std::fs::create_dir(kiosk.path().join(".staging")).unwrap();
+        let vars = [("VERSION_SCHEME", "lexical")];
+
+        assert_eq!(
+            listed(&kiosk, &vars).await,
+            ["release-b", "release-a", "2024.07.01", "1.9.0", "1.10.0"]
+        );
+        assert_eq!(
+            crate::resolve_latest_version(&kiosk.config(&vars))
+                .await
+                .unwrap()
+                .version,
+            "release-b"
+        );
+    }
+
+    #[test]
+    fn a_version_without_platforms_is_incomplete() {
+        assert!(!is_servable(std::iter::empty()));
+    }
+
+    #[tokio::test]
+    async fn a_notes_directory_reads_as_empty_notes() {
+        let kiosk = Kiosk::new();
+        for platform in crate::test_support::PLATFORMS {
+            kiosk.add_platform("1.0.0", platform);
+        }
+        let notes = kiosk.path().join("1.0.0").join(NOTES_FILE);
+        std::fs::create_dir(&notes).unwrap();
+
+        assert!(is_not_a_file(&notes).await);
+        assert_eq!(read_text_file(&notes).await.unwrap(), None);
+        assert_eq!(read_notes(&kiosk.path().join("1.0.0")).await.unwrap(), "");
+
+        let manifest = crate::resolve_latest_version(&kiosk.config(&[]))
+            .await
+            .unwrap();
+        assert_eq!(manifest.version, "1.0.0");
+        assert_eq!(manifest.notes, "");
+    }
+
+    #[tokio::test]
+    async fn only_an_existing_non_file_is_not_a_file() {
+        let kiosk = Kiosk::new();
+        kiosk.write("notes.txt", "notes");
+
+        assert!(!is_not_a_file(&kiosk.path().join("notes.txt")).await);
+        assert!(!is_not_a_file(&kiosk.path().join("missing.txt")).await);
+    }
+
+    #[tokio::test]
+    async fn a_signature_outside_the_bounds_counts_as_missing() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let signature = "1.0.0/linux_x86_64/app-linux_x86_64.tar.gz.sig";
+        let config = kiosk.config(&[("SIGNATURE_MIN_BYTES", "10"), ("SIGNATURE_MAX_BYTES", "20")]);
+
+        for (content, valid) in [
+            ("too short", false),
+            ("ten bytes!", true),
+            ("twenty bytes exactly", true),
+            ("twenty one bytes long", false),
+        ] {
+            kiosk.write(signature, content);
+
+            let inspection = inspect_platform(&config, "1.0.0", "linux_x86_64")
+                .await
+                .unwrap();
+            assert_eq!(inspection.is_complete(), valid, "{}", content);
+            if !valid {
+                assert_eq!(inspection.missing, [MissingPart::Signature]);
+                assert_eq!(inspection.describe(), "missing signature");
+                assert!(!inspect_version(&config, "1.0.0")
+                    .await
+                    .unwrap()
+                    .is_complete());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn the_default_bounds_refuse_a_truncated_signature() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.write("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz.sig", "RUT");
+        let config = kiosk.config(&[]);
+
+        let inspection = inspect_platform(&config, "1.0.0", "linux_x86_64")
+            .await
+            .unwrap();
+
+        assert_eq!(inspection.missing, [MissingPart::Signature]);
+        assert_eq!(inspection.signature, None);
+    }
+
+    #[tokio::test]
+    async fn a_read_slower_than_the_timeout_times_out() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("FS_OP_TIMEOUT_MS", "20")]);
+        assert_eq!(config.fs_op_timeout, Duration::from_millis(20));
+
+        let stuck = async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        };
+        let started = std::time::Instant::now();
+        let result = timed(config.fs_op_timeout, stuck).await;
+
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let quick = timed(config.fs_op_timeout, async { Ok(7) }).await;
+        assert_eq!(quick.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn only_picks_an_allowed_extension_as_the_artifact() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .write("1.0.0/linux_x86_64/.DS_Store", "finder")
+            .write("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz~", "backup")
+            .write("1.0.0/linux_x86_64/README", "readme");
+        std::fs::remove_file(
+            kiosk
+                .path()
+                .join("1.0.0/windows_x86_64/app-windows_x86_64.tar.gz"),
+        )
+        .unwrap();
+        kiosk.write("1.0.0/windows_x86_64/app-windows_x86_64.txt", "notes");
+        let config = kiosk.config(&[]);
+
+        let linux = inspect_platform(&config, "1.0.0", "linux_x86_64")
+            .await
+            .unwrap();
+        assert_eq!(linux.artifact(), Some("app-linux_x86_64.tar.gz"));
+        assert!(linux.is_complete());
+
+        let windows = inspect_platform(&config, "1.0.0", "windows_x86_64")
+            .await
+            .unwrap();
+        assert_eq!(windows.artifact(), None);
+        assert_eq!(windows.missing, [MissingPart::Binary]);
+
+        let zip_only = kiosk.config(&[("ARTIFACT_EXTENSIONS", ".zip, .msi")]);
+        let linux = inspect_platform(&zip_only, "1.0.0", "linux_x86_64")
+            .await
+            .unwrap();
+        assert_eq!(linux.artifact(), None);
+    }
+
+    #[tokio::test]
+    async fn lists_symlinked_versions_by_mode() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let outside = Kiosk::new();
+        outside.add_version("2.0.0");
+        std::os::unix::fs::symlink(kiosk.path().join("1.0.0"), kiosk.path().join("stable"))
+            .unwrap();
+        // a version linked in from elsewhere is followed, it's no alias
+        std::os::unix::fs::symlink(outside.path().join("2.0.0"), kiosk.path().join("2.0.0"))
+            .unwrap();
+
+        for (mode, listed, latest) in [
+            ("alias", vec!["2.0.0", "1.0.0"], "2.0.0"),
+            ("ignore", vec!["1.0.0"], "1.0.0"),
+        ] {
+            let config = kiosk.config(&[("SYMLINKED_VERSIONS", mode)]);
+
+            let folders = list_versions(&config).await.unwrap();
+            let names: Vec<&str> = folders
+                .versions
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect();
+            assert_eq!(names, listed, "{}", mode);
+            assert!(folders.ignored.is_empty(), "{}", mode);
+
+            let state = AppState::build(config).await.unwrap();
+            let served =
+                test_support::json(test_support::get(&state, "/latest-version").await).await;
+            assert_eq!(served["version"], latest, "{}", mode);
+        }
+
+        let config = kiosk.config(&[]);
+        assert_eq!(
+            resolve_alias(&config, "stable").await.as_deref(),
+            Some("1.0.0")
+        );
+        assert_eq!(resolve_alias(&config, "2.0.0").await, None);
+        let config = kiosk.config(&[("SYMLINKED_VERSIONS", "ignore")]);
+        assert_eq!(resolve_alias(&config, "stable").await, None);
+    }
+}
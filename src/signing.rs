@@ -0,0 +1,145 @@
+use std::time::{Duration, SystemTime};
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::config::Config;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// query parameters appended to a download url when SIGNED_URLS is enabled
+#[derive(Deserialize)]
+pub struct SignedQuery {
+    pub expires: Option<u64>,
+    pub signature: Option<String>,
+}
+
+// `?expires=<unix secs>&signature=<hex hmac>` for the artifact, the signature
+// covers the path and the expiry so neither can be changed, empty when
+// SIGNED_URLS is disabled
+pub fn signed_query(config: &Config, version: &str, platform: &str, filename: &str) -> String {
+    if !config.signed_urls {
+        return String::new();
+    }
+
+    let expires = unix_now().saturating_add(config.signed_url_ttl.as_secs());
+
+    format!(
+        "?expires={}&signature={}",
+        expires,
+        hex::encode(
+            mac(config, version, platform, filename, expires)
+                .finalize()
+                .into_bytes()
+        )
+    )
+}
+
+// false for an expired url, a missing parameter or a signature that doesn't
+// match, the comparison is constant time
+pub fn is_valid(
+    config: &Config,
+    version: &str,
+    platform: &str,
+    filename: &str,
+    query: &SignedQuery,
+) -> bool {
+    let (Some(expires), Some(signature)) = (query.expires, &query.signature) else {
+        return false;
+    };
+    if expires < unix_now() {
+        return false;
+    }
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+
+    mac(config, version, platform, filename, expires)
+        .verify_slice(&signature)
+        .is_ok()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+fn mac(config: &Config, version: &str, platform: &str, filename: &str, expires: u64) -> HmacSha256 {
+    let secret = config.url_signing_secret.as_deref().unwrap_or_default();
+    // hmac accepts keys of any length
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}/{}/{}:{}", version, platform, filename, expires).as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    fn parse(query: &str) -> SignedQuery {
+        let (expires, signature) = query
+            .strip_prefix("?expires=")
+            .unwrap()
+            .split_once("&signature=")
+            .unwrap();
+        SignedQuery {
+            expires: Some(expires.parse().unwrap()),
+            signature: Some(signature.to_string()),
+        }
+    }
+
+    #[test]
+    fn accepts_its_own_signature_only_for_the_same_file() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("SIGNED_URLS", "true"), ("URL_SIGNING_SECRET", "secret")]);
+
+        let query = parse(&signed_query(
+            &config,
+            "1.0.0",
+            "linux_x86_64",
+            "app.tar.gz",
+        ));
+
+        assert!(is_valid(
+            &config,
+            "1.0.0",
+            "linux_x86_64",
+            "app.tar.gz",
+            &query
+        ));
+        assert!(!is_valid(
+            &config,
+            "1.0.1",
+            "linux_x86_64",
+            "app.tar.gz",
+            &query
+        ));
+    }
+
+    #[test]
+    fn a_huge_ttl_does_not_overflow() {
+        let kiosk = Kiosk::new();
+        let mut config = kiosk.config(&[("SIGNED_URLS", "true"), ("URL_SIGNING_SECRET", "secret")]);
+        config.signed_url_ttl = Duration::MAX;
+
+        let query = parse(&signed_query(
+            &config,
+            "1.0.0",
+            "linux_x86_64",
+            "app.tar.gz",
+        ));
+
+        assert_eq!(query.expires, Some(u64::MAX));
+        assert!(is_valid(
+            &config,
+            "1.0.0",
+            "linux_x86_64",
+            "app.tar.gz",
+            &query
+        ));
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
+use tokio::sync::{broadcast, OwnedMutexGuard};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    cache::{LatestVersionCache, ManifestKey, StatsCache},
+    checksum::HashCache,
+    config::Config,
+    error::APIError,
+    events::{self, DownloadEvent},
+    publish,
+    publish_queue::PublishQueue,
+    resolve_latest_version,
+    rollout::RolloutLog,
+    scanner,
+    webhook::{PublishWebhook, PublishedVersion},
+};
+
+pub struct AppState {
+    pub config: Config,
+    pub db: Option<DatabaseConnection>,
+    pub latest_version_cache: LatestVersionCache,
+    pub stats_cache: StatsCache,
+    // set by the first successful warmup, stays set when the entry expires
+    pub cache_warmed: AtomicBool,
+    pub hash_cache: HashCache,
+    // downloads served per version since the process started
+    pub download_counts: Mutex<HashMap<String, u64>>,
+    // downloads whose body is still being streamed to the client
+    pub downloads_in_flight: Arc<AtomicUsize>,
+    // download starts and finishes for /admin/downloads/stream
+    pub download_events: broadcast::Sender<DownloadEvent>,
+    // update checks of clients sending X-Client-Id
+    pub rollout_log: RolloutLog,
+    // artifacts the integrity check found changed since their hash was stored
+    pub corruption_detected: AtomicU64,
+    // only set when PUBLISH_QUEUE_CAPACITY is configured
+    pub publish_queue: Option<PublishQueue>,
+    pub publish_webhook: Option<PublishWebhook>,
+    // serializes writes to the same version, different versions don't wait
+    // on each other
+    version_locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    pub started_at: Instant,
+    // cancelled on SIGTERM so endless responses like the download event
+    // stream end and don't hold up the graceful shutdown
+    pub shutdown: CancellationToken,
+}
+
+// decrements the in-flight counter when dropped, i.e. once the response body
+// finished streaming or the client went away
+pub struct InFlightDownload {
+    counter: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlightDownload {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl AppState {
+    pub async fn build(config: Config) -> Result<Arc<Self>, DbErr> {
+        // connect lazily so an unreachable database shows up in /ready instead
+        // of keeping the server from starting
+        let db = match &config.database_url {
+            Some(url) => {
+                let mut options = ConnectOptions::new(url);
+                options.connect_lazy(true);
+                Some(Database::connect(options).await?)
+            }
+            None => None,
+        };
+
+        let state = Arc::new(Self {
+            latest_version_cache: LatestVersionCache::new(config.cache_ttl, config.cache_capacity),
+            stats_cache: StatsCache::new(config.stats_cache_ttl),
+            cache_warmed: AtomicBool::new(false),
+            hash_cache: HashCache::new(config.hash_cache_capacity),
+            download_counts: Mutex::new(HashMap::new()),
+            downloads_in_flight: Arc::new(AtomicUsize::new(0)),
+            download_events: broadcast::channel(events::CAPACITY).0,
+            rollout_log: RolloutLog::default(),
+            corruption_detected: AtomicU64::new(0),
+            publish_queue: config.publish_queue_capacity.map(PublishQueue::start),
+            publish_webhook: PublishWebhook::from_config(&config),
+            version_locks: Mutex::new(HashMap::new()),
+            started_at: Instant::now(),
+            shutdown: CancellationToken::new(),
+            db,
+            config,
+        });
+
+        if state.config.warm_cache_on_start {
+            state.warm_latest_version_cache().await;
+        }
+
+        Ok(state)
+    }
+
+    pub fn record_download(&self, version: &str) {
+        let mut download_counts = self.download_counts.lock().unwrap();
+        *download_counts.entry(version.to_string()).or_insert(0) += 1;
+    }
+
+    // filesystem heavy publish work goes through the queue when one is
+    // configured, otherwise it runs right away
+    pub async fn publish<T: Send + 'static>(
+        &self,
+        operation: impl Future<Output = Result<T, APIError>> + Send + 'static,
+    ) -> Result<T, APIError> {
+        match &self.publish_queue {
+            Some(queue) => queue.run(operation).await,
+            None => operation.await,
+        }
+    }
+
+    pub fn notify_published(&self, version: &str, notes: &str) {
+        if let Some(webhook) = &self.publish_webhook {
+            webhook.notify(PublishedVersion::new(
+                version,
+                &publish::notes_or_default(&self.config, version, notes),
+                &self.config.platforms,
+            ));
+        }
+    }
+
+    // held while creating a version or writing one of its uploads
+    pub async fn lock_version(&self, version: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.version_locks.lock().unwrap();
+            // drop locks nobody holds or waits for so the map doesn't grow
+            // with every version ever touched
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(version.to_string()).or_default().clone()
+        };
+
+        lock.lock_owned().await
+    }
+
+    pub fn start_download(&self) -> InFlightDownload {
+        self.downloads_in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightDownload {
+            counter: self.downloads_in_flight.clone(),
+        }
+    }
+
+    pub fn downloads_in_flight(&self) -> usize {
+        self.downloads_in_flight.load(Ordering::SeqCst)
+    }
+
+    // returns false when downloads were still streaming after `timeout`
+    pub async fn wait_for_downloads(&self, timeout: Duration) -> bool {
+        let started = Instant::now();
+        while self.downloads_in_flight() > 0 {
+            if started.elapsed() >= timeout {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        true
+    }
+
+    // hashes the artifacts of the warmed version before clients ask for them
+    async fn warm_hash_cache(&self, version: &str) {
+        for platform in &self.config.platforms {
+            let Ok(Some(files)) = scanner::read_platform(&self.config, version, platform).await
+            else {
+                continue;
+            };
+            let Some(artifact) = files.artifact else {
+                continue;
+            };
+            let path = files.folder.join(artifact);
+            if let Err(e) = self.hash_cache.content_md5(&path).await {
+                tracing::warn!("failed to warm the hash of {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    // computes the latest version once so the first request after a restart
+    // doesn't pay the full directory scan
+    pub async fn warm_latest_version_cache(&self) {
+        match resolve_latest_version(&self.config).await {
+            Ok(response) => {
+                tracing::info!("warmed latest version cache with {}", response.version);
+                if self.config.emit_content_md5 {
+                    self.warm_hash_cache(&response.version).await;
+                }
+                self.latest_version_cache
+                    .set(ManifestKey::default(), response)
+                    .await;
+                self.cache_warmed.store(true, Ordering::SeqCst);
+            }
+            Err(_) => {
+                tracing::warn!("failed to warm latest version cache, no complete version found");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, Kiosk};
+
+    #[tokio::test]
+    async fn warms_the_latest_version_on_build() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+
+        let state = AppState::build(kiosk.config(&[("WARM_CACHE_ON_START", "true")]))
+            .await
+            .unwrap();
+
+        let cached = state
+            .latest_version_cache
+            .get(&ManifestKey::default())
+            .await
+            .unwrap();
+        assert_eq!(cached.version, "1.1.0");
+    }
+
+    #[tokio::test]
+    async fn leaves_the_cache_cold_without_warmup() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        assert!(
+            !state
+                .latest_version_cache
+                .is_populated(&ManifestKey::default())
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn warms_the_hashes_of_the_latest_version() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+
+        let state = AppState::build(kiosk.config(&[
+            ("WARM_CACHE_ON_START", "true"),
+            ("EMIT_CONTENT_MD5", "true"),
+        ]))
+        .await
+        .unwrap();
+
+        let hashed = |version: &str| {
+            kiosk
+                .path()
+                .join(version)
+                .join("linux_x86_64/app-linux_x86_64.tar.gz")
+        };
+        assert!(state
+            .hash_cache
+            .peek_content_md5(&hashed("1.1.0"))
+            .await
+            .unwrap()
+            .is_some());
+        assert!(state
+            .hash_cache
+            .peek_content_md5(&hashed("1.0.0"))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_a_streaming_download() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let response = test_support::download(
+            &state,
+            axum::http::Method::GET,
+            "linux_x86_64",
+            "1.0.0",
+            "app-linux_x86_64.tar.gz",
+        )
+        .await
+        .unwrap();
+        assert_eq!(state.downloads_in_flight(), 1);
+
+        let waiting = tokio::spawn({
+            let state = state.clone();
+            async move { state.wait_for_downloads(Duration::from_secs(10)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(!waiting.is_finished());
+
+        assert_eq!(test_support::body(response).await, "bin 1.0.0 linux_x86_64");
+        assert!(waiting.await.unwrap());
+        assert_eq!(state.downloads_in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn shutdown_gives_up_after_the_timeout() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let in_flight = state.start_download();
+        assert!(!state.wait_for_downloads(Duration::from_millis(150)).await);
+
+        drop(in_flight);
+        assert!(state.wait_for_downloads(Duration::from_millis(150)).await);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{extract::State, Json};
+use serde::Serialize;
+
+use crate::{
+    cache::ManifestKey, error::APIError, resolve_latest_version, scanner, state::AppState,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsResponse {
+    pub total_versions: usize,
+    // size of every file inside the platform folders, signatures included
+    pub total_artifact_bytes: u64,
+    pub downloads: BTreeMap<String, u64>,
+    pub latest_version: Option<String>,
+}
+
+pub async fn get_stats(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<StatsResponse>, APIError> {
+    if let Some(stats) = state.stats_cache.get().await {
+        return Ok(Json(stats));
+    }
+
+    let stats = compute_stats(&state).await?;
+    state.stats_cache.set(stats.clone()).await;
+
+    Ok(Json(stats))
+}
+
+async fn compute_stats(state: &AppState) -> Result<StatsResponse, APIError> {
+    let folders = scanner::list_versions(&state.config).await?;
+
+    let mut total_artifact_bytes = 0;
+    for (_, name) in folders.versions.iter() {
+        for platform in &state.config.platforms {
+            if let Some(folder) =
+                scanner::locate_platform_directory(&state.config, name, platform).await?
+            {
+                total_artifact_bytes += scanner::directory_size(&folder).await?;
+            }
+        }
+    }
+
+    let latest_version = match state
+        .latest_version_cache
+        .get(&ManifestKey::default())
+        .await
+    {
+        Some(response) => Some(response.version),
+        None => resolve_latest_version(&state.config)
+            .await
+            .ok()
+            .map(|response| response.version),
+    };
+
+    let downloads = state
+        .download_counts
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(version, count)| (version.clone(), *count))
+        .collect();
+
+    Ok(StatsResponse {
+        total_versions: folders.versions.len(),
+        total_artifact_bytes,
+        downloads,
+        latest_version,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+
+    use super::*;
+    use crate::test_support::{self, Kiosk, PLATFORMS, SIGNATURE};
+
+    #[tokio::test]
+    async fn sums_up_versions_sizes_and_downloads() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        // listed and counted, but without a signature it is never the latest
+        kiosk.write("1.2.0/linux_x86_64/app.tar.gz", "bin 1.2.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for (version, times) in [("1.0.0", 1), ("1.1.0", 3)] {
+            for _ in 0..times {
+                test_support::download(
+                    &state,
+                    Method::GET,
+                    "windows_x86_64",
+                    version,
+                    "app-windows_x86_64.tar.gz",
+                )
+                .await
+                .unwrap();
+            }
+        }
+
+        let Json(stats) = get_stats(State(state.clone())).await.unwrap();
+
+        let version_bytes = |version: &str| -> u64 {
+            PLATFORMS
+                .iter()
+                .map(|platform| {
+                    (format!("bin {} {}", version, platform).len() + SIGNATURE.len()) as u64
+                })
+                .sum()
+        };
+        assert_eq!(stats.total_versions, 3);
+        assert_eq!(
+            stats.total_artifact_bytes,
+            version_bytes("1.0.0") + version_bytes("1.1.0") + "bin 1.2.0".len() as u64
+        );
+        assert_eq!(
+            stats.downloads,
+            BTreeMap::from([("1.0.0".to_string(), 1), ("1.1.0".to_string(), 3)])
+        );
+        assert_eq!(stats.latest_version.as_deref(), Some("1.1.0"));
+    }
+
+    #[tokio::test]
+    async fn has_no_latest_version_in_an_empty_kiosk() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let Json(stats) = get_stats(State(state)).await.unwrap();
+
+        assert_eq!(stats.total_versions, 0);
+        assert_eq!(stats.total_artifact_bytes, 0);
+        assert!(stats.downloads.is_empty());
+        assert_eq!(stats.latest_version, None);
+    }
+}
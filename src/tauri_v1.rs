@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    Json,
+};
+use indexmap::IndexMap;
+use sea_orm::sqlx::types::chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{error::APIError, state::AppState, KioskVersionResponse, LatestVersionQuery};
+
+// the tauri v1 updater shape, unlike v2 a platform only carries `signature`
+// and `url` (no `name`) and `pub_date` is rfc3339 in utc with whole seconds
+#[derive(Debug, Serialize)]
+pub struct TauriV1Response {
+    pub version: String,
+    pub notes: String,
+    pub pub_date: String,
+    pub platforms: IndexMap<String, TauriV1Platform>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TauriV1Platform {
+    pub signature: String,
+    pub url: String,
+}
+
+impl From<KioskVersionResponse> for TauriV1Response {
+    fn from(response: KioskVersionResponse) -> Self {
+        let pub_date = match DateTime::parse_from_rfc3339(&response.pub_date) {
+            Ok(pub_date) => pub_date
+                .with_timezone(&Utc)
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string(),
+            Err(_) => response.pub_date,
+        };
+
+        Self {
+            platforms: response
+                .platforms
+                .iter()
+                .map(|(target, details)| {
+                    (
+                        target.clone(),
+                        TauriV1Platform {
+                            signature: details.signature.clone(),
+                            url: details.url.clone(),
+                        },
+                    )
+                })
+                .collect(),
+            version: response.version,
+            notes: response.notes,
+            pub_date,
+        }
+    }
+}
+
+// same version resolution as /latest-version, only the response shape differs
+pub async fn get_latest_version(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LatestVersionQuery>,
+    headers: HeaderMap,
+) -> Result<Json<TauriV1Response>, APIError> {
+    let response = crate::latest_manifest(&state, &query, &headers).await?;
+
+    Ok(Json(response.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    // the files pin the field names, order and formats clients parse
+    const GOLDEN_V2: &str = include_str!("../testdata/latest_version_v2.json");
+    const GOLDEN_V1: &str = include_str!("../testdata/latest_version_v1.json");
+
+    async fn manifest() -> KioskVersionResponse {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let query = serde_json::from_value(serde_json::json!({})).unwrap();
+
+        let mut response = crate::latest_manifest(&state, &query, &HeaderMap::new())
+            .await
+            .unwrap();
+        // the only value that depends on when the files were written
+        response.pub_date = "2026-01-02T03:04:05.678901+07:00".to_string();
+        response
+    }
+
+    #[tokio::test]
+    async fn v2_shape_matches_the_golden_file() {
+        let actual = serde_json::to_string_pretty(&manifest().await).unwrap();
+
+        assert_eq!(actual, GOLDEN_V2.trim_end());
+    }
+
+    #[tokio::test]
+    async fn v1_shape_matches_the_golden_file() {
+        let actual =
+            serde_json::to_string_pretty(&TauriV1Response::from(manifest().await)).unwrap();
+
+        assert_eq!(actual, GOLDEN_V1.trim_end());
+    }
+}
@@ -0,0 +1,136 @@
+use std::{collections::HashMap, net::SocketAddr, path::Path, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path as PathParams, Query, State},
+    http::{Method, Request},
+    response::Response,
+};
+use tempfile::TempDir;
+use tower::ServiceExt;
+
+use crate::{
+    config::{Config, ConfigSources},
+    error::APIError,
+    signing,
+    state::AppState,
+};
+
+pub mod minisign;
+
+// long enough to pass the default signature length check
+pub const SIGNATURE: &str = concat!(
+    "untrusted comment: signature from tauri secret key\n",
+    "RUTx2d6kW3S8d7Hu0Mxa7ZHB1WzeNX0kJekRGyDwlAPbiSWbjm2YpDECe1fx3WfYL1t+",
+    "oWMssubexsUwk3h3kUKyNk8V35ylrAg=\n",
+    "trusted comment: timestamp:1700000000\tfile:app.tar.gz\n",
+    "5xoH9c0HOnUqAaZMRAAe2gEH3XFx3Hvp0nYCZBp8QqP0ZfTQueyEn2vlsy2dl+stm5h3w5dZxTfj3ecoJZpqDQ==\n",
+);
+
+pub const PLATFORMS: [&str; 4] = [
+    "linux_x86_64",
+    "windows_x86_64",
+    "darwin_x86_64",
+    "darwin_aarch64",
+];
+
+// a kiosk directory that is removed again when the test ends
+pub struct Kiosk {
+    dir: TempDir,
+}
+
+impl Kiosk {
+    pub fn new() -> Self {
+        Self {
+            dir: TempDir::new().unwrap(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    // a version with a signed artifact for every default platform
+    pub fn add_version(&self, version: &str) -> &Self {
+        for platform in PLATFORMS {
+            self.add_platform(version, platform);
+        }
+        self.write(
+            &format!("{}/notes.txt", version),
+            &format!("notes {}", version),
+        );
+        self
+    }
+
+    pub fn add_platform(&self, version: &str, platform: &str) -> &Self {
+        let artifact = format!("{}/{}/app-{}.tar.gz", version, platform, platform);
+        self.write(&artifact, &format!("bin {} {}", version, platform));
+        self.write(&format!("{}.sig", artifact), SIGNATURE);
+        self
+    }
+
+    pub fn write(&self, relative: &str, content: &str) -> &Self {
+        let path = self.path().join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+        self
+    }
+
+    // settings given as their environment variable, everything else default
+    pub fn config(&self, vars: &[(&str, &str)]) -> Config {
+        let mut env: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        env.entry("KIOSK_DIRECTORY".to_string())
+            .or_insert_with(|| self.path().to_str().unwrap().to_string());
+
+        let lookup = |key: &str| env.get(key).cloned();
+        Config::from_sources(&ConfigSources {
+            file: toml::Table::new(),
+            env: &lookup,
+        })
+        .unwrap()
+    }
+}
+
+pub async fn body(response: Response) -> String {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+pub async fn json(response: Response) -> serde_json::Value {
+    serde_json::from_str(&body(response).await).unwrap()
+}
+
+// a request through every route and middleware
+pub async fn send(state: &Arc<AppState>, request: Request<Body>) -> Response {
+    crate::app(state).oneshot(request).await.unwrap()
+}
+
+pub async fn get(state: &Arc<AppState>, uri: &str) -> Response {
+    send(state, Request::get(uri).body(Body::empty()).unwrap()).await
+}
+
+// straight to the download handler, unsigned and from localhost
+pub async fn download(
+    state: &Arc<AppState>,
+    method: Method,
+    platform: &str,
+    version: &str,
+    filename: &str,
+) -> Result<Response, APIError> {
+    crate::download_file(
+        State(state.clone()),
+        PathParams((version.into(), platform.into(), filename.into())),
+        Query(signing::SignedQuery {
+            expires: None,
+            signature: None,
+        }),
+        ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))),
+        method,
+    )
+    .await
+}
@@ -0,0 +1,178 @@
+// signs like `tauri signer sign`, so tests can check real signatures without
+// the minisign cli. the prehashed (`ED`) mode hashes with BLAKE2b-512, which
+// minisign-verify keeps private, so a small implementation lives here
+
+use base64::Engine;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+
+pub struct SigningKey {
+    key_pair: Ed25519KeyPair,
+    key_id: [u8; 8],
+}
+
+impl SigningKey {
+    // the same seed always gives the same key, `seed` tells keys apart
+    pub fn new(seed: u8) -> Self {
+        Self {
+            key_pair: Ed25519KeyPair::from_seed_unchecked(&[seed; 32]).unwrap(),
+            key_id: [seed; 8],
+        }
+    }
+
+    // TAURI_PUBLIC_KEY as `tauri signer generate` prints it
+    pub fn tauri_public_key(&self) -> String {
+        let mut key = b"Ed".to_vec();
+        key.extend_from_slice(&self.key_id);
+        key.extend_from_slice(self.key_pair.public_key().as_ref());
+        let file = format!("untrusted comment: minisign public key\n{}\n", base64(&key));
+        base64(file.as_bytes())
+    }
+
+    // the minisign signature file of `data`
+    pub fn sign(&self, data: &[u8]) -> String {
+        let mut signature = b"ED".to_vec();
+        signature.extend_from_slice(&self.key_id);
+        let prehashed = self.key_pair.sign(&blake2b_512(data));
+        signature.extend_from_slice(prehashed.as_ref());
+
+        let trusted_comment = "timestamp:1700000000\tfile:app.tar.gz";
+        let mut global = prehashed.as_ref().to_vec();
+        global.extend_from_slice(trusted_comment.as_bytes());
+
+        format!(
+            "untrusted comment: signature from tauri secret key\n{}\ntrusted comment: {}\n{}\n",
+            base64(&signature),
+            trusted_comment,
+            base64(self.key_pair.sign(&global).as_ref())
+        )
+    }
+
+    // the signature base64 encoded like the `.sig` files of the tauri bundler
+    pub fn tauri_signature(&self, data: &[u8]) -> String {
+        base64(self.sign(data).as_bytes())
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+// unkeyed BLAKE2b with a 64 byte digest (RFC 7693)
+fn blake2b_512(data: &[u8]) -> [u8; 64] {
+    let mut h = IV;
+    h[0] ^= 0x0101_0040;
+
+    let blocks = data.len().div_ceil(128).max(1);
+    for index in 0..blocks {
+        let start = index * 128;
+        let end = data.len().min(start + 128);
+        let mut block = [0u8; 128];
+        block[..end - start].copy_from_slice(&data[start..end]);
+        compress(&mut h, &block, end as u128, index == blocks - 1);
+    }
+
+    let mut digest = [0u8; 64];
+    for (chunk, word) in digest.chunks_exact_mut(8).zip(h) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+fn compress(h: &mut [u64; 8], block: &[u8; 128], counter: u128, last: bool) {
+    let mut m = [0u64; 16];
+    for (word, bytes) in m.iter_mut().zip(block.chunks_exact(8)) {
+        *word = u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(h);
+    v[8..].copy_from_slice(&IV);
+    v[12] ^= counter as u64;
+    v[13] ^= (counter >> 64) as u64;
+    if last {
+        v[14] = !v[14];
+    }
+
+    for round in 0..12 {
+        let s = &SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+}
+
+fn mix(v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64) {
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+    v[d] = (v[d] ^ v[a]).rotate_right(32);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(24);
+    v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+    v[d] = (v[d] ^ v[a]).rotate_right(16);
+    v[c] = v[c].wrapping_add(v[d]);
+    v[b] = (v[b] ^ v[c]).rotate_right(63);
+}
+
+#[cfg(test)]
+mod tests {
+    use minisign_verify::Signature;
+
+    use super::*;
+    use crate::validate::decode_public_key;
+
+    #[test]
+    fn hashes_the_rfc_7693_example() {
+        assert_eq!(
+            hex::encode(blake2b_512(b"abc")),
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+        );
+    }
+
+    #[test]
+    fn signatures_verify_with_minisign() {
+        let key = SigningKey::new(1);
+        let data = vec![7u8; 300];
+
+        let public_key = decode_public_key(&key.tauri_public_key()).unwrap();
+        let signature = Signature::decode(&key.sign(&data)).unwrap();
+        let mut verifier = public_key.verify_stream(&signature).unwrap();
+        verifier.update(&data);
+        assert!(verifier.finalize().is_ok());
+
+        let other = decode_public_key(&SigningKey::new(2).tauri_public_key()).unwrap();
+        assert!(other.verify_stream(&signature).is_err());
+    }
+}
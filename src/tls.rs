@@ -0,0 +1,77 @@
+use axum_server::tls_rustls::RustlsConfig;
+
+use crate::config::Config;
+
+// loads TLS_CERT and TLS_KEY (pem) so a bad certificate stops the server at
+// startup, None keeps the plain http listener
+pub async fn load(config: &Config) -> Result<Option<RustlsConfig>, String> {
+    let (Some(cert), Some(key)) = (&config.tls_cert, &config.tls_key) else {
+        return Ok(None);
+    };
+
+    // the same ring provider reqwest uses, installed before any server config
+    // is built
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    // offers h2 and http/1.1 over alpn
+    RustlsConfig::from_pem_file(cert, key)
+        .await
+        .map(Some)
+        .map_err(|e| {
+            format!(
+                "failed to load TLS_CERT {} and TLS_KEY {}: {}",
+                cert, key, e
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    const CERT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tls_cert.pem");
+    const KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tls_key.pem");
+    // a valid key that doesn't belong to CERT
+    const OTHER_KEY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tls_other_key.pem");
+
+    #[tokio::test]
+    async fn loads_a_matching_pair() {
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("TLS_CERT", CERT), ("TLS_KEY", KEY)]);
+
+        let tls = load(&config).await.unwrap().unwrap();
+
+        let alpn = &tls.get_inner().alpn_protocols;
+        assert_eq!(alpn, &[b"h2".to_vec(), b"http/1.1".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn stays_plain_http_without_a_certificate() {
+        let kiosk = Kiosk::new();
+
+        assert!(load(&kiosk.config(&[])).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn refuses_a_bad_pair() {
+        let kiosk = Kiosk::new();
+        kiosk.write("garbage.pem", "not a pem file");
+        let garbage = kiosk.path().join("garbage.pem");
+        let garbage = garbage.to_str().unwrap();
+
+        for (cert, key) in [
+            (CERT, OTHER_KEY),
+            (CERT, garbage),
+            (garbage, KEY),
+            (CERT, "/nonexistent/key.pem"),
+        ] {
+            let config = kiosk.config(&[("TLS_CERT", cert), ("TLS_KEY", key)]);
+
+            let error = load(&config).await.err().unwrap_or_else(|| {
+                panic!("loaded TLS_CERT {} with TLS_KEY {}", cert, key);
+            });
+            assert!(error.contains(cert) && error.contains(key), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,610 @@
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    Json,
+};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    binary_format,
+    error::{APIError, UploadOffset},
+    platform::Platform,
+    scanner,
+    state::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct OverwriteQuery {
+    // replace an artifact that is already in the platform folder
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+// how many bytes of the upload already landed, an interrupted client asks this
+// before resuming
+pub async fn get_upload_offset(
+    State(state): State<Arc<AppState>>,
+    Path((version, platform, filename)): Path<(String, String, String)>,
+) -> Result<Json<UploadOffset>, APIError> {
+    let platform = upload_platform(&state, platform)?;
+    let part = part_path(&state, &version, &platform, &filename).await?;
+
+    Ok(Json(UploadOffset {
+        offset: part_length(&part).await?,
+    }))
+}
+
+// appends the body to the part file, `Content-Range: bytes <start>-<end>/<total>`
+// has to start at the current offset otherwise the chunk is rejected with 409.
+// Content-Length is required, 411 without it
+pub async fn upload_chunk(
+    State(state): State<Arc<AppState>>,
+    Path((version, platform, filename)): Path<(String, String, String)>,
+    Query(query): Query<OverwriteQuery>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<UploadOffset>, APIError> {
+    let platform = upload_platform(&state, platform)?;
+    let start = match headers.get(header::CONTENT_RANGE) {
+        Some(value) => parse_content_range_start(value.to_str()?)?,
+        None => 0,
+    };
+    // without it a chunk of any size would pass the free space check
+    let chunk_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .ok_or(APIError::LengthRequired)?;
+
+    let _lock = state.lock_version(&version).await;
+    let part = part_path(&state, &version, &platform, &filename).await?;
+    scanner::ensure_free_space(&state.config, chunk_length).await?;
+    let offset = part_length(&part).await?;
+    if start != offset {
+        tracing::warn!(
+            "upload of {} sent a chunk at {} but {} bytes are already stored",
+            part.display(),
+            start,
+            offset
+        );
+        return Err(APIError::UploadOffsetMismatch(offset));
+    }
+    // fail before the first byte is stored instead of after the whole upload
+    if offset == 0 && !query.overwrite {
+        if let Some(existing) = conflicting_file(&state, &part, &platform).await? {
+            return Err(APIError::ArtifactExists(existing));
+        }
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&part)
+        .await
+        .inspect_err(|e| tracing::error!("failed to open {}: {}", part.display(), e))?;
+
+    // whatever arrived before the client went away stays in the part file
+    let mut stream = body.into_data_stream();
+    let mut interrupted = false;
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => file.write_all(&chunk).await?,
+            Err(e) => {
+                tracing::warn!("upload of {} interrupted: {}", part.display(), e);
+                interrupted = true;
+                break;
+            }
+        }
+    }
+    file.flush().await?;
+    if state.config.fsync_uploads {
+        file.sync_all().await?;
+    }
+
+    let offset = part_length(&part).await?;
+    if interrupted {
+        return Err(APIError::UploadOffsetMismatch(offset));
+    }
+
+    Ok(Json(UploadOffset { offset }))
+}
+
+// renames the part file into place once every chunk is uploaded, until then
+// the scanner ignores it so a half uploaded artifact is never served. an
+// existing artifact is only replaced with `?overwrite=true`, the rename swaps
+// it atomically. the signatures of the replaced artifact are dropped, or
+// replaced by `<artifact>.sig.part` when its chunks were uploaded first, and a
+// differently named previous artifact is removed afterwards
+pub async fn complete_upload(
+    State(state): State<Arc<AppState>>,
+    Path((version, platform, filename)): Path<(String, String, String)>,
+    Query(query): Query<OverwriteQuery>,
+) -> Result<StatusCode, APIError> {
+    let platform = upload_platform(&state, platform)?;
+    let _lock = state.lock_version(&version).await;
+    let part = part_path(&state, &version, &platform, &filename).await?;
+    if !fs::try_exists(&part).await? {
+        return Err(APIError::NotFound);
+    }
+
+    let existing = conflicting_file(&state, &part, &platform).await?;
+    if let Some(existing) = &existing {
+        if !query.overwrite {
+            tracing::warn!(
+                "refused to replace {} of {} {} without overwrite",
+                existing,
+                version,
+                platform
+            );
+            return Err(APIError::ArtifactExists(existing.clone()));
+        }
+    }
+
+    if state.config.validate_binary_format
+        && platform != scanner::ATTACHMENTS
+        && scanner::is_artifact(&state.config, &filename)
+    {
+        // the part is useless once rejected, the retry starts from scratch
+        if let Err(e) = binary_format::check(&part, &filename, &platform).await {
+            fs::remove_file(&part).await?;
+            return Err(e);
+        }
+    }
+
+    let destination = part.with_extension("");
+    let fsync = state.config.fsync_uploads;
+    // signatures belong to the artifact they were made for, replacing an
+    // artifact never keeps the old ones
+    let replaces_artifact = existing.is_some()
+        && platform != scanner::ATTACHMENTS
+        && scanner::is_artifact(&state.config, &filename);
+    state
+        .publish(async move {
+            let folder = destination
+                .parent()
+                .unwrap_or(std::path::Path::new("."))
+                .to_path_buf();
+            if replaces_artifact {
+                let existing = existing.as_deref().unwrap_or(&filename);
+                for extension in SIGNATURE_EXTENSIONS {
+                    let signature = folder.join(format!("{}.{}", filename, extension));
+                    let pending = signature.with_extension(format!(
+                        "{}.{}",
+                        extension,
+                        scanner::PART_EXTENSION
+                    ));
+                    if existing != filename || !fs::try_exists(&pending).await? {
+                        remove_replaced(&folder.join(format!("{}.{}", existing, extension)))
+                            .await?;
+                    }
+                    // an uploaded but not yet completed signature goes in right
+                    // before the artifact it belongs to
+                    if fs::try_exists(&pending).await? {
+                        fs::rename(&pending, &signature).await.inspect_err(|e| {
+                            tracing::error!("failed to complete {}: {}", pending.display(), e)
+                        })?;
+                    }
+                }
+            }
+
+            fs::rename(&part, &destination).await.inspect_err(|e| {
+                tracing::error!("failed to complete upload {}: {}", part.display(), e)
+            })?;
+            if fsync {
+                sync_to_disk(&destination).await.inspect_err(|e| {
+                    tracing::error!("failed to sync {}: {}", destination.display(), e)
+                })?;
+            }
+            tracing::info!("uploaded {}", destination.display());
+
+            if let Some(existing) = existing.filter(|existing| *existing != filename) {
+                remove_replaced(&folder.join(existing)).await?;
+            }
+            Ok(())
+        })
+        .await?;
+    state.latest_version_cache.invalidate().await;
+
+    Ok(StatusCode::OK)
+}
+
+// detached signatures kept next to an artifact as `<artifact>.<extension>`
+const SIGNATURE_EXTENSIONS: [&str; 2] = ["sig", "asc"];
+
+async fn remove_replaced(path: &std::path::Path) -> Result<(), APIError> {
+    match fs::remove_file(path).await {
+        Ok(_) => {
+            tracing::info!("removed replaced {}", path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => {
+            tracing::error!("failed to remove replaced {}: {}", path.display(), e);
+            Err(APIError::Internal)
+        }
+    }
+}
+
+// a configured platform in its canonical spelling, or the attachments folder
+fn upload_platform(state: &AppState, platform: String) -> Result<String, APIError> {
+    match platform.as_str() {
+        scanner::ATTACHMENTS => Ok(platform),
+        name => Ok(Platform::parse(&state.config, name)?.to_string()),
+    }
+}
+
+// only existing version and platform folders created by publishing (or the
+// version root for attachments), and a plain file name so the path parameters
+// can't escape KIOSK_DIRECTORY
+async fn part_path(
+    state: &AppState,
+    version: &str,
+    platform: &str,
+    filename: &str,
+) -> Result<PathBuf, APIError> {
+    if !scanner::is_plain_file_name(filename) {
+        return Err(APIError::NotFound);
+    }
+
+    let platform_folder = scanner::artifact_directory(&state.config, version, platform)
+        .await?
+        .ok_or(APIError::NotFound)?;
+
+    Ok(platform_folder.join(format!("{}.{}", filename, scanner::PART_EXTENSION)))
+}
+
+// flushes the artifact and the directory entry of the rename, so a crash right
+// after publishing can't leave an empty or missing artifact behind
+async fn sync_to_disk(path: &std::path::Path) -> std::io::Result<()> {
+    fs::File::open(path).await?.sync_all().await?;
+    if let Some(parent) = path.parent() {
+        fs::File::open(parent).await?.sync_all().await?;
+    }
+
+    Ok(())
+}
+
+// the file a finished upload would replace, the same name or, for platform
+// artifacts, the artifact already in the folder (a platform has only one)
+async fn conflicting_file(
+    state: &AppState,
+    part: &std::path::Path,
+    platform: &str,
+) -> Result<Option<String>, APIError> {
+    let destination = part.with_extension("");
+    let Some(filename) = destination.file_name().and_then(|name| name.to_str()) else {
+        return Ok(None);
+    };
+    if fs::try_exists(&destination).await? {
+        return Ok(Some(filename.to_string()));
+    }
+    if platform == scanner::ATTACHMENTS || !scanner::is_artifact(&state.config, filename) {
+        return Ok(None);
+    }
+
+    let Some(folder) = destination.parent() else {
+        return Ok(None);
+    };
+    Ok(scanner::read_platform_folder(&state.config, folder)
+        .await?
+        .artifact)
+}
+
+async fn part_length(part: &std::path::Path) -> Result<u64, APIError> {
+    match fs::metadata(part).await {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// `bytes 100-199/1000` or `bytes 100-199/*`, only the start matters since the
+// body length decides how much gets appended
+fn parse_content_range_start(value: &str) -> Result<u64, APIError> {
+    value
+        .strip_prefix("bytes ")
+        .and_then(|range| range.split_once('-'))
+        .and_then(|(start, _)| start.trim().parse().ok())
+        .ok_or(APIError::InvalidContentRange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{Kiosk, SIGNATURE};
+
+    const FOLDER: &str = "1.0.0/linux_x86_64";
+
+    async fn complete(
+        state: &Arc<AppState>,
+        filename: &str,
+        overwrite: bool,
+    ) -> Result<StatusCode, APIError> {
+        complete_upload(
+            State(state.clone()),
+            Path(("1.0.0".into(), "linux_x86_64".into(), filename.into())),
+            Query(OverwriteQuery { overwrite }),
+        )
+        .await
+    }
+
+    fn read(kiosk: &Kiosk, file: &str) -> Option<String> {
+        std::fs::read_to_string(kiosk.path().join(FOLDER).join(file)).ok()
+    }
+
+    async fn published() -> (Kiosk, Arc<AppState>) {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        (kiosk, state)
+    }
+
+    #[tokio::test]
+    async fn refuses_to_replace_an_artifact_without_overwrite() {
+        let (kiosk, state) = published().await;
+        kiosk.write(&format!("{}/app-linux_x86_64.tar.gz.part", FOLDER), "new");
+
+        let result = complete(&state, "app-linux_x86_64.tar.gz", false).await;
+
+        assert!(
+            matches!(result, Err(APIError::ArtifactExists(existing)) if existing == "app-linux_x86_64.tar.gz")
+        );
+        assert_eq!(
+            read(&kiosk, "app-linux_x86_64.tar.gz").as_deref(),
+            Some("bin 1.0.0 linux_x86_64")
+        );
+    }
+
+    #[tokio::test]
+    async fn an_overwrite_drops_the_stale_signature() {
+        let (kiosk, state) = published().await;
+        kiosk.write(&format!("{}/app-linux_x86_64.tar.gz.asc", FOLDER), "pgp");
+        kiosk.write(&format!("{}/app-linux_x86_64.tar.gz.part", FOLDER), "new");
+
+        complete(&state, "app-linux_x86_64.tar.gz", true)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read(&kiosk, "app-linux_x86_64.tar.gz").as_deref(),
+            Some("new")
+        );
+        assert_eq!(read(&kiosk, "app-linux_x86_64.tar.gz.sig"), None);
+        assert_eq!(read(&kiosk, "app-linux_x86_64.tar.gz.asc"), None);
+    }
+
+    #[tokio::test]
+    async fn an_overwrite_installs_the_uploaded_signature() {
+        let (kiosk, state) = published().await;
+        let signature = SIGNATURE.replace("1700000000", "1800000000");
+        kiosk.write(
+            &format!("{}/app-linux_x86_64.tar.gz.sig.part", FOLDER),
+            &signature,
+        );
+        kiosk.write(&format!("{}/app-linux_x86_64.tar.gz.part", FOLDER), "new");
+
+        complete(&state, "app-linux_x86_64.tar.gz", true)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            read(&kiosk, "app-linux_x86_64.tar.gz").as_deref(),
+            Some("new")
+        );
+        assert_eq!(read(&kiosk, "app-linux_x86_64.tar.gz.sig"), Some(signature));
+        assert_eq!(read(&kiosk, "app-linux_x86_64.tar.gz.sig.part"), None);
+    }
+
+    #[tokio::test]
+    async fn an_overwrite_with_a_new_name_removes_the_old_artifact() {
+        let (kiosk, state) = published().await;
+        kiosk.write(&format!("{}/app-2.tar.gz.sig.part", FOLDER), SIGNATURE);
+        kiosk.write(&format!("{}/app-2.tar.gz.part", FOLDER), "new");
+
+        assert!(matches!(
+            complete(&state, "app-2.tar.gz", false).await,
+            Err(APIError::ArtifactExists(_))
+        ));
+        complete(&state, "app-2.tar.gz", true).await.unwrap();
+
+        assert_eq!(read(&kiosk, "app-linux_x86_64.tar.gz"), None);
+        assert_eq!(read(&kiosk, "app-linux_x86_64.tar.gz.sig"), None);
+        assert_eq!(read(&kiosk, "app-2.tar.gz").as_deref(), Some("new"));
+        assert_eq!(read(&kiosk, "app-2.tar.gz.sig").as_deref(), Some(SIGNATURE));
+    }
+
+    // ten chunks with a pause in between so an unserialized writer would
+    // interleave with the other upload
+    fn slow_body(byte: u8) -> Body {
+        Body::from_stream(futures_util::stream::iter(0..10).then(move |_| async move {
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+            Ok::<_, std::io::Error>(vec![byte; 100])
+        }))
+    }
+
+    #[tokio::test]
+    async fn concurrent_uploads_of_the_same_file_dont_interleave() {
+        let (kiosk, state) = published().await;
+        let upload = |byte: u8| {
+            let state = state.clone();
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_RANGE, "bytes 0-999/1000".parse().unwrap());
+            headers.insert(header::CONTENT_LENGTH, 1000.into());
+            tokio::spawn(upload_chunk(
+                State(state),
+                Path(("1.0.0".into(), "linux_x86_64".into(), "app.tar.gz".into())),
+                Query(OverwriteQuery { overwrite: true }),
+                headers,
+                slow_body(byte),
+            ))
+        };
+
+        let (a, b) = tokio::join!(upload(b'a'), upload(b'b'));
+        let results = [a.unwrap(), b.unwrap()];
+
+        // the second one finds the first upload's bytes and has to resume after them
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, Err(APIError::UploadOffsetMismatch(1000)))));
+        let part = std::fs::read(kiosk.path().join(FOLDER).join("app.tar.gz.part")).unwrap();
+        assert_eq!(part.len(), 1000);
+        assert!(part.iter().all(|byte| *byte == part[0]));
+    }
+
+    #[tokio::test]
+    async fn refuses_a_binary_for_another_platform() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[("VALIDATE_BINARY_FORMAT", "true")]))
+            .await
+            .unwrap();
+        let part = kiosk.path().join("1.0.0/windows_x86_64/app.exe.part");
+        std::fs::write(&part, b"\x7fELF\x02\x01\x01\x00").unwrap();
+
+        let result = complete_upload(
+            State(state.clone()),
+            Path(("1.0.0".into(), "windows_x86_64".into(), "app.exe".into())),
+            Query(OverwriteQuery { overwrite: true }),
+        )
+        .await;
+
+        assert!(
+            matches!(result, Err(APIError::BinaryFormatMismatch(_))),
+            "{:?}",
+            result
+        );
+        assert!(!part.exists());
+        // the artifact it would have replaced stays
+        assert!(!kiosk.path().join("1.0.0/windows_x86_64/app.exe").exists());
+        assert!(kiosk
+            .path()
+            .join("1.0.0/windows_x86_64/app-windows_x86_64.tar.gz.sig")
+            .exists());
+    }
+
+    async fn send_chunk(
+        state: &Arc<AppState>,
+        content_range: Option<&str>,
+        length: usize,
+        body: Body,
+    ) -> Result<u64, APIError> {
+        let mut headers = HeaderMap::new();
+        if let Some(content_range) = content_range {
+            headers.insert(header::CONTENT_RANGE, content_range.parse().unwrap());
+        }
+        headers.insert(header::CONTENT_LENGTH, length.into());
+        upload_chunk(
+            State(state.clone()),
+            Path(("1.0.0".into(), "linux_x86_64".into(), "app.tar.gz".into())),
+            Query(OverwriteQuery { overwrite: true }),
+            headers,
+            body,
+        )
+        .await
+        .map(|Json(UploadOffset { offset })| offset)
+    }
+
+    #[tokio::test]
+    async fn resumes_an_interrupted_upload() {
+        let (kiosk, state) = published().await;
+        // the client goes away after the first six bytes
+        let interrupted = Body::from_stream(futures_util::stream::iter([
+            Ok(b"hello ".to_vec()),
+            Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "gone",
+            )),
+        ]));
+
+        let result = send_chunk(&state, Some("bytes 0-10/11"), 11, interrupted).await;
+        assert!(matches!(result, Err(APIError::UploadOffsetMismatch(6))));
+
+        let Json(UploadOffset { offset }) = get_upload_offset(
+            State(state.clone()),
+            Path(("1.0.0".into(), "linux_x86_64".into(), "app.tar.gz".into())),
+        )
+        .await
+        .unwrap();
+        assert_eq!(offset, 6);
+
+        // a chunk that doesn't continue at the offset is refused untouched
+        let result = send_chunk(&state, Some("bytes 3-10/11"), 8, Body::from("lo world")).await;
+        assert!(matches!(result, Err(APIError::UploadOffsetMismatch(6))));
+
+        let offset = send_chunk(&state, Some("bytes 6-10/11"), 5, Body::from("world"))
+            .await
+            .unwrap();
+        assert_eq!(offset, 11);
+        complete(&state, "app.tar.gz", true).await.unwrap();
+
+        let artifact = std::fs::read(kiosk.path().join(FOLDER).join("app.tar.gz")).unwrap();
+        assert_eq!(artifact, b"hello world");
+        assert!(!kiosk.path().join(FOLDER).join("app.tar.gz.part").exists());
+    }
+
+    #[tokio::test]
+    async fn requires_the_chunk_length() {
+        let (kiosk, state) = published().await;
+
+        let result = upload_chunk(
+            State(state.clone()),
+            Path(("1.0.0".into(), "linux_x86_64".into(), "app.tar.gz".into())),
+            Query(OverwriteQuery { overwrite: true }),
+            HeaderMap::new(),
+            Body::from("chunk"),
+        )
+        .await;
+
+        assert!(matches!(result, Err(APIError::LengthRequired)));
+        assert!(!kiosk.path().join(FOLDER).join("app.tar.gz.part").exists());
+    }
+
+    #[tokio::test]
+    async fn refuses_a_chunk_that_doesnt_fit_on_disk() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let available = fs2::available_space(kiosk.path()).unwrap();
+        // half the disk has to stay free, so a chunk of the other half can't fit
+        let reserve = (available / 2).to_string();
+        let state = AppState::build(kiosk.config(&[("MIN_FREE_SPACE_BYTES", &reserve)]))
+            .await
+            .unwrap();
+
+        let result = send_chunk(&state, None, available as usize, Body::from("chunk")).await;
+        assert!(matches!(result, Err(APIError::InsufficientStorage)));
+        assert!(!kiosk.path().join(FOLDER).join("app.tar.gz.part").exists());
+
+        let offset = send_chunk(&state, None, 5, Body::from("chunk"))
+            .await
+            .unwrap();
+        assert_eq!(offset, 5);
+    }
+
+    #[tokio::test]
+    async fn completes_with_and_without_fsync() {
+        for fsync in ["true", "false"] {
+            let kiosk = Kiosk::new();
+            kiosk.add_version("1.0.0");
+            let state = AppState::build(kiosk.config(&[("FSYNC_UPLOADS", fsync)]))
+                .await
+                .unwrap();
+            assert_eq!(state.config.fsync_uploads, fsync == "true");
+
+            let offset = send_chunk(&state, Some("bytes 0-8/9"), 9, Body::from("fsynced!\n"))
+                .await
+                .unwrap();
+            assert_eq!(offset, 9);
+            complete(&state, "app.tar.gz", true).await.unwrap();
+
+            assert_eq!(read(&kiosk, "app.tar.gz").as_deref(), Some("fsynced!\n"));
+            assert_eq!(read(&kiosk, "app.tar.gz.part"), None);
+            assert_eq!(read(&kiosk, "app-linux_x86_64.tar.gz"), None, "{}", fsync);
+        }
+    }
+}
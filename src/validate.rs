@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Multipart, State},
+    Json,
+};
+use base64::Engine;
+use minisign_verify::{PublicKey, Signature};
+use serde::Serialize;
+
+use tokio::io::AsyncReadExt;
+
+use crate::{config::Config, error::APIError, platform::Platform, state::AppState};
+
+#[derive(Serialize)]
+pub struct ValidationResponse {
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+impl ValidationResponse {
+    fn invalid(error: impl Into<String>) -> Json<Self> {
+        Json(Self {
+            valid: false,
+            error: Some(error.into()),
+        })
+    }
+}
+
+// TAURI_PUBLIC_KEY as printed by `tauri signer generate`, the base64 of the
+// whole minisign key file, or just the key line
+pub fn decode_public_key(value: &str) -> Result<PublicKey, minisign_verify::Error> {
+    match decode_text(value) {
+        Some(text) => PublicKey::decode(&text),
+        None => PublicKey::from_base64(value.trim()),
+    }
+}
+
+// `.sig` files written by the tauri bundler are base64, the manifest serves
+// them as is, but a plain minisign signature is accepted too
+fn decode_signature(content: &str) -> Result<Signature, minisign_verify::Error> {
+    let content = content.trim();
+    if content.starts_with("untrusted comment:") {
+        return Signature::decode(content);
+    }
+
+    Signature::decode(&decode_text(content).unwrap_or_default())
+}
+
+fn decode_text(value: &str) -> Option<String> {
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(value.trim())
+        .ok()?;
+    String::from_utf8(decoded)
+        .ok()
+        .filter(|text| text.starts_with("untrusted comment:"))
+}
+
+// whether a stored artifact verifies against its signature, None without
+// TAURI_PUBLIC_KEY since there is nothing to check against
+pub async fn verify_file(
+    config: &Config,
+    artifact: &std::path::Path,
+    signature: &str,
+) -> Result<Option<bool>, APIError> {
+    let Some(public_key) = &config.tauri_public_key else {
+        return Ok(None);
+    };
+    let public_key = decode_public_key(public_key).map_err(|_| APIError::Internal)?;
+    let Ok(signature) = decode_signature(signature) else {
+        return Ok(Some(false));
+    };
+    let Ok(mut verifier) = public_key.verify_stream(&signature) else {
+        return Ok(Some(false));
+    };
+
+    let mut file = tokio::fs::File::open(artifact).await?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        verifier.update(&buffer[..read]);
+    }
+
+    Ok(Some(verifier.finalize().is_ok()))
+}
+
+// checks that `binary` verifies against `signature` with TAURI_PUBLIC_KEY
+// without storing anything. the binary is hashed while it streams in, so the
+// `platform` and `signature` fields have to come before it
+pub async fn validate_artifact(
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<Json<ValidationResponse>, APIError> {
+    let Some(public_key) = &state.config.tauri_public_key else {
+        return Ok(ValidationResponse::invalid(
+            "TAURI_PUBLIC_KEY is not configured",
+        ));
+    };
+    let public_key = decode_public_key(public_key).map_err(|_| APIError::Internal)?;
+
+    let mut platform = None;
+    let mut signature = None;
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| APIError::InvalidMultipart)?
+    {
+        match field.name() {
+            Some("platform") => {
+                platform = Some(field.text().await.map_err(|_| APIError::InvalidMultipart)?)
+            }
+            Some("signature") => {
+                let content = field.text().await.map_err(|_| APIError::InvalidMultipart)?;
+                match decode_signature(&content) {
+                    Ok(decoded) => signature = Some(decoded),
+                    Err(e) => return Ok(ValidationResponse::invalid(e.to_string())),
+                }
+            }
+            Some("binary") => {
+                let Some(platform) = &platform else {
+                    return Ok(ValidationResponse::invalid(
+                        "platform has to be sent before binary",
+                    ));
+                };
+                if Platform::parse(&state.config, platform).is_err() {
+                    return Ok(ValidationResponse::invalid(format!(
+                        "unknown platform {}",
+                        platform
+                    )));
+                }
+                let Some(signature) = &signature else {
+                    return Ok(ValidationResponse::invalid(
+                        "signature has to be sent before binary",
+                    ));
+                };
+
+                let mut verifier = match public_key.verify_stream(signature) {
+                    Ok(verifier) => verifier,
+                    Err(e) => return Ok(ValidationResponse::invalid(e.to_string())),
+                };
+                while let Some(chunk) = field
+                    .chunk()
+                    .await
+                    .map_err(|_| APIError::InvalidMultipart)?
+                {
+                    verifier.update(&chunk);
+                }
+
+                return Ok(Json(match verifier.finalize() {
+                    Ok(()) => ValidationResponse {
+                        valid: true,
+                        error: None,
+                    },
+                    Err(e) => ValidationResponse {
+                        valid: false,
+                        error: Some(e.to_string()),
+                    },
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(ValidationResponse::invalid("binary is missing"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+
+    use crate::test_support::{self, minisign::SigningKey, Kiosk};
+
+    const BOUNDARY: &str = "kiosk-boundary";
+    const BINARY: &[u8] = b"the update bundle";
+
+    // posts the fields in the given order as they'd come from curl -F
+    async fn validate(key: &SigningKey, fields: &[(&str, &[u8])]) -> serde_json::Value {
+        let kiosk = Kiosk::new();
+        let state = crate::state::AppState::build(
+            kiosk.config(&[("TAURI_PUBLIC_KEY", &key.tauri_public_key())]),
+        )
+        .await
+        .unwrap();
+
+        let mut body = Vec::new();
+        for (name, content) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+                    BOUNDARY, name
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+
+        let request = Request::post("/validate-artifact")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        test_support::json(test_support::send(&state, request).await).await
+    }
+
+    #[tokio::test]
+    async fn accepts_a_matching_pair() {
+        let key = SigningKey::new(1);
+
+        for signature in [key.tauri_signature(BINARY), key.sign(BINARY)] {
+            let response = validate(
+                &key,
+                &[
+                    ("platform", b"linux_x86_64"),
+                    ("signature", signature.as_bytes()),
+                    ("binary", BINARY),
+                ],
+            )
+            .await;
+            assert_eq!(response["valid"], true, "{}", response);
+            assert_eq!(response["error"], serde_json::Value::Null);
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_a_wrong_signature() {
+        let key = SigningKey::new(1);
+
+        for signature in [
+            key.tauri_signature(b"another bundle"),
+            SigningKey::new(2).tauri_signature(BINARY),
+        ] {
+            let response = validate(
+                &key,
+                &[
+                    ("platform", b"linux_x86_64"),
+                    ("signature", signature.as_bytes()),
+                    ("binary", BINARY),
+                ],
+            )
+            .await;
+            assert_eq!(response["valid"], false, "{}", response);
+            assert!(response["error"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn needs_the_signature_before_the_binary() {
+        let key = SigningKey::new(1);
+        let signature = key.tauri_signature(BINARY);
+
+        let response = validate(
+            &key,
+            &[
+                ("platform", b"linux_x86_64"),
+                ("binary", BINARY),
+                ("signature", signature.as_bytes()),
+            ],
+        )
+        .await;
+
+        assert_eq!(response["valid"], false);
+        assert_eq!(response["error"], "signature has to be sent before binary");
+    }
+
+    #[tokio::test]
+    async fn refuses_an_unknown_platform() {
+        let key = SigningKey::new(1);
+        let signature = key.tauri_signature(BINARY);
+
+        let response = validate(
+            &key,
+            &[
+                ("platform", b"solaris_sparc"),
+                ("signature", signature.as_bytes()),
+                ("binary", BINARY),
+            ],
+        )
+        .await;
+
+        assert_eq!(response["valid"], false);
+        assert_eq!(response["error"], "unknown platform solaris_sparc");
+    }
+}
@@ -0,0 +1,1497 @@
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc, time::SystemTime};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use sea_orm::sqlx::types::chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::{
+    config::Config, error::APIError, negotiate::Format, notes, platform::Platform, scanner,
+    state::AppState, validate,
+};
+
+#[derive(Serialize)]
+pub struct VersionSummary {
+    pub version: String,
+    pub yanked: bool,
+}
+
+#[derive(Serialize)]
+pub struct VersionsResponse {
+    pub versions: Vec<VersionSummary>,
+}
+
+// answers 304 when If-None-Match carries the current ETag, which is derived
+// from the version set, their pub dates and yanked state so it changes with
+// every publish, upload, yank or delete
+pub async fn get_versions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Response, APIError> {
+    let folders = scanner::list_versions(&state.config).await?;
+
+    let mut versions = Vec::new();
+    let mut hasher = Sha256::new();
+    for (_, name) in folders.versions {
+        let yanked =
+            scanner::is_yanked(&std::path::Path::new(&state.config.kiosk_directory).join(&name))
+                .await;
+        let pub_date = scanner::inspect_version(&state.config, &name)
+            .await?
+            .modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        hasher.update(format!("{}\n{}\n{}\n", name, pub_date.as_nanos(), yanked));
+        versions.push(VersionSummary {
+            version: name,
+            yanked,
+        });
+    }
+    // each representation gets its own tag
+    let format = Format::from_headers(&headers);
+    if format == Format::Yaml {
+        hasher.update("yaml");
+    }
+    let etag = format!("\"{}\"", hex::encode(hasher.finalize()));
+
+    let matches = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    let mut response = if matches {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        format.respond(&VersionsResponse { versions })?
+    };
+    response
+        .headers_mut()
+        .insert(header::ETAG, etag.parse().map_err(|_| APIError::Internal)?);
+    response
+        .headers_mut()
+        .insert(header::VARY, header::HeaderValue::from_static("accept"));
+
+    Ok(response)
+}
+
+#[derive(Serialize)]
+pub struct VersionsStatusResponse {
+    pub versions: Vec<String>,
+    pub ignored: Vec<scanner::IgnoredFolder>,
+}
+
+pub async fn get_versions_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<VersionsStatusResponse>, APIError> {
+    let folders = scanner::list_versions(&state.config).await?;
+
+    Ok(Json(VersionsStatusResponse {
+        versions: folders.versions.into_iter().map(|(_, name)| name).collect(),
+        ignored: folders.ignored,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct PlatformStatus {
+    pub missing: Vec<scanner::MissingPart>,
+}
+
+#[derive(Serialize)]
+pub struct VersionStatusResponse {
+    pub version: String,
+    pub complete: bool,
+    pub yanked: bool,
+    // published under REQUIRE_APPROVAL and not approved yet
+    pub pending: bool,
+    pub platforms: BTreeMap<String, PlatformStatus>,
+}
+
+pub async fn get_version_status(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<Json<VersionStatusResponse>, APIError> {
+    Ok(Json(version_status(&state, version).await?))
+}
+
+// versions one POST /versions/status may ask for
+pub const MAX_BATCH_VERSIONS: usize = 100;
+
+// the status of every requested version for a dashboard grid, versions that
+// don't exist are left out of the map
+pub async fn get_batch_status(
+    State(state): State<Arc<AppState>>,
+    Json(versions): Json<Vec<String>>,
+) -> Result<Json<BTreeMap<String, VersionStatusResponse>>, APIError> {
+    if versions.len() > MAX_BATCH_VERSIONS {
+        return Err(APIError::TooManyVersions(MAX_BATCH_VERSIONS));
+    }
+
+    let mut statuses = BTreeMap::new();
+    for version in versions {
+        if statuses.contains_key(&version) {
+            continue;
+        }
+        match version_status(&state, version.clone()).await {
+            Ok(status) => {
+                statuses.insert(version, status);
+            }
+            Err(APIError::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Json(statuses))
+}
+
+async fn version_status(
+    state: &AppState,
+    version: String,
+) -> Result<VersionStatusResponse, APIError> {
+    let version_directory = existing_version_directory(state, &version).await?;
+
+    let mut inspections = Vec::new();
+    for platform in &state.config.platforms {
+        inspections.push((
+            platform,
+            scanner::inspect_platform(&state.config, &version, platform).await?,
+        ));
+    }
+    let complete = scanner::is_servable(inspections.iter().map(|(_, inspection)| inspection));
+
+    let platforms = inspections
+        .into_iter()
+        .map(|(platform, inspection)| {
+            (
+                platform.to_string(),
+                PlatformStatus {
+                    missing: inspection.missing,
+                },
+            )
+        })
+        .collect();
+
+    Ok(VersionStatusResponse {
+        complete,
+        yanked: scanner::is_yanked(&version_directory).await,
+        pending: scanner::is_pending(&version_directory).await,
+        version,
+        platforms,
+    })
+}
+
+#[derive(Serialize)]
+pub struct ChannelVersion {
+    pub version: String,
+    pub pub_date: String,
+}
+
+#[derive(Serialize)]
+pub struct LatestChannelsResponse {
+    // newest servable release without a prerelease tag
+    pub stable: Option<ChannelVersion>,
+    // what /latest-version serves, prereleases included
+    pub latest: Option<ChannelVersion>,
+}
+
+// both channels at once so a client on stable can tell a newer beta exists
+pub async fn get_latest_channels(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<LatestChannelsResponse>, APIError> {
+    Ok(Json(LatestChannelsResponse {
+        stable: channel_version(&state.config, crate::Channel::Stable).await?,
+        latest: channel_version(&state.config, crate::Channel::Any).await?,
+    }))
+}
+
+async fn channel_version(
+    config: &Config,
+    channel: crate::Channel,
+) -> Result<Option<ChannelVersion>, APIError> {
+    match crate::resolve_latest_version_in(config, channel).await {
+        Ok(response) => Ok(Some(ChannelVersion {
+            version: response.version,
+            pub_date: response.pub_date,
+        })),
+        Err(APIError::FileOrPathNotExist) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Serialize)]
+pub struct LatestPlatformStatus {
+    pub available: bool,
+    pub complete: bool,
+    pub size: Option<u64>,
+    // None without TAURI_PUBLIC_KEY or a signature to check
+    pub signature_valid: Option<bool>,
+    pub missing: Vec<scanner::MissingPart>,
+}
+
+#[derive(Serialize)]
+pub struct LatestStatusResponse {
+    pub version: String,
+    // every platform with a build is complete and no signature failed
+    pub healthy: bool,
+    pub platforms: BTreeMap<String, LatestPlatformStatus>,
+}
+
+// the status of what /latest-version serves right now with the signatures
+// checked against TAURI_PUBLIC_KEY, 204 when nothing is servable
+pub async fn get_latest_version_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<LatestStatusResponse>, APIError> {
+    let version = match crate::resolve_latest_version(&state.config).await {
+        Ok(response) => response.version,
+        Err(APIError::FileOrPathNotExist) => return Err(APIError::NoUpdate),
+        Err(e) => return Err(e),
+    };
+
+    let mut platforms = BTreeMap::new();
+    for (platform, inspection) in scanner::inspect_version(&state.config, &version)
+        .await?
+        .platforms
+    {
+        let signature_valid = match (&inspection.files, &inspection.signature) {
+            (Some(files), Some(signature)) => match &files.artifact {
+                Some(artifact) => {
+                    validate::verify_file(&state.config, &files.folder.join(artifact), signature)
+                        .await?
+                }
+                None => None,
+            },
+            _ => None,
+        };
+        platforms.insert(
+            platform,
+            LatestPlatformStatus {
+                available: inspection.is_available(),
+                complete: inspection.is_complete(),
+                size: inspection.artifact_size(),
+                signature_valid,
+                missing: inspection.missing,
+            },
+        );
+    }
+    let healthy = platforms.values().all(|platform| {
+        !platform.available || (platform.complete && platform.signature_valid != Some(false))
+    });
+
+    Ok(Json(LatestStatusResponse {
+        version,
+        healthy,
+        platforms,
+    }))
+}
+
+// everything the dashboard shows for one platform, unlike the tauri manifest
+// this also carries the optional pgp signature
+#[derive(Serialize)]
+pub struct PlatformDetailsView {
+    // false when the version has no folder for the platform, i.e. no build
+    // rather than a broken one
+    pub available: bool,
+    pub url: Option<String>,
+    // fallbacks for `url`, empty without MIRROR_BASE_URLS
+    pub mirrors: Vec<String>,
+    pub size: Option<u64>,
+    pub signature: Option<String>,
+    pub pgp_signature: Option<String>,
+    pub missing: Vec<scanner::MissingPart>,
+}
+
+#[derive(Serialize)]
+pub struct Attachment {
+    pub filename: String,
+    pub size: u64,
+    pub url: String,
+}
+
+// a platform folder outside PLATFORMS, shown but never served
+#[derive(Serialize)]
+pub struct ExtraPlatform {
+    pub artifact: Option<String>,
+    pub size: Option<u64>,
+    pub signed: bool,
+}
+
+#[derive(Serialize)]
+pub struct VersionDetailsResponse {
+    pub version: String,
+    pub pub_date: String,
+    // pub_date in PUB_DATE_TZ, only set when that is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pub_date_local: Option<String>,
+    pub complete: bool,
+    pub yanked: bool,
+    pub required: bool,
+    pub platforms: BTreeMap<String, PlatformDetailsView>,
+    pub extra_platforms: BTreeMap<String, ExtraPlatform>,
+    pub attachments: Vec<Attachment>,
+}
+
+pub async fn get_version_details(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<Json<VersionDetailsResponse>, APIError> {
+    // `stable -> 1.2.0` answers with the details of 1.2.0
+    let version = scanner::resolve_alias(&state.config, &version)
+        .await
+        .unwrap_or(version);
+    let version_directory = existing_version_directory(&state, &version).await?;
+
+    let inspection = scanner::inspect_version(&state.config, &version).await?;
+    let complete = inspection.is_complete();
+    let pub_date = DateTime::<Utc>::from(inspection.modified);
+    let pub_date_local = state
+        .config
+        .pub_date_tz
+        .map(|tz| pub_date.with_timezone(&tz).to_rfc3339());
+
+    let mut platforms = BTreeMap::new();
+    for (platform, inspection) in inspection.platforms {
+        platforms.insert(
+            platform.clone(),
+            PlatformDetailsView {
+                available: inspection.is_available(),
+                url: crate::artifact_url(&state.config, &version, &platform, &inspection),
+                mirrors: inspection
+                    .artifact()
+                    .map(|artifact| {
+                        crate::mirror_urls(&state.config, &version, &platform, artifact)
+                    })
+                    .unwrap_or_default(),
+                size: inspection.artifact_size(),
+                pgp_signature: inspection.pgp_signature().map(String::from),
+                signature: inspection.signature,
+                missing: inspection.missing,
+            },
+        );
+    }
+
+    let mut extra_platforms = BTreeMap::new();
+    for platform in scanner::extra_platforms(&state.config, &version).await? {
+        let inspection = scanner::inspect_platform(&state.config, &version, &platform).await?;
+        extra_platforms.insert(
+            platform,
+            ExtraPlatform {
+                artifact: inspection.artifact().map(String::from),
+                size: inspection.artifact_size(),
+                signed: inspection.signature.is_some(),
+            },
+        );
+    }
+
+    let attachments = scanner::list_attachments(&version_directory)
+        .await?
+        .into_iter()
+        .map(|(filename, size)| Attachment {
+            url: crate::download_url(&state.config, &version, scanner::ATTACHMENTS, &filename),
+            filename,
+            size,
+        })
+        .collect();
+
+    Ok(Json(VersionDetailsResponse {
+        pub_date: pub_date.to_rfc3339(),
+        pub_date_local,
+        complete,
+        yanked: scanner::is_yanked(&version_directory).await,
+        required: scanner::is_required(&version_directory).await,
+        version,
+        platforms,
+        extra_platforms,
+        attachments,
+    }))
+}
+
+#[derive(Serialize)]
+pub struct PlatformUrls {
+    pub binary: String,
+    pub signature: Option<String>,
+}
+
+// download urls of every platform that has an artifact, for scripts fetching
+// a whole version
+pub async fn get_version_urls(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<Json<BTreeMap<String, PlatformUrls>>, APIError> {
+    existing_version_directory(&state, &version).await?;
+
+    let mut urls = BTreeMap::new();
+    for platform in &state.config.platforms {
+        let inspection = scanner::inspect_platform(&state.config, &version, platform).await?;
+        let Some(files) = inspection.files else {
+            continue;
+        };
+        let Some(artifact) = &files.artifact else {
+            continue;
+        };
+        let signature = files
+            .signature
+            .as_ref()
+            .and_then(|(path, _)| path.file_name()?.to_str())
+            .map(|filename| crate::download_url(&state.config, &version, platform, filename));
+
+        urls.insert(
+            platform.clone(),
+            PlatformUrls {
+                binary: crate::download_url(&state.config, &version, platform, artifact),
+                signature,
+            },
+        );
+    }
+
+    Ok(Json(urls))
+}
+
+// the tauri signature as text so it can be copied from a browser
+pub async fn get_platform_signature(
+    State(state): State<Arc<AppState>>,
+    Path((version, platform)): Path<(String, String)>,
+) -> Result<Response, APIError> {
+    let platform = Platform::parse(&state.config, &platform)?;
+    existing_version_directory(&state, &version).await?;
+    let inspection = scanner::inspect_platform(&state.config, &version, &platform).await?;
+
+    inspection
+        .signature
+        .map(notes::inline_text)
+        .ok_or(APIError::NotFound)
+}
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactChange {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+#[derive(Serialize)]
+pub struct ArtifactFile {
+    pub filename: String,
+    pub size: u64,
+}
+
+#[derive(Serialize)]
+pub struct PlatformDiff {
+    pub change: ArtifactChange,
+    pub from: Option<ArtifactFile>,
+    pub to: Option<ArtifactFile>,
+}
+
+#[derive(Serialize)]
+pub struct NotesDiff {
+    pub changed: bool,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize)]
+pub struct VersionDiffResponse {
+    pub from: String,
+    pub to: String,
+    pub platforms: BTreeMap<String, PlatformDiff>,
+    pub notes: NotesDiff,
+}
+
+// what changed between two versions per platform, artifacts of the same size
+// are compared by sha256
+pub async fn get_version_diff(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<VersionDiffResponse>, APIError> {
+    let from_directory = existing_version_directory(&state, &query.from).await?;
+    let to_directory = existing_version_directory(&state, &query.to).await?;
+
+    let mut platforms = BTreeMap::new();
+    for platform in &state.config.platforms {
+        let from = artifact_of(&state, &query.from, platform).await?;
+        let to = artifact_of(&state, &query.to, platform).await?;
+
+        let change = match (&from, &to) {
+            (None, None) => continue,
+            (None, Some(_)) => ArtifactChange::Added,
+            (Some(_), None) => ArtifactChange::Removed,
+            (Some((from_path, from_file)), Some((to_path, to_file))) => {
+                if from_file.size != to_file.size
+                    || state.hash_cache.sha256(from_path).await?
+                        != state.hash_cache.sha256(to_path).await?
+                {
+                    ArtifactChange::Changed
+                } else {
+                    ArtifactChange::Unchanged
+                }
+            }
+        };
+
+        platforms.insert(
+            platform.to_string(),
+            PlatformDiff {
+                change,
+                from: from.map(|(_, file)| file),
+                to: to.map(|(_, file)| file),
+            },
+        );
+    }
+
+    let from_notes = scanner::read_notes(&from_directory).await?;
+    let to_notes = scanner::read_notes(&to_directory).await?;
+
+    Ok(Json(VersionDiffResponse {
+        from: query.from,
+        to: query.to,
+        platforms,
+        notes: NotesDiff {
+            changed: from_notes != to_notes,
+            from: from_notes,
+            to: to_notes,
+        },
+    }))
+}
+
+async fn artifact_of(
+    state: &AppState,
+    version: &str,
+    platform: &str,
+) -> Result<Option<(PathBuf, ArtifactFile)>, APIError> {
+    let Some(files) = scanner::read_platform(&state.config, version, platform).await? else {
+        return Ok(None);
+    };
+    let Some(filename) = files.artifact else {
+        return Ok(None);
+    };
+
+    let path = files.folder.join(&filename);
+    let size = fs::metadata(&path).await?.len();
+    Ok(Some((path, ArtifactFile { filename, size })))
+}
+
+#[derive(Deserialize)]
+pub struct ChangelogQuery {
+    pub since: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ChangelogEntry {
+    pub version: String,
+    pub pub_date: String,
+    pub notes: String,
+}
+
+// notes of every servable version newer than `since`, latest first, so a kiosk
+// can show what changed since the version it runs
+pub async fn get_changelog(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ChangelogQuery>,
+) -> Result<Json<Vec<ChangelogEntry>>, APIError> {
+    let since = match &query.since {
+        Some(since) => Some((
+            scanner::parse_version(&state.config, since).ok_or(APIError::InvalidVersion)?,
+            since.clone(),
+        )),
+        None => None,
+    };
+
+    let folders = scanner::list_versions(&state.config).await?;
+    let mut changelog = Vec::new();
+    for (version, name) in folders.versions {
+        // the order depends on PRERELEASE_ORDER so older versions aren't
+        // necessarily at the end
+        if since.as_ref().is_some_and(|(since, since_name)| {
+            scanner::compare_folders(&state.config, (&version, &name), (since, since_name)).is_le()
+        }) {
+            continue;
+        }
+
+        let version_directory = std::path::Path::new(&state.config.kiosk_directory).join(&name);
+        if scanner::is_yanked(&version_directory).await
+            || scanner::is_pending(&version_directory).await
+        {
+            continue;
+        }
+        let inspection = scanner::inspect_version(&state.config, &name).await?;
+        if !inspection.is_complete() {
+            continue;
+        }
+
+        changelog.push(ChangelogEntry {
+            pub_date: DateTime::<Utc>::from(inspection.modified).to_rfc3339(),
+            notes: scanner::read_notes(&version_directory).await?,
+            version: name,
+        });
+    }
+
+    Ok(Json(changelog))
+}
+
+pub async fn yank_version(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<StatusCode, APIError> {
+    set_marker(&state, &version, scanner::YANKED_MARKER, true).await?;
+    tracing::info!("yanked version {}", version);
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn unyank_version(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<StatusCode, APIError> {
+    set_marker(&state, &version, scanner::YANKED_MARKER, false).await?;
+    tracing::info!("unyanked version {}", version);
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn mark_required(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<StatusCode, APIError> {
+    set_marker(&state, &version, scanner::REQUIRED_MARKER, true).await?;
+    tracing::info!("marked version {} as a required update", version);
+
+    Ok(StatusCode::OK)
+}
+
+pub async fn unmark_required(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<StatusCode, APIError> {
+    set_marker(&state, &version, scanner::REQUIRED_MARKER, false).await?;
+    tracing::info!("version {} is no longer a required update", version);
+
+    Ok(StatusCode::OK)
+}
+
+pub const APPROVER_HEADER: &str = "x-approver";
+
+// flips a version published under REQUIRE_APPROVAL to servable. needs
+// `Authorization: Bearer <APPROVER_TOKEN>`, `X-Approver` names who approved
+// and ends up in the log and in APPROVED_FILE
+pub async fn approve_version(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, APIError> {
+    let Some(token) = &state.config.approver_token else {
+        return Err(APIError::Forbidden);
+    };
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    // compares digests so the time taken says nothing about the token
+    if Sha256::digest(presented.trim()) != Sha256::digest(token) {
+        tracing::warn!("refused to approve version {} with a wrong token", version);
+        return Err(APIError::Forbidden);
+    }
+    let approver = headers
+        .get(APPROVER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|approver| !approver.is_empty())
+        .unwrap_or("unknown");
+
+    let version_directory = existing_version_directory(&state, &version).await?;
+    if !scanner::is_pending(&version_directory).await {
+        return Ok(StatusCode::OK);
+    }
+    let record = format!("{}\t{}\n", approver, Utc::now().to_rfc3339());
+    if let Err(e) = fs::write(version_directory.join(scanner::APPROVED_FILE), record).await {
+        tracing::error!("failed to record the approval of {}: {}", version, e);
+        return Err(APIError::Internal);
+    }
+    set_marker(&state, &version, scanner::PENDING_MARKER, false).await?;
+    tracing::info!("version {} approved by {}", version, approver);
+
+    Ok(StatusCode::OK)
+}
+
+// re-scans one version after its files were fixed out of band. only cache
+// entries the version could change are dropped: the ones serving it and the
+// ones serving an older version it may now outrank
+pub async fn refresh_version(
+    State(state): State<Arc<AppState>>,
+    Path(version): Path<String>,
+) -> Result<Json<VersionStatusResponse>, APIError> {
+    let status = get_version_status(State(state.clone()), Path(version)).await?;
+    let refreshed =
+        scanner::parse_version(&state.config, &status.version).ok_or(APIError::NotFound)?;
+
+    let evicted = state
+        .latest_version_cache
+        .evict(
+            |cached| match scanner::parse_version(&state.config, &cached.version) {
+                Some(cached_version) => scanner::compare_folders(
+                    &state.config,
+                    (&cached_version, &cached.version),
+                    (&refreshed, &status.version),
+                )
+                .is_le(),
+                None => true,
+            },
+        )
+        .await;
+    tracing::info!(
+        "refreshed version {}, {} cached manifests evicted",
+        status.version,
+        evicted
+    );
+
+    Ok(status)
+}
+
+// creates or removes a marker file in the version folder, both change what
+// /latest-version serves so the cache is dropped
+async fn set_marker(
+    state: &AppState,
+    version: &str,
+    marker: &str,
+    present: bool,
+) -> Result<(), APIError> {
+    let version_directory = existing_version_directory(state, version).await?;
+    let path = version_directory.join(marker);
+
+    let result = if present {
+        fs::write(&path, b"").await
+    } else {
+        match fs::remove_file(&path).await {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    };
+    if let Err(e) = result {
+        tracing::error!("failed to update {}: {}", path.display(), e);
+        return Err(APIError::Internal);
+    }
+    state.latest_version_cache.invalidate().await;
+
+    Ok(())
+}
+
+// only accepts version names under VERSION_SCHEME so a path parameter can't
+// escape KIOSK_DIRECTORY
+pub async fn existing_version_directory(
+    state: &AppState,
+    version: &str,
+) -> Result<PathBuf, APIError> {
+    if scanner::parse_version(&state.config, version).is_none() {
+        return Err(APIError::NotFound);
+    }
+
+    let version_directory = std::path::Path::new(&state.config.kiosk_directory).join(version);
+    if !fs::try_exists(&version_directory).await? {
+        return Err(APIError::NotFound);
+    }
+
+    Ok(version_directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::ManifestKey,
+        resolve_latest_version,
+        test_support::{self, Kiosk},
+    };
+
+    #[tokio::test]
+    async fn a_yanked_version_is_listed_but_never_served() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        yank_version(State(state.clone()), Path("1.1.0".to_string()))
+            .await
+            .unwrap();
+
+        let latest = resolve_latest_version(&state.config).await.unwrap();
+        assert_eq!(latest.version, "1.0.0");
+
+        let response = get_versions(State(state.clone()), HeaderMap::new())
+            .await
+            .unwrap();
+        let listed = test_support::json(response).await;
+        assert_eq!(
+            listed["versions"],
+            serde_json::json!([
+                {"version": "1.1.0", "yanked": true},
+                {"version": "1.0.0", "yanked": false},
+            ])
+        );
+
+        unyank_version(State(state.clone()), Path("1.1.0".to_string()))
+            .await
+            .unwrap();
+        let latest = resolve_latest_version(&state.config).await.unwrap();
+        assert_eq!(latest.version, "1.1.0");
+    }
+
+    #[tokio::test]
+    async fn answers_304_until_a_publish_changes_the_list() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let conditional = |etag: &header::HeaderValue| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, etag.clone());
+            headers
+        };
+
+        let first = get_versions(State(state.clone()), HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()[header::ETAG].clone();
+
+        let unchanged = get_versions(State(state.clone()), conditional(&etag))
+            .await
+            .unwrap();
+        assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(unchanged.headers()[header::ETAG], etag);
+
+        kiosk.add_version("1.1.0");
+        let published = get_versions(State(state.clone()), conditional(&etag))
+            .await
+            .unwrap();
+        assert_eq!(published.status(), StatusCode::OK);
+        assert_ne!(published.headers()[header::ETAG], etag);
+    }
+
+    #[tokio::test]
+    async fn serves_a_quarantined_version_once_approved() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(
+            kiosk.config(&[("REQUIRE_APPROVAL", "true"), ("APPROVER_TOKEN", "s3cret")]),
+        )
+        .await
+        .unwrap();
+        crate::publish::create_version(&state.config, "1.1.0", "notes 1.1.0")
+            .await
+            .unwrap();
+        kiosk.add_version("1.1.0");
+        let latest = || async {
+            test_support::json(test_support::get(&state, "/latest-version").await).await["version"]
+                .clone()
+        };
+        let approve = |authorization: Option<&str>| {
+            let mut request = axum::http::Request::post("/versions/1.1.0/approve")
+                .header(APPROVER_HEADER, "alice");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            test_support::send(&state, request.body(axum::body::Body::empty()).unwrap())
+        };
+
+        assert!(kiosk
+            .path()
+            .join("1.1.0")
+            .join(scanner::PENDING_MARKER)
+            .exists());
+        assert_eq!(latest().await, "1.0.0");
+
+        for authorization in [None, Some("Bearer wrong"), Some("s3cret")] {
+            assert_eq!(approve(authorization).await.status(), StatusCode::FORBIDDEN);
+        }
+        assert_eq!(latest().await, "1.0.0");
+
+        assert_eq!(
+            approve(Some("Bearer s3cret")).await.status(),
+            StatusCode::OK
+        );
+        assert!(!kiosk
+            .path()
+            .join("1.1.0")
+            .join(scanner::PENDING_MARKER)
+            .exists());
+        let record =
+            std::fs::read_to_string(kiosk.path().join("1.1.0").join(scanner::APPROVED_FILE))
+                .unwrap();
+        assert!(record.starts_with("alice\t"), "{}", record);
+        assert_eq!(latest().await, "1.1.0");
+    }
+
+    #[tokio::test]
+    async fn reports_folders_that_arent_versions() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .add_version("1.2")
+            .add_version("v1.1.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let status = test_support::json(test_support::get(&state, "/versions/status").await).await;
+
+        assert_eq!(status["versions"], serde_json::json!(["1.0.0"]));
+        let reason = |name: &str| name.parse::<semver::Version>().unwrap_err().to_string();
+        assert_eq!(
+            status["ignored"],
+            serde_json::json!([
+                { "name": "1.2", "reason": reason("1.2") },
+                { "name": "v1.1.0", "reason": reason("v1.1.0") },
+            ])
+        );
+        assert!(reason("1.2").contains("end of input"), "{}", reason("1.2"));
+        assert_eq!(
+            resolve_latest_version(&state.config).await.unwrap().version,
+            "1.0.0"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_what_each_platform_is_missing() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let folder = kiosk.path().join("1.0.0");
+        std::fs::remove_file(folder.join("linux_x86_64/app-linux_x86_64.tar.gz.sig")).unwrap();
+        std::fs::remove_file(folder.join("windows_x86_64/app-windows_x86_64.tar.gz")).unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let status =
+            test_support::json(test_support::get(&state, "/versions/1.0.0/status").await).await;
+
+        assert_eq!(status["version"], "1.0.0");
+        assert_eq!(status["complete"], false);
+        assert_eq!(
+            status["platforms"],
+            serde_json::json!({
+                "darwin_aarch64": { "missing": [] },
+                "darwin_x86_64": { "missing": [] },
+                "linux_x86_64": { "missing": ["signature"] },
+                "windows_x86_64": { "missing": ["binary"] },
+            })
+        );
+
+        let missing = test_support::get(&state, "/versions/2.0.0/status").await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn serves_minisign_and_pgp_signatures_apart() {
+        let pgp = "-----BEGIN PGP SIGNATURE-----\n\niQEzBAABCAAdFiEE\n=abcd\n-----END PGP SIGNATURE-----\n";
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .write("1.0.0/linux_x86_64/app-linux_x86_64.tar.gz.asc", pgp)
+            .write(
+                "1.0.0/windows_x86_64/app-windows_x86_64.tar.gz.asc",
+                test_support::SIGNATURE,
+            );
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let details = test_support::json(test_support::get(&state, "/versions/1.0.0").await).await;
+
+        let linux = &details["platforms"]["linux_x86_64"];
+        assert_eq!(linux["signature"], test_support::SIGNATURE);
+        assert_eq!(linux["pgp_signature"], pgp);
+        assert!(linux["url"]
+            .as_str()
+            .unwrap()
+            .ends_with("/app-linux_x86_64.tar.gz"));
+        // a minisign signature under `.asc` is neither pgp nor the artifact
+        let windows = &details["platforms"]["windows_x86_64"];
+        assert_eq!(windows["signature"], test_support::SIGNATURE);
+        assert_eq!(windows["pgp_signature"], serde_json::Value::Null);
+        assert!(windows["url"]
+            .as_str()
+            .unwrap()
+            .ends_with("/app-windows_x86_64.tar.gz"));
+        assert_eq!(details["complete"], true);
+    }
+
+    #[tokio::test]
+    async fn lists_the_notes_newer_than_since() {
+        let kiosk = Kiosk::new();
+        for version in ["1.0.0", "1.1.0", "1.2.0", "2.0.0"] {
+            kiosk.add_version(version);
+        }
+        kiosk.add_version("2.1.0");
+        std::fs::remove_file(
+            kiosk
+                .path()
+                .join("2.1.0/linux_x86_64/app-linux_x86_64.tar.gz.sig"),
+        )
+        .unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let changelog = |uri: &'static str| async {
+            let response = test_support::get(&state, uri).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            test_support::json(response).await
+        };
+
+        let since = changelog("/changelog?since=1.1.0").await;
+        let entries: Vec<(&str, &str)> = since
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                (
+                    entry["version"].as_str().unwrap(),
+                    entry["notes"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        // 2.1.0 is incomplete and left out
+        assert_eq!(
+            entries,
+            [("2.0.0", "notes 2.0.0"), ("1.2.0", "notes 1.2.0")]
+        );
+
+        assert_eq!(changelog("/changelog").await.as_array().unwrap().len(), 4);
+        assert_eq!(
+            changelog("/changelog?since=2.0.0").await,
+            serde_json::json!([])
+        );
+        assert_eq!(
+            test_support::get(&state, "/changelog?since=latest")
+                .await
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_notes_and_signatures_as_inline_text() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        std::fs::remove_file(
+            kiosk
+                .path()
+                .join("1.0.0/windows_x86_64/app-windows_x86_64.tar.gz.sig"),
+        )
+        .unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        for (uri, body) in [
+            ("/versions/1.0.0/notes", "notes 1.0.0"),
+            (
+                "/versions/1.0.0/linux_x86_64/signature",
+                test_support::SIGNATURE,
+            ),
+        ] {
+            let response = test_support::get(&state, uri).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+            assert_eq!(
+                response.headers()[header::CONTENT_TYPE],
+                "text/plain; charset=utf-8",
+                "{}",
+                uri
+            );
+            assert_eq!(
+                response.headers()[header::CONTENT_DISPOSITION],
+                "inline",
+                "{}",
+                uri
+            );
+            assert_eq!(test_support::body(response).await, body);
+        }
+
+        let unsigned = test_support::get(&state, "/versions/1.0.0/windows_x86_64/signature").await;
+        assert_eq!(unsigned.status(), StatusCode::NOT_FOUND);
+
+        let artifact = test_support::download(
+            &state,
+            axum::http::Method::GET,
+            "linux_x86_64",
+            "1.0.0",
+            "app-linux_x86_64.tar.gz",
+        )
+        .await
+        .unwrap();
+        assert!(artifact.headers()[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .starts_with("attachment"));
+    }
+
+    #[tokio::test]
+    async fn diffs_the_artifacts_of_two_versions() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0").write(
+            "1.1.0/linux_x86_64/app-linux_x86_64.tar.gz",
+            "bin 1.0.0 linux_x86_64",
+        );
+        std::fs::remove_dir_all(kiosk.path().join("1.0.0/darwin_aarch64")).unwrap();
+        std::fs::remove_dir_all(kiosk.path().join("1.1.0/darwin_x86_64")).unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let diff =
+            test_support::json(test_support::get(&state, "/diff?from=1.0.0&to=1.1.0").await).await;
+
+        let change = |platform: &str| diff["platforms"][platform]["change"].clone();
+        assert_eq!(change("darwin_aarch64"), "added");
+        assert_eq!(change("darwin_x86_64"), "removed");
+        assert_eq!(change("linux_x86_64"), "unchanged");
+        // same size, different content
+        assert_eq!(change("windows_x86_64"), "changed");
+        assert_eq!(
+            diff["platforms"]["darwin_aarch64"]["from"],
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            diff["platforms"]["darwin_x86_64"]["from"],
+            serde_json::json!({
+                "filename": "app-darwin_x86_64.tar.gz",
+                "size": "bin 1.0.0 darwin_x86_64".len(),
+            })
+        );
+        assert_eq!(
+            diff["platforms"]["darwin_x86_64"]["to"],
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            diff["notes"],
+            serde_json::json!({ "changed": true, "from": "notes 1.0.0", "to": "notes 1.1.0" })
+        );
+
+        let missing = test_support::get(&state, "/diff?from=1.0.0&to=9.0.0").await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn tells_an_absent_platform_from_a_broken_one() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("1.1.0");
+        std::fs::remove_dir_all(kiosk.path().join("1.1.0/darwin_aarch64")).unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let details = test_support::json(test_support::get(&state, "/versions/1.1.0").await).await;
+        let absent = &details["platforms"]["darwin_aarch64"];
+        assert_eq!(absent["available"], false);
+        assert_eq!(absent["url"], serde_json::Value::Null);
+        assert_eq!(details["platforms"]["linux_x86_64"]["available"], true);
+        assert_eq!(details["complete"], true);
+
+        // tauri clients get a manifest without the absent target
+        let latest = test_support::json(test_support::get(&state, "/latest-version").await).await;
+        assert_eq!(latest["version"], "1.1.0");
+        let targets = latest["platforms"].as_object().unwrap();
+        assert!(!targets.contains_key("darwin-aarch64"));
+        assert_eq!(targets.len(), 3);
+
+        // an existing but empty folder is a broken build instead
+        std::fs::create_dir(kiosk.path().join("1.1.0/darwin_aarch64")).unwrap();
+        let details = test_support::json(test_support::get(&state, "/versions/1.1.0").await).await;
+        let broken = &details["platforms"]["darwin_aarch64"];
+        assert_eq!(broken["available"], true);
+        assert_eq!(
+            broken["missing"],
+            serde_json::json!(["binary", "signature"])
+        );
+        assert_eq!(details["complete"], false);
+    }
+
+    #[tokio::test]
+    async fn lists_the_mirrors_after_the_primary_url() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let state = AppState::build(kiosk.config(&[
+            ("KIOSK_DOWNLOADABLE_URL", "https://updates.example.com"),
+            (
+                "MIRROR_BASE_URLS",
+                "https://eu.mirror.example.com/kiosk/, https://us.mirror.example.com",
+            ),
+        ]))
+        .await
+        .unwrap();
+
+        let details = test_support::json(test_support::get(&state, "/versions/1.0.0").await).await;
+
+        let linux = &details["platforms"]["linux_x86_64"];
+        assert_eq!(
+            linux["url"],
+            "https://updates.example.com/download/1.0.0/linux_x86_64/app-linux_x86_64.tar.gz"
+        );
+        assert_eq!(
+            linux["mirrors"],
+            serde_json::json!([
+                "https://eu.mirror.example.com/kiosk/1.0.0/linux_x86_64/app-linux_x86_64.tar.gz",
+                "https://us.mirror.example.com/1.0.0/linux_x86_64/app-linux_x86_64.tar.gz",
+            ])
+        );
+
+        let without = AppState::build(kiosk.config(&[])).await.unwrap();
+        let details =
+            test_support::json(test_support::get(&without, "/versions/1.0.0").await).await;
+        assert_eq!(
+            details["platforms"]["linux_x86_64"]["mirrors"],
+            serde_json::json!([])
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_unconfigured_platform_folders_in_the_details() {
+        let kiosk = Kiosk::new();
+        kiosk
+            .add_version("1.0.0")
+            .add_platform("1.0.0", "linux_riscv64")
+            .write("1.0.0/freebsd_x86_64/app.tar.gz", "bin freebsd");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let details = test_support::json(test_support::get(&state, "/versions/1.0.0").await).await;
+
+        assert_eq!(
+            details["extra_platforms"],
+            serde_json::json!({
+                "freebsd_x86_64": {
+                    "artifact": "app.tar.gz",
+                    "size": "bin freebsd".len(),
+                    "signed": false,
+                },
+                "linux_riscv64": {
+                    "artifact": "app-linux_riscv64.tar.gz",
+                    "size": "bin 1.0.0 linux_riscv64".len(),
+                    "signed": true,
+                },
+            })
+        );
+        assert!(details["platforms"].get("linux_riscv64").is_none());
+        let latest = test_support::json(test_support::get(&state, "/latest-version").await).await;
+        assert!(latest["platforms"].get("linux-riscv64").is_none());
+        assert_eq!(latest["platforms"].as_object().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn refresh_evicts_only_the_manifests_it_can_change() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0").add_version("2.0.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let cache = &state.latest_version_cache;
+        let latest = resolve_latest_version(&state.config).await.unwrap();
+        let mut older = latest.clone();
+        older.version = "1.0.0".to_string();
+        let older_key = ManifestKey {
+            as_of: None,
+            languages: vec!["de".to_string()],
+        };
+        cache.set(ManifestKey::default(), latest).await;
+        cache.set(older_key.clone(), older).await;
+        let refresh = |version: &'static str| {
+            test_support::send(
+                &state,
+                axum::http::Request::post(format!("/admin/refresh/{}", version))
+                    .body(axum::body::Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        // 1.0.0 can't outrank the cached 2.0.0
+        let response = refresh("1.0.0").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test_support::json(response).await["version"], "1.0.0");
+        assert!(!cache.is_populated(&older_key).await);
+        assert!(cache.is_populated(&ManifestKey::default()).await);
+
+        assert_eq!(refresh("2.0.0").await.status(), StatusCode::OK);
+        assert!(!cache.is_populated(&ManifestKey::default()).await);
+
+        assert_eq!(refresh("3.0.0").await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn reports_pub_date_in_utc_and_the_configured_zone() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let details = |vars: &'static [(&'static str, &'static str)]| {
+            let config = kiosk.config(vars);
+            async move {
+                let state = AppState::build(config).await.unwrap();
+                test_support::json(test_support::get(&state, "/versions/1.0.0").await).await
+            }
+        };
+
+        let utc = details(&[]).await;
+        let pub_date = utc["pub_date"].as_str().unwrap();
+        assert!(pub_date.ends_with("+00:00"), "{}", pub_date);
+        assert!(utc.get("pub_date_local").is_none());
+
+        // no daylight saving, always seven hours ahead
+        let local = details(&[("PUB_DATE_TZ", "Asia/Jakarta")]).await;
+        let pub_date_local = local["pub_date_local"].as_str().unwrap();
+        assert!(pub_date_local.ends_with("+07:00"), "{}", pub_date_local);
+        assert_eq!(local["pub_date"], pub_date);
+        assert_eq!(
+            DateTime::parse_from_rfc3339(pub_date_local).unwrap(),
+            DateTime::parse_from_rfc3339(pub_date).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn indexes_the_urls_of_every_built_platform() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        let folder = kiosk.path().join("1.0.0");
+        std::fs::remove_dir_all(folder.join("darwin_x86_64")).unwrap();
+        std::fs::remove_file(folder.join("windows_x86_64/app-windows_x86_64.tar.gz.sig")).unwrap();
+        let state = AppState::build(
+            kiosk.config(&[("KIOSK_DOWNLOADABLE_URL", "https://updates.example.com")]),
+        )
+        .await
+        .unwrap();
+
+        let urls =
+            test_support::json(test_support::get(&state, "/versions/1.0.0/urls").await).await;
+
+        let download = |file: &str| format!("https://updates.example.com/download/1.0.0/{}", file);
+        assert_eq!(
+            urls,
+            serde_json::json!({
+                "darwin_aarch64": {
+                    "binary": download("darwin_aarch64/app-darwin_aarch64.tar.gz"),
+                    "signature": download("darwin_aarch64/app-darwin_aarch64.tar.gz.sig"),
+                },
+                "linux_x86_64": {
+                    "binary": download("linux_x86_64/app-linux_x86_64.tar.gz"),
+                    "signature": download("linux_x86_64/app-linux_x86_64.tar.gz.sig"),
+                },
+                "windows_x86_64": {
+                    "binary": download("windows_x86_64/app-windows_x86_64.tar.gz"),
+                    "signature": null,
+                },
+            })
+        );
+
+        let missing = test_support::get(&state, "/versions/2.0.0/urls").await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn latest_status_follows_the_served_version() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let nothing = test_support::get(&state, "/versions/latest/status").await;
+        assert_eq!(nothing.status(), StatusCode::NO_CONTENT);
+
+        kiosk.add_version("1.0.0");
+        kiosk.add_version("1.1.0");
+        // an unsigned build keeps 1.1.0 from being served
+        std::fs::remove_file(
+            kiosk
+                .path()
+                .join("1.1.0/linux_x86_64/app-linux_x86_64.tar.gz.sig"),
+        )
+        .unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let served = test_support::json(test_support::get(&state, "/latest-version").await).await;
+        let status =
+            test_support::json(test_support::get(&state, "/versions/latest/status").await).await;
+
+        assert_eq!(served["version"], "1.0.0");
+        assert_eq!(status["version"], served["version"]);
+        assert_eq!(status["healthy"], true);
+        assert_eq!(
+            status["platforms"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            [
+                "darwin_aarch64",
+                "darwin_x86_64",
+                "linux_x86_64",
+                "windows_x86_64"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_status_leaves_out_missing_versions() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.add_version("1.1.0");
+        std::fs::remove_file(
+            kiosk
+                .path()
+                .join("1.1.0/linux_x86_64/app-linux_x86_64.tar.gz.sig"),
+        )
+        .unwrap();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let Json(statuses) = get_batch_status(
+            State(state.clone()),
+            Json(vec![
+                "1.0.0".into(),
+                "2.0.0".into(),
+                "1.1.0".into(),
+                "1.0.0".into(),
+            ]),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(statuses.keys().collect::<Vec<_>>(), ["1.0.0", "1.1.0"]);
+        assert!(statuses["1.0.0"].complete);
+        assert!(!statuses["1.1.0"].complete);
+    }
+
+    #[tokio::test]
+    async fn batch_status_refuses_too_many_versions() {
+        let kiosk = Kiosk::new();
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let versions = |count: usize| (0..count).map(|n| format!("1.0.{}", n)).collect();
+
+        let at_limit = get_batch_status(State(state.clone()), Json(versions(MAX_BATCH_VERSIONS)))
+            .await
+            .unwrap();
+        assert!(at_limit.0.is_empty());
+
+        let response = get_batch_status(State(state), Json(versions(MAX_BATCH_VERSIONS + 1)))
+            .await
+            .map(|_| ())
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            test_support::json(response).await,
+            serde_json::json!({
+                "kiosk_version_error": {
+                    "code": "TooManyVersions",
+                    "data": { "max": MAX_BATCH_VERSIONS },
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn channels_tell_a_newer_prerelease_from_stable() {
+        let kiosk = Kiosk::new();
+        kiosk.add_version("1.0.0");
+        kiosk.add_version("1.1.0-beta.1");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+
+        let channels =
+            test_support::json(test_support::get(&state, "/latest-version/channels").await).await;
+        let served = test_support::json(test_support::get(&state, "/latest-version").await).await;
+
+        assert_eq!(channels["stable"]["version"], "1.0.0");
+        assert_eq!(channels["latest"]["version"], "1.1.0-beta.1");
+        assert_eq!(channels["latest"]["version"], served["version"]);
+
+        // a stable release past the prerelease is both
+        kiosk.add_version("1.1.0");
+        let state = AppState::build(kiosk.config(&[])).await.unwrap();
+        let channels =
+            test_support::json(test_support::get(&state, "/latest-version/channels").await).await;
+        assert_eq!(channels["stable"]["version"], "1.1.0");
+        assert_eq!(channels["latest"], channels["stable"]);
+    }
+}
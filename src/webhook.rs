@@ -0,0 +1,311 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use axum::{extract::State, Json};
+use sea_orm::sqlx::types::chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+
+use crate::{config::Config, error::APIError, state::AppState};
+
+// publishes whose webhook never got through, one json event per line, kept in
+// KIOSK_DIRECTORY until POST /admin/replay-webhooks delivers them
+pub const FAILURES_FILE: &str = ".webhook-failures.jsonl";
+
+// longest wait between two attempts however large the backoff or retry count
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishedVersion {
+    pub version: String,
+    pub notes: String,
+    pub pub_date: String,
+    pub platforms: Vec<String>,
+}
+
+impl PublishedVersion {
+    pub fn new(version: &str, notes: &str, platforms: &[String]) -> Self {
+        Self {
+            version: version.to_string(),
+            notes: notes.to_string(),
+            pub_date: Utc::now().to_rfc3339(),
+            platforms: platforms.to_vec(),
+        }
+    }
+}
+
+// posts every successful publish to PUBLISH_WEBHOOK_URL so downstream
+// automation (cache purge, chat notices) can pick it up
+#[derive(Clone)]
+pub struct PublishWebhook {
+    url: String,
+    client: reqwest::Client,
+    retries: u32,
+    backoff: Duration,
+    failures: PathBuf,
+    // replaying rewrites the failures file, appends wait for it
+    failures_lock: Arc<Mutex<()>>,
+}
+
+#[derive(Serialize)]
+pub struct ReplayResponse {
+    pub delivered: usize,
+    pub failed: usize,
+}
+
+impl PublishWebhook {
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let url = config.publish_webhook_url.clone()?;
+        let client = reqwest::Client::builder()
+            .timeout(config.publish_webhook_timeout)
+            .build()
+            .inspect_err(|e| tracing::error!("failed to build webhook client: {}", e))
+            .ok()?;
+
+        Some(Self {
+            url,
+            client,
+            retries: config.publish_webhook_retries,
+            backoff: config.publish_webhook_backoff,
+            failures: std::path::Path::new(&config.kiosk_directory).join(FAILURES_FILE),
+            failures_lock: Arc::new(Mutex::new(())),
+        })
+    }
+
+    // fire and forget, the publish response never waits for the webhook
+    pub fn notify(&self, payload: PublishedVersion) {
+        let webhook = self.clone();
+        tokio::spawn(async move {
+            let attempts = webhook.retries + 1;
+            for attempt in 1..=attempts {
+                match webhook.send(&payload).await {
+                    Ok(_) => {
+                        tracing::info!("sent publish webhook for {}", payload.version);
+                        return;
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "publish webhook for {} failed (attempt {} of {}): {}",
+                            payload.version,
+                            attempt,
+                            attempts,
+                            e
+                        );
+                    }
+                }
+                if attempt < attempts {
+                    tokio::time::sleep(backoff_delay(webhook.backoff, attempt)).await;
+                }
+            }
+            tracing::error!(
+                "giving up on the publish webhook for {}, kept in {}",
+                payload.version,
+                FAILURES_FILE
+            );
+            if let Err(e) = webhook.record_failure(&payload).await {
+                tracing::error!("failed to record the failed webhook: {}", e);
+            }
+        });
+    }
+
+    async fn send(&self, payload: &PublishedVersion) -> reqwest::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+    }
+
+    async fn record_failure(&self, payload: &PublishedVersion) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(payload)?;
+        line.push(b'\n');
+
+        let _lock = self.failures_lock.lock().await;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.failures)
+            .await?;
+        file.write_all(&line).await?;
+        file.sync_all().await
+    }
+
+    // sends every recorded failure once, the ones that fail again stay
+    async fn replay(&self) -> std::io::Result<ReplayResponse> {
+        let _lock = self.failures_lock.lock().await;
+        let content = match fs::read_to_string(&self.failures).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let mut delivered = 0;
+        let mut remaining = String::new();
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            let Ok(payload) = serde_json::from_str::<PublishedVersion>(line) else {
+                tracing::warn!("dropping unreadable line of {}", FAILURES_FILE);
+                continue;
+            };
+            match self.send(&payload).await {
+                Ok(_) => {
+                    tracing::info!("replayed publish webhook for {}", payload.version);
+                    delivered += 1;
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "replaying the publish webhook for {} failed: {}",
+                        payload.version,
+                        e
+                    );
+                    remaining.push_str(line);
+                    remaining.push('\n');
+                }
+            }
+        }
+
+        let failed = remaining.lines().count();
+        if remaining.is_empty() {
+            match fs::remove_file(&self.failures).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        } else {
+            fs::write(&self.failures, remaining).await?;
+        }
+
+        Ok(ReplayResponse { delivered, failed })
+    }
+}
+
+// the wait after failed attempt `attempt`, doubling from `backoff`
+fn backoff_delay(backoff: Duration, attempt: u32) -> Duration {
+    backoff
+        .checked_mul(1 << attempt.saturating_sub(1).min(16))
+        .unwrap_or(MAX_BACKOFF)
+        .min(MAX_BACKOFF)
+}
+
+// 404 without PUBLISH_WEBHOOK_URL since there is nowhere to replay to
+pub async fn replay_webhooks(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ReplayResponse>, APIError> {
+    let webhook = state.publish_webhook.as_ref().ok_or(APIError::NotFound)?;
+    let response = webhook
+        .replay()
+        .await
+        .inspect_err(|e| tracing::error!("failed to replay {}: {}", FAILURES_FILE, e))?;
+
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Kiosk;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let second = Duration::from_secs(1);
+        assert_eq!(backoff_delay(second, 1), second);
+        assert_eq!(backoff_delay(second, 2), 2 * second);
+        assert_eq!(backoff_delay(second, 5), 16 * second);
+        assert_eq!(backoff_delay(second, 40), MAX_BACKOFF);
+        assert_eq!(backoff_delay(Duration::MAX, 3), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn a_failing_receiver_leaves_the_event_for_replay() {
+        // nothing listens on the port once the listener is dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let kiosk = Kiosk::new();
+        let url = format!("http://127.0.0.1:{}/hook", port);
+        let config = kiosk.config(&[
+            ("PUBLISH_WEBHOOK_URL", &url),
+            ("PUBLISH_WEBHOOK_RETRIES", "1"),
+            ("PUBLISH_WEBHOOK_BACKOFF_MS", "1"),
+        ]);
+        let webhook = PublishWebhook::from_config(&config).unwrap();
+
+        webhook.notify(PublishedVersion::new("1.0.0", "notes", &config.platforms));
+        let failures = kiosk.path().join(FAILURES_FILE);
+        for _ in 0..500 {
+            // a whole line, not just the created file
+            if std::fs::read_to_string(&failures).is_ok_and(|content| content.ends_with('\n')) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let recorded: PublishedVersion =
+            serde_json::from_str(std::fs::read_to_string(&failures).unwrap().trim()).unwrap();
+        assert_eq!(recorded.version, "1.0.0");
+
+        let replay = webhook.replay().await.unwrap();
+        assert_eq!((replay.delivered, replay.failed), (0, 1));
+    }
+
+    // a receiver on a free local port, every posted event comes out of the
+    // channel
+    async fn receiver() -> (
+        String,
+        tokio::sync::mpsc::UnboundedReceiver<PublishedVersion>,
+    ) {
+        let (sender, events) = tokio::sync::mpsc::unbounded_channel();
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(move |Json(event): Json<PublishedVersion>| async move {
+                sender.send(event).unwrap();
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        (url, events)
+    }
+
+    #[tokio::test]
+    async fn posts_the_published_version_to_the_receiver() {
+        let (url, mut events) = receiver().await;
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("PUBLISH_WEBHOOK_URL", &url)]);
+        let webhook = PublishWebhook::from_config(&config).unwrap();
+
+        webhook.notify(PublishedVersion::new("1.0.0", "notes", &config.platforms));
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.version, "1.0.0");
+        assert_eq!(event.notes, "notes");
+        assert_eq!(event.platforms, config.platforms.to_vec());
+        assert!(
+            sea_orm::sqlx::types::chrono::DateTime::parse_from_rfc3339(&event.pub_date).is_ok()
+        );
+        assert!(!kiosk.path().join(FAILURES_FILE).exists());
+    }
+
+    #[tokio::test]
+    async fn replay_delivers_recorded_failures() {
+        let (url, mut events) = receiver().await;
+        let kiosk = Kiosk::new();
+        let config = kiosk.config(&[("PUBLISH_WEBHOOK_URL", &url)]);
+        let webhook = PublishWebhook::from_config(&config).unwrap();
+        webhook
+            .record_failure(&PublishedVersion::new("1.0.0", "notes", &[]))
+            .await
+            .unwrap();
+
+        let replay = webhook.replay().await.unwrap();
+
+        assert_eq!((replay.delivered, replay.failed), (1, 0));
+        assert_eq!(events.recv().await.unwrap().version, "1.0.0");
+        assert!(!kiosk.path().join(FAILURES_FILE).exists());
+    }
+}